@@ -0,0 +1,41 @@
+//! Colors of terminal output, which are left out unless the `colored` feature is enabled
+
+#[cfg(feature = "colored")]
+pub use colored::Colorize;
+
+#[cfg(not(feature = "colored"))]
+pub trait Colorize: ToString + Sized {
+    fn red(self) -> String {
+        self.to_string()
+    }
+
+    fn green(self) -> String {
+        self.to_string()
+    }
+
+    fn yellow(self) -> String {
+        self.to_string()
+    }
+
+    fn cyan(self) -> String {
+        self.to_string()
+    }
+
+    fn dimmed(self) -> String {
+        self.to_string()
+    }
+
+    fn blue(self) -> String {
+        self.to_string()
+    }
+
+    fn bold(self) -> String {
+        self.to_string()
+    }
+}
+
+#[cfg(not(feature = "colored"))]
+impl Colorize for &str {}
+
+#[cfg(not(feature = "colored"))]
+impl Colorize for String {}
@@ -0,0 +1,214 @@
+//! Errors pointing at the part of the input they come from
+
+use crate::{
+    color::Colorize,
+    interpreter::{
+        env::{Environment, Function, NamedItem},
+        EvalError,
+    },
+};
+use std::{ops::Range, path::Path};
+
+/// Stable number identifying syntax errors, alongside those of `EvalError::code`
+pub const SYNTAX_ERROR: u32 = 1;
+
+/// Error pointing at a part of a script
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// Kind of the error, such as `syntax` or `reference`, named as in machine mode
+    pub kind: &'static str,
+    pub code: u32,
+    pub message: String,
+    pub span: Range<usize>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    /// Diagnoses a syntax error at the byte offset `position`
+    pub fn from_parse_error(err: &impl ToString, source: &str, position: usize) -> Self {
+        let message = err.to_string();
+        let mut lines = message.lines().map(str::trim).filter(|x| !x.is_empty());
+        lines.next();
+        let end = source[position..]
+            .chars()
+            .next()
+            .map_or(position, |c| position + c.len_utf8());
+        Self {
+            kind: "syntax",
+            code: SYNTAX_ERROR,
+            message: "Syntax error".to_string(),
+            span: position..end,
+            notes: lines.map(str::to_string).collect(),
+            help: None,
+        }
+    }
+
+    /// Diagnoses an error that occurred while executing the statement at `span`
+    pub fn from_eval_error(
+        err: &EvalError,
+        source: &str,
+        span: Range<usize>,
+        env: &Environment,
+    ) -> Self {
+        let (span, help) = match err {
+            EvalError::ReferenceError(ident) => {
+                let help =
+                    closest_name(&ident.0, env).map(|name| format!("did you mean {}?", name));
+                (find_word(source, span, &ident.0), help)
+            }
+            EvalError::ArityError {
+                name,
+                expected,
+                got,
+            } => {
+                let help = match env.iter().find(|(x, _)| x.0 == *name) {
+                    Some((_, NamedItem::Function(Function::UserDefined { params, .. }))) => {
+                        format!(
+                            "{} is defined as {}({})",
+                            name,
+                            name,
+                            params
+                                .iter()
+                                .map(|x| x.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )
+                    }
+                    _ if *got < expected.min => plural(expected.min - got, "add", "more"),
+                    _ => plural(got - expected.max.unwrap_or(*got), "remove", "extra"),
+                };
+                (find_word(source, span, name), Some(help))
+            }
+            _ => (span, None),
+        };
+        Self {
+            kind: err.kind(),
+            code: err.code(),
+            message: err.to_string(),
+            span,
+            notes: Vec::new(),
+            help,
+        }
+    }
+
+    /// Renders the diagnostic with the line of `source` it points at. `source` starts at
+    /// the line numbered `first_line` in the file at `path`, or is input typed in if `path`
+    /// is `None`.
+    pub fn render(&self, path: Option<&Path>, source: &str, first_line: usize) -> String {
+        let start = self.span.start.min(source.len());
+        let (line_start, line_end, line_number, column) = locate(source, start, first_line);
+        let line = source[line_start..line_end].trim_end_matches('\r');
+        let width = source[start..self.span.end.clamp(start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+
+        let gutter = " ".repeat(line_number.to_string().len());
+        let mut lines = vec![format!(
+            "{}: {}",
+            "error".red().bold(),
+            self.message.as_str().bold()
+        )];
+        if let Some(path) = path {
+            lines.push(format!(
+                "{}{} {}:{}:{}",
+                gutter,
+                "-->".blue(),
+                path.display(),
+                line_number,
+                column + 1
+            ));
+        }
+        lines.extend([
+            format!("{} {}", gutter, "|".blue()),
+            format!("{} {} {}", line_number.to_string().blue(), "|".blue(), line),
+            format!(
+                "{} {} {}{}",
+                gutter,
+                "|".blue(),
+                " ".repeat(column),
+                "^".repeat(width).red()
+            ),
+        ]);
+        for note in &self.notes {
+            lines.push(format!("{} {} {}", gutter, "=".blue(), note));
+        }
+        if let Some(help) = &self.help {
+            lines.push(format!("{} {} help: {}", gutter, "=".blue(), help));
+        }
+        lines.join("\n")
+    }
+
+    /// Line and column, both starting at 1, the diagnostic points at in `source`, which starts
+    /// at the line numbered `first_line`
+    pub fn position(&self, source: &str, first_line: usize) -> (usize, usize) {
+        let start = self.span.start.min(source.len());
+        let (_, _, line_number, column) = locate(source, start, first_line);
+        (line_number, column + 1)
+    }
+}
+
+/// Finds the line of `source` containing the byte offset `offset`, returning the byte offsets
+/// where the line starts and ends, its number counted from `first_line`, and the column of
+/// `offset` in characters starting at 0
+fn locate(source: &str, offset: usize, first_line: usize) -> (usize, usize, usize, usize) {
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[offset..]
+        .find('\n')
+        .map_or(source.len(), |i| offset + i);
+    let line_number = source[..line_start].matches('\n').count() + first_line;
+    let column = source[line_start..offset].chars().count();
+    (line_start, line_end, line_number, column)
+}
+
+fn plural(n: usize, verb: &str, adjective: &str) -> String {
+    format!(
+        "{} {} {} {}",
+        verb,
+        n,
+        adjective,
+        if n == 1 { "argument" } else { "arguments" }
+    )
+}
+
+/// Narrows `span` down to the first occurrence of the word `word` in it
+fn find_word(source: &str, span: Range<usize>, word: &str) -> Range<usize> {
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+    let text = &source[span.clone()];
+    text.match_indices(word)
+        .find(|(i, _)| {
+            let before = text[..*i].chars().next_back();
+            let after = text[i + word.len()..].chars().next();
+            !before.is_some_and(is_name_char) && !after.is_some_and(is_name_char)
+        })
+        .map_or(span.clone(), |(i, _)| {
+            span.start + i..span.start + i + word.len()
+        })
+}
+
+/// Finds the defined name most similar to `name` within a small number of edits
+fn closest_name(name: &str, env: &Environment) -> Option<String> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    env.iter()
+        .map(|(x, _)| (edit_distance(name, &x.0), &x.0))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, x)| x.clone())
+}
+
+/// Levenshtein distance between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<_> = b.chars().collect();
+    let mut row: Vec<_> = (0..=b.len()).collect();
+    for (i, x) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(x != *y);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
@@ -1,11 +1,39 @@
+#[cfg(not(target_arch = "wasm32"))]
+pub mod data;
+#[cfg(feature = "statrs")]
+pub mod distributions;
 pub mod env;
+pub mod equations;
+pub mod format;
+pub mod modular;
+pub mod settings;
+pub mod statistics;
+pub mod trace;
+pub mod units;
+pub mod value;
+pub mod warning;
 
 use crate::language::{
-    BinaryOp, Expression, FunctionDefinition, Identifier, Number, Statement, UnaryOp,
+    self, BinaryOp, ConstantDefinition, Expression, FunctionDefinition, Identifier,
+    MultipleAssignment, Number, Parameter, Statement, Subscript, UnaryOp, UnitDefinition,
     VariableAssignment,
 };
-use env::{Environment, Function};
+use combine::easy;
+use env::{Arity, Environment, Field, Function, NamedItem};
+use itertools::Itertools;
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive, Zero};
+use std::{
+    cmp::Ordering,
+    convert::TryFrom,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 use thiserror::Error;
+use trace::{Event, Trace};
+use units::{Quantity, Unit};
+use value::{FunctionValue, Value};
+use warning::Warning;
 
 #[derive(Error, Debug)]
 pub enum EvalError {
@@ -18,50 +46,331 @@ pub enum EvalError {
     #[error("Unknown identifier {0}")]
     ReferenceError(Identifier),
 
-    #[error("The function {name} takes {expected} {} but {got} {} supplied",
-            if *.expected == 1 { "argument" } else { "arguments" },
+    #[error("The function {name} takes {expected} but {got} {} supplied",
             if *.got == 1 { "was" } else { "were" }
         )]
     ArityError {
         name: String,
-        expected: usize,
+        expected: Arity,
         got: usize,
     },
 
     #[error("{0}")]
     DefinitionError(String),
+
+    #[error("Cannot assign {got} {} to {expected} {}",
+            if *.got == 1 { "value" } else { "values" },
+            if *.expected == 1 { "variable" } else { "variables" }
+        )]
+    DestructuringError { expected: usize, got: usize },
+
+    #[error("{0}")]
+    IncludeError(String),
+
+    #[error("The result of {function}({input}) is too large to represent")]
+    OverflowError { function: String, input: Number },
+
+    #[error("{message}")]
+    DimensionError {
+        message: String,
+        left: units::Dimension,
+        right: units::Dimension,
+    },
+
+    #[error("Assertion failed: {expr}{detail}")]
+    AssertionError {
+        /// Text of the failed assertion, filled in where the assertion is called
+        expr: String,
+        detail: String,
+    },
+
+    #[error("Evaluation took more than {0} steps")]
+    StepLimitError(usize),
+}
+
+impl EvalError {
+    /// Stable number identifying the kind of error
+    pub fn code(&self) -> u32 {
+        match self {
+            Self::NumericalError(_) => 10,
+            Self::TypeError(_) => 11,
+            Self::ReferenceError(_) => 12,
+            Self::ArityError { .. } => 13,
+            Self::DefinitionError(_) => 14,
+            Self::DestructuringError { .. } => 15,
+            Self::IncludeError(_) => 16,
+            Self::AssertionError { .. } => 17,
+            Self::OverflowError { .. } => 18,
+            Self::DimensionError { .. } => 19,
+            Self::StepLimitError(_) => 20,
+        }
+    }
+
+    /// Stable name of the kind of error
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::NumericalError(_) => "numerical",
+            Self::TypeError(_) => "type",
+            Self::ReferenceError(_) => "reference",
+            Self::ArityError { .. } => "arity",
+            Self::DefinitionError(_) => "definition",
+            Self::DestructuringError { .. } => "destructuring",
+            Self::IncludeError(_) => "include",
+            Self::AssertionError { .. } => "assertion",
+            Self::OverflowError { .. } => "overflow",
+            Self::DimensionError { .. } => "dimension",
+            Self::StepLimitError(_) => "limit",
+        }
+    }
 }
 
 pub type EvalResult<T> = Result<T, EvalError>;
 
-pub fn exec_stmt(stmt: &Statement, env: &mut Environment) -> EvalResult<Option<Number>> {
+pub fn exec_stmt(stmt: &Statement, env: &mut Environment) -> EvalResult<Option<Value>> {
     let value = match stmt {
         Statement::Expression(expr) => Some(eval_expr_global(expr, env)?),
         Statement::VariableAssignment(VariableAssignment { name, expr }) => {
+            check_shadowing(name, env);
             let evaluated = eval_expr_global(expr, env)?;
-            env.assign_var(name, evaluated)?;
+            check_overwrite(name, &evaluated, env);
+            env.assign_var(name, evaluated.clone())?;
             Some(evaluated)
         }
-        Statement::FunctionDefinition(FunctionDefinition {
-            name,
-            arg_names,
-            expr,
-        }) => {
-            env.def_func(name, arg_names, expr)?;
+        Statement::MultipleAssignment(MultipleAssignment { names, exprs }) => {
+            if let [expr] = &exprs[..] {
+                return destructure(names, expr, env);
+            }
+            if names.len() != exprs.len() {
+                return Err(EvalError::DestructuringError {
+                    expected: names.len(),
+                    got: exprs.len(),
+                });
+            }
+            if let Some(dup) = env::find_duplicate(names) {
+                return Err(EvalError::DefinitionError(format!(
+                    "Duplicate variable {}",
+                    dup
+                )));
+            }
+
+            // evaluate every right-hand side before assigning so that `a, b = b, a` swaps
+            let values = exprs
+                .iter()
+                .map(|expr| eval_expr_global(expr, env))
+                .collect::<EvalResult<Vec<_>>>()?;
+            for (name, value) in names.iter().zip(values) {
+                check_shadowing(name, env);
+                check_overwrite(name, &value, env);
+                env.assign_var(name, value)?;
+            }
+            None
+        }
+        Statement::ConstantDefinition(ConstantDefinition { name, expr }) => {
+            check_shadowing(name, env);
+            let evaluated = eval_expr_global(expr, env)?;
+            env.declare_const(name, evaluated.clone())?;
+            Some(evaluated)
+        }
+        Statement::FunctionDefinition(FunctionDefinition { name, params, expr }) => {
+            check_shadowing(name, env);
+            if env.settings().strict {
+                check_params(name, params, expr, env)?;
+            }
+            env.def_func(name, params, expr)?;
+            None
+        }
+        Statement::UnitDefinition(UnitDefinition { name, expr }) => {
+            let quantity = match eval_expr_global(expr, env)? {
+                Value::Quantity(q) if q.value > 0.0 => q,
+                value => {
+                    return Err(EvalError::TypeError(format!(
+                        "A unit must be defined as a positive quantity, but got {}",
+                        value
+                    )))
+                }
+            };
+            env.def_unit(
+                name,
+                Unit {
+                    symbol: name.0.clone(),
+                    factor: quantity.value,
+                    dimension: quantity.dimension().clone(),
+                },
+            )?;
+            None
+        }
+        Statement::Override(stmt) => return env.with_override(|env| exec_stmt(stmt, env)),
+        Statement::Include(path) => {
+            env.check_file_access()?;
+            // relative paths are relative to the including file
+            let path = match env.current_file().and_then(Path::parent) {
+                Some(dir) => dir.join(path),
+                None => PathBuf::from(path),
+            };
+            exec_file(&path, env)?;
             None
         }
     };
 
-    if let Some(value) = value {
-        for name in &["ans", "_"] {
-            env.def_const(&Identifier(name.to_string()), value)?;
-        }
+    if let Some(value) = &value {
+        env.set_last_result(value.clone());
     }
 
     Ok(value)
 }
 
-fn eval_expr_global(expr: &Expression, env: &Environment) -> EvalResult<Number> {
+/// Assigns the elements of the list `expr` evaluates to, as in `a, b = [1, 2]`
+fn destructure(
+    names: &[Identifier],
+    expr: &Expression,
+    env: &mut Environment,
+) -> EvalResult<Option<Value>> {
+    if let Some(dup) = env::find_duplicate(names) {
+        return Err(EvalError::DefinitionError(format!(
+            "Duplicate variable {}",
+            dup
+        )));
+    }
+    let values = match eval_expr_global(expr, env)? {
+        Value::List(xs) if xs.len() == names.len() => xs,
+        Value::List(xs) => {
+            return Err(EvalError::DestructuringError {
+                expected: names.len(),
+                got: xs.len(),
+            })
+        }
+        _ => {
+            return Err(EvalError::DestructuringError {
+                expected: names.len(),
+                got: 1,
+            })
+        }
+    };
+    for (name, value) in names.iter().zip(values) {
+        check_shadowing(name, env);
+        check_overwrite(name, &value, env);
+        env.assign_var(name, value)?;
+    }
+    Ok(None)
+}
+
+/// Parses `input`, rejecting implicit multiplication if it is off
+pub fn parse<'a>(
+    input: &'a str,
+    env: &Environment,
+) -> Result<Vec<Statement>, easy::Errors<char, &'a str, usize>> {
+    if env.settings().implicit_mul() {
+        language::parse(input)
+    } else {
+        language::parse_explicit(input)
+    }
+}
+
+/// Same as `parse`, but along with the byte ranges of `input` the statements span
+#[allow(clippy::type_complexity)]
+pub fn parse_spanned<'a>(
+    input: &'a str,
+    env: &Environment,
+) -> Result<Vec<(Statement, std::ops::Range<usize>)>, easy::Errors<char, &'a str, usize>> {
+    if env.settings().implicit_mul() {
+        language::parse_spanned(input)
+    } else {
+        language::parse_spanned_explicit(input)
+    }
+}
+
+/// Same as `parse_spanned`, but reuses the statements `old_stmts` parsed from `old_input`
+/// on the lines of `input` before the first change, as `language::reparse` does
+#[allow(clippy::type_complexity)]
+pub fn reparse<'a>(
+    old_stmts: &[(Statement, std::ops::Range<usize>)],
+    old_input: &str,
+    input: &'a str,
+    env: &Environment,
+) -> Result<Vec<(Statement, std::ops::Range<usize>)>, easy::Errors<char, &'a str, usize>> {
+    if env.settings().implicit_mul() {
+        language::reparse(old_stmts, old_input, input)
+    } else {
+        language::reparse_explicit(old_stmts, old_input, input)
+    }
+}
+
+/// Executes the script in a file and returns the value of the last statement
+pub fn exec_file(path: &Path, env: &mut Environment) -> EvalResult<Option<Value>> {
+    let io_error =
+        |err: std::io::Error| EvalError::IncludeError(format!("{}: {}", path.display(), err));
+    let canonical = std::fs::canonicalize(path).map_err(io_error)?;
+    if env.is_running_file(&canonical) {
+        return Err(EvalError::IncludeError(format!(
+            "Circular include of {}",
+            path.display()
+        )));
+    }
+    let script = std::fs::read_to_string(path).map_err(io_error)?;
+    let stmts = parse(&script, env).map_err(|err| {
+        EvalError::IncludeError(format!("{}: {}", path.display(), err.to_string().trim()))
+    })?;
+
+    env.with_file(canonical, |env| {
+        stmts.iter().try_fold(None, |_, stmt| exec_stmt(stmt, env))
+    })
+}
+
+/// Evaluates an expression without changing the environment, recording how it was evaluated
+pub fn explain(expr: &Expression, env: &Environment) -> (EvalResult<Value>, Vec<Event>) {
+    let mut env = env.clone();
+    let trace = Trace::rewrites();
+    env.set_trace(Some(trace.clone()));
+    let result = eval_expr_global(expr, &env);
+    (result, trace.take())
+}
+
+/// Evaluates an expression without changing the environment
+pub fn eval_expr(expr: &Expression, env: &Environment) -> EvalResult<Value> {
+    eval_expr_global(expr, env)
+}
+
+/// Applies a unary operator to an evaluated operand
+pub fn apply_unary_op(op: UnaryOp, x: Value, env: &Environment) -> EvalResult<Value> {
+    let result = apply_unary(op, x.clone())?;
+    check_precision(&[x], &result, env);
+    check_finite(normalize(result, env)?)
+}
+
+/// Applies a binary operator to evaluated operands
+pub fn apply_binary_op(op: BinaryOp, a: Value, b: Value, env: &Environment) -> EvalResult<Value> {
+    let result = apply_binary_in(op, a.clone(), b.clone(), env)?;
+    check_precision(&[a, b], &result, env);
+    check_finite(normalize(result, env)?)
+}
+
+/// Calls a function with evaluated arguments
+pub fn call_func(name: &Identifier, args: &[Value], env: &Environment) -> EvalResult<Value> {
+    let (name, func, bound) = env.resolve_callee(name)?;
+    let args: Vec<_> = bound.iter().chain(args).cloned().collect();
+    let result = eval_func(name, func, &args, env)?;
+    check_precision(&args, &result, env);
+    check_finite(normalize(result, env)?)
+}
+
+/// Calls a function value with evaluated arguments
+pub fn call_value(func: &Value, args: &[Value], env: &Environment) -> EvalResult<Value> {
+    let func = match func {
+        Value::Function(func) => func,
+        _ => {
+            return Err(EvalError::TypeError(format!(
+                "Expected a function, but got {}",
+                func
+            )))
+        }
+    };
+    let args: Vec<_> = func.bound.iter().chain(args).cloned().collect();
+    let result = eval_func(&func.name, &func.func, &args, env)?;
+    check_precision(&args, &result, env);
+    check_finite(normalize(result, env)?)
+}
+
+fn eval_expr_global(expr: &Expression, env: &Environment) -> EvalResult<Value> {
     eval_expr_local(expr, env, env)
 }
 
@@ -69,75 +378,662 @@ fn eval_expr_local(
     expr: &Expression,
     local_env: &Environment,
     global_env: &Environment,
-) -> EvalResult<Number> {
+) -> EvalResult<Value> {
+    global_env.step()?;
+
+    // sub-expression with its operands evaluated, shown in explanations
+    let tracing = global_env.trace().is_some_and(Trace::records_rewrites);
+    let mut rewritten = None;
+
     let value = match expr {
-        Expression::Number(x) => *x,
-        Expression::Field(name) => local_env.resolve_field(name)?,
-        Expression::Function(name, xs) => {
-            let func = local_env.resolve_func(name)?;
-            let args = xs
+        Expression::Number(x) => Value::Number(*x),
+        Expression::String(x) => Value::String(x.clone()),
+        Expression::Quantity(x, suffix) => {
+            // variables take precedence over units, as they do where unit names stand alone
+            let name = Identifier::new(suffix);
+            if let Some(NamedItem::Field(_)) = local_env.user_item(&name) {
+                let product = Expression::BinaryOp(
+                    BinaryOp::Multiply,
+                    Box::new(Expression::Number(*x)),
+                    Box::new(Expression::Field(name)),
+                );
+                return eval_expr_local(&product, local_env, global_env);
+            }
+            let unit = units::find(suffix).ok_or_else(|| unknown_unit(suffix))?;
+            Value::Quantity(Quantity::new(x.0, unit))
+        }
+        Expression::Conversion(x, name) => {
+            let x = eval_expr_local(x, local_env, global_env)?;
+            if tracing {
+                rewritten = Some(format!("{} to {}", x, name));
+            }
+            match global_env.find_unit(&name.0) {
+                Some(unit) => units::convert(x, unit)?,
+                None => format::to_notation(&x, &name.0, global_env.settings().bits)
+                    .ok_or_else(|| unknown_unit(&name.0))??,
+            }
+        }
+        Expression::Field(name) => {
+            if tracing {
+                rewritten = Some(name.to_string());
+            }
+            local_env.resolve_field(name)?
+        }
+        Expression::Function(name, xs, named) => {
+            if let Some(value) = local_env.uncallable_value(name) {
+                return match &xs[..] {
+                    [x] if named.is_empty()
+                        && global_env.settings().call_multiplies
+                        && is_multipliable(value) =>
+                    {
+                        let product = Expression::BinaryOp(
+                            BinaryOp::Multiply,
+                            Box::new(Expression::Field(name.clone())),
+                            Box::new(x.clone()),
+                        );
+                        eval_expr_local(&product, local_env, global_env)
+                    }
+                    _ => Err(not_a_function(name, value, xs)),
+                };
+            }
+            let (name, func, bound) = local_env.resolve_callee(name)?;
+            let args = bound
                 .iter()
+                .cloned()
+                .map(Ok)
+                .chain(xs.iter().map(|x| eval_expr_local(x, local_env, global_env)))
+                .collect::<EvalResult<Vec<Value>>>()?;
+            let named = named
+                .iter()
+                .map(|(k, x)| Ok((k, eval_expr_local(x, local_env, global_env)?)))
+                .collect::<EvalResult<Vec<_>>>()?;
+            if tracing {
+                let named = named.iter().map(|(k, x)| format!("{} = {}", k, x));
+                rewritten = Some(format!(
+                    "{}({})",
+                    name,
+                    args.iter().map(ToString::to_string).chain(named).join(", ")
+                ));
+            }
+            let result = if named.is_empty() {
+                eval_func(name, func, &args, global_env)
+            } else {
+                eval_func_named(name, func, &args, &named, global_env)
+            };
+            let result = match result {
+                Err(EvalError::AssertionError { expr: text, detail }) if text.is_empty() => {
+                    return Err(EvalError::AssertionError {
+                        expr: expr.to_string(),
+                        detail,
+                    })
+                }
+                result => result?,
+            };
+            let args: Vec<_> = args
+                .into_iter()
+                .chain(named.into_iter().map(|(_, x)| x))
+                .collect();
+            check_precision(&args, &result, global_env);
+            result
+        }
+        Expression::List(xs) => Value::List(
+            xs.iter()
                 .map(|x| eval_expr_local(x, local_env, global_env))
-                .collect::<EvalResult<Vec<Number>>>()?;
-            eval_func(name, func, &args, global_env)?
+                .collect::<EvalResult<_>>()?,
+        ),
+        Expression::Subscript(x, subscript) => {
+            let x = eval_expr_local(x, local_env, global_env)?;
+            let xs = x.as_list()?;
+            let eval_bound = |bound: &Option<Box<Expression>>, default| match bound {
+                Some(bound) => {
+                    let i = eval_expr_local(bound, local_env, global_env)?;
+                    Ok((to_index(&i, xs.len())?, i.to_string()))
+                }
+                None => Ok((default, String::new())),
+            };
+            match subscript {
+                Subscript::Index(i) => {
+                    let (i, text) = eval_bound(&Some(i.clone()), 0)?;
+                    if tracing {
+                        rewritten = Some(format!("{}[{}]", x, text));
+                    }
+                    let element = usize::try_from(i).ok().and_then(|i| xs.get(i));
+                    element.cloned().ok_or_else(|| {
+                        EvalError::TypeError(format!(
+                            "Index {} is out of range for a list of length {}",
+                            text,
+                            xs.len()
+                        ))
+                    })?
+                }
+                Subscript::Slice(start, end) => {
+                    let (start, start_text) = eval_bound(start, 0)?;
+                    let (end, end_text) = eval_bound(end, xs.len() as i64)?;
+                    if tracing {
+                        rewritten = Some(format!("{}[{}:{}]", x, start_text, end_text));
+                    }
+                    // bounds beyond the ends are clamped as in Python
+                    let end = end.clamp(0, xs.len() as i64) as usize;
+                    let start = start.clamp(0, end as i64) as usize;
+                    Value::List(xs[start..end].to_vec())
+                }
+            }
         }
         Expression::UnaryOp(op, x) => {
             let x = eval_expr_local(x, local_env, global_env)?;
-            op.apply(x)?
+            if tracing {
+                rewritten = Some(match op {
+                    UnaryOp::Factorial => format!("{}{}", x, op),
+                    _ => format!("{}{}", op, x),
+                });
+            }
+            let result = apply_unary(*op, x.clone())?;
+            check_precision(&[x], &result, global_env);
+            result
         }
         Expression::BinaryOp(op, a, b) => {
-            let (a, b) = (
-                eval_expr_local(a, local_env, global_env)?,
-                eval_expr_local(b, local_env, global_env)?,
-            );
-            op.apply(a, b)?
+            let a = eval_expr_local(a, local_env, global_env)?;
+            let b = match global_env.settings().modulus {
+                Some(_) if *op == BinaryOp::Power => {
+                    // exponents are not residues, so they are evaluated in ordinary arithmetic
+                    let mut plain_env = global_env.clone();
+                    plain_env.settings_mut().modulus = None;
+                    eval_expr_local(b, local_env, &plain_env)?
+                }
+                _ => eval_expr_local(b, local_env, global_env)?,
+            };
+            if tracing {
+                rewritten = Some(match op {
+                    BinaryOp::Power => format!("{}{}{}", a, op, b),
+                    _ => format!("{} {} {}", a, op, b),
+                });
+            }
+            let result = apply_binary_in(*op, a.clone(), b.clone(), global_env)?;
+            check_precision(&[a, b], &result, global_env);
+            result
+        }
+        Expression::Conditional(cond, then, otherwise) => {
+            if eval_expr_local(cond, local_env, global_env)?.as_f64()? != 0.0 {
+                eval_expr_local(then, local_env, global_env)?
+            } else if let Some(otherwise) = otherwise {
+                eval_expr_local(otherwise, local_env, global_env)?
+            } else {
+                return Err(EvalError::TypeError(format!(
+                    "None of the cases applies: {}",
+                    expr
+                )));
+            }
         }
     };
 
-    if value.0.is_finite() {
-        Ok(value)
-    } else {
-        Err(EvalError::NumericalError(value))
+    let value = normalize(value, global_env)?;
+
+    if let (Some(trace), Some(rewritten)) = (global_env.trace(), rewritten) {
+        trace.push(Event::Rewrite {
+            depth: global_env.call_depth(),
+            expr: rewritten,
+            value: value.clone(),
+        });
+    }
+
+    check_finite(value)
+}
+
+/// Error for calling `name`, which holds `value` instead of a function
+fn not_a_function(name: &Identifier, value: &Value, args: &[Expression]) -> EvalError {
+    let hint = match args {
+        [x] if is_multipliable(value) => format!("; did you mean {} * ({})?", name, x),
+        _ => String::new(),
+    };
+    EvalError::TypeError(format!(
+        "{} is {}, which is not a function{}",
+        name, value, hint
+    ))
+}
+
+/// Whether `x` is a number or quantity, which a call may have been meant to multiply
+fn is_multipliable(x: &Value) -> bool {
+    x.is_numeric() || matches!(x, Value::Quantity(_))
+}
+
+/// Converts a list index to an offset from the start, counting negative ones from the end
+fn to_index(i: &Value, len: usize) -> EvalResult<i64> {
+    let i = match i.to_exact_integer().and_then(|i| i.to_i64()) {
+        Some(i) => i,
+        None => {
+            return Err(EvalError::TypeError(format!(
+                "Indices must be integers, but got {}",
+                i
+            )))
+        }
+    };
+    Ok(if i < 0 { len as i64 + i } else { i })
+}
+
+/// Brings a value into the range of the current modulus or bit width
+fn normalize(value: Value, env: &Environment) -> EvalResult<Value> {
+    let value = match env.settings().modulus {
+        Some(modulus) if value.is_numeric() && value.is_finite() => {
+            modular::reduce(value.as_number()?, modulus)?.into()
+        }
+        _ => value,
+    };
+
+    let value = match env.settings().bits {
+        Some(bits) if value.is_numeric() && value.is_finite() => {
+            settings::wrap_to_bits(value.as_f64()?, bits).into()
+        }
+        _ => value,
+    };
+
+    Ok(value)
+}
+
+fn check_finite(value: Value) -> EvalResult<Value> {
+    match value {
+        _ if value.is_finite() => Ok(value),
+        Value::Quantity(x) => Err(EvalError::NumericalError(Number(x.magnitude()))),
+        Value::List(xs) => Err(xs.into_iter().find_map(|x| check_finite(x).err()).unwrap()),
+        _ => Err(EvalError::NumericalError(value.as_number()?)),
+    }
+}
+
+/// Warns if `name` makes a unit inaccessible
+fn check_shadowing(name: &Identifier, env: &Environment) {
+    if env.find_unit(&name.0).is_some() {
+        env.warn(Warning::ShadowsUnit(name.clone()));
+    }
+}
+
+/// Ratio of magnitudes from which a new value of a variable is suspected to be a mistake,
+/// such as a percentage written as 5 instead of 0.05
+const GUARD_MAGNITUDE_RATIO: f64 = 100.0;
+
+/// Warns in guard mode if assigning `value` to `name` loses a user-defined function,
+/// or replaces a value of a very different magnitude
+fn check_overwrite(name: &Identifier, value: &Value, env: &Environment) {
+    if !env.settings().guard {
+        return;
+    }
+    match env.user_item(name) {
+        Some(NamedItem::Function(Function::UserDefined { params, expr })) => {
+            let def = FunctionDefinition {
+                name: name.clone(),
+                params: params.clone(),
+                expr: expr.clone(),
+            };
+            env.warn(Warning::FunctionOverwritten(def.to_string()));
+        }
+        Some(NamedItem::Field(Field::Variable(old))) => {
+            // magnitudes are compared in base units, and only between values of the same kind
+            let magnitudes = match (old, value) {
+                (Value::Quantity(a), Value::Quantity(b)) if a.dimension() == b.dimension() => {
+                    Some((a.value, b.value))
+                }
+                (Value::Quantity(_), _) | (_, Value::Quantity(_)) => None,
+                _ => old.as_f64().ok().zip(value.as_f64().ok()),
+            };
+            if let Some((a, b)) = magnitudes {
+                let ratio = (a / b).abs();
+                // zeros are left alone since counters and sums are often reset to them
+                if a != 0.0
+                    && b != 0.0
+                    && (ratio >= GUARD_MAGNITUDE_RATIO || ratio <= 1.0 / GUARD_MAGNITUDE_RATIO)
+                {
+                    env.warn(Warning::MagnitudeChange(name.clone(), old.clone()));
+                }
+            }
+        }
+        _ => (),
     }
 }
 
+/// Checks the parameters of a function definition in strict mode: unused ones are errors,
+/// and ones hiding other definitions are warned about
+fn check_params(
+    name: &Identifier,
+    params: &[Parameter],
+    expr: &Expression,
+    env: &Environment,
+) -> EvalResult<()> {
+    if let Some(param) = params.iter().find(|x| !expr.uses(&x.name)) {
+        return Err(EvalError::DefinitionError(format!(
+            "Parameter {} of {} is never used",
+            param.name, name
+        )));
+    }
+    for param in params {
+        if env.is_defined(&param.name) {
+            env.warn(Warning::ShadowsDefinition(param.name.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Warns if an exact integer operand too large for f64 gave an inexact result
+fn check_precision(operands: &[Value], result: &Value, env: &Environment) {
+    if matches!(result, Value::Integer(_) | Value::Function(_)) {
+        return;
+    }
+    let inexact = operands.iter().find(|x| match x {
+        Value::Integer(n) => !is_exact_in_f64(n),
+        _ => false,
+    });
+    if let Some(x) = inexact {
+        env.warn(Warning::PrecisionLoss(x.clone()));
+    }
+}
+
+fn unknown_unit(name: &str) -> EvalError {
+    EvalError::TypeError(format!("Unknown unit {}", name))
+}
+
+/// Functions taking angles, which can be written with units as in `sin(90deg)`
+const TRIG_FUNCS: &[&str] = &["sin", "cos", "tan"];
+
 fn eval_func(
     name: &Identifier,
     func: &Function,
-    args: &[Number],
+    args: &[Value],
     env: &Environment,
-) -> EvalResult<Number> {
-    if args.len() != func.num_args() {
+) -> EvalResult<Value> {
+    if !func.arity().accepts(args.len()) {
+        // calling with some but not all of the required arguments fixes them,
+        // giving a function that takes the rest
+        if !args.is_empty() && args.len() < func.arity().min {
+            return Ok(Value::Function(Rc::new(FunctionValue {
+                name: name.clone(),
+                func: func.clone(),
+                bound: args.to_vec(),
+            })));
+        }
         return Err(EvalError::ArityError {
-            name: name.to_string(),
-            expected: func.num_args(),
+            name: name.0.clone(),
+            expected: func.arity(),
             got: args.len(),
         });
     }
 
     match func {
-        Function::NullaryBuiltin(ptr) => Ok(Number(ptr())),
-        Function::UnaryBuiltin(ptr) => Ok(Number(ptr(args[0].0))),
-        Function::BinaryBuiltin(ptr) => Ok(Number(ptr(args[0].0, args[1].0))),
-        Function::UserDefined { arg_names, expr } => {
-            let mut global_env = env.clone();
-            global_env.delete(name).unwrap(); // HACK: avoid infinite recursion
+        Function::NullaryBuiltin(ptr) => Ok(ptr().into()),
+        Function::UnaryBuiltin(ptr) => {
+            // trigonometric functions take angles with units as well as numbers of radians
+            let x = match &args[0] {
+                Value::Quantity(q) if TRIG_FUNCS.contains(&name.0.as_str()) => {
+                    q.radians().ok_or_else(|| {
+                        EvalError::TypeError(format!("Expected an angle, but got {}", q))
+                    })?
+                }
+                x => x.as_f64()?,
+            };
+            Ok(ptr(x).into())
+        }
+        Function::BinaryBuiltin(ptr) => Ok(ptr(args[0].as_f64()?, args[1].as_f64()?).into()),
+        Function::VariadicBuiltin(ptr) => {
+            // lists are spread into their elements, as in `sum([1, 2], 3)`
+            let args = args
+                .iter()
+                .flat_map(|x| match x {
+                    Value::List(xs) => xs.iter().collect(),
+                    x => vec![x],
+                })
+                .map(Value::as_f64)
+                .collect::<EvalResult<Vec<_>>>()?;
+            ptr(&args).map(Into::into).ok_or_else(|| {
+                EvalError::TypeError(format!("{} of an empty list is undefined", name))
+            })
+        }
+        Function::SequenceBuiltin(ptr) => {
+            let n = match &args[0] {
+                Value::Integer(n) => n.to_u64(),
+                Value::Number(n) if n.0.fract() == 0.0 && n.0 >= 0.0 => Some(n.0 as u64),
+                _ => None,
+            };
+            match n {
+                Some(n) if n <= env::MAX_SEQUENCE_INDEX => Ok(ptr(n).into()),
+                _ => Err(EvalError::TypeError(format!(
+                    "The function {} takes an integer between 0 and {}",
+                    name,
+                    env::MAX_SEQUENCE_INDEX
+                ))),
+            }
+        }
+        Function::SettingsBuiltin(ptr) => Ok(ptr(args[0].as_f64()?, env.settings()).into()),
+        Function::GenericBuiltin(_, ptr) => ptr(args),
+        Function::HigherOrderBuiltin(_, ptr) => ptr(args, env),
+        Function::UserDefined { params, expr } => {
+            let args: Vec<_> = args.iter().cloned().map(Some).collect();
+            eval_user_func(name, params, expr, &args, env)
+        }
+    }
+}
+
+/// Calls a user-defined function with arguments given by the names of its parameters
+/// as well as positionally. Named arguments take their parameters first, and positional ones
+/// fill the rest in order.
+fn eval_func_named(
+    name: &Identifier,
+    func: &Function,
+    args: &[Value],
+    named: &[(&Identifier, Value)],
+    env: &Environment,
+) -> EvalResult<Value> {
+    let (params, expr) = match func {
+        Function::UserDefined { params, expr } => (params, expr),
+        _ => {
+            return Err(EvalError::TypeError(format!(
+                "The built-in function {} takes no named arguments",
+                name
+            )))
+        }
+    };
+
+    if args.len() > params.len() {
+        return Err(EvalError::ArityError {
+            name: name.0.clone(),
+            expected: func.arity(),
+            got: args.len() + named.len(),
+        });
+    }
+    // positional arguments fill the parameters from the left, leaving the rest to named ones
+    let mut slots: Vec<Option<Value>> = vec![None; params.len()];
+    for (slot, value) in slots.iter_mut().zip(args) {
+        *slot = Some(value.clone());
+    }
+    for (k, value) in named {
+        let i = params
+            .iter()
+            .position(|param| param.name == **k)
+            .ok_or_else(|| {
+                EvalError::TypeError(format!("The function {} has no parameter {}", name, k))
+            })?;
+        if slots[i].is_some() {
+            return Err(EvalError::TypeError(format!(
+                "The argument {} is given more than once",
+                k
+            )));
+        }
+        slots[i] = Some(value.clone());
+    }
+
+    let missing = params
+        .iter()
+        .zip(&slots)
+        .find(|(param, slot)| slot.is_none() && param.default.is_none());
+    if let Some((param, _)) = missing {
+        return Err(EvalError::TypeError(format!(
+            "The argument {} of {} is missing",
+            param.name, name
+        )));
+    }
+
+    eval_user_func(name, params, expr, &slots, env)
+}
+
+/// Evaluates the body of a user-defined function with its parameters bound to `args`,
+/// where `None` stands for an omitted argument
+fn eval_user_func(
+    name: &Identifier,
+    params: &[Parameter],
+    expr: &Expression,
+    args: &[Option<Value>],
+    env: &Environment,
+) -> EvalResult<Value> {
+    let mut global_env = env.clone();
+    // HACK: avoid infinite recursion. The name may have been deleted or reassigned
+    // since a function value was made from it.
+    let _ = global_env.delete(name);
+    global_env.enter_call();
+
+    let mut local_env = global_env.clone();
+    for (i, param) in params.iter().enumerate() {
+        // omitted arguments take default values, which can refer to preceding arguments
+        let value = match (args.get(i).cloned().flatten(), &param.default) {
+            (Some(value), _) => value,
+            (None, Some(default)) => eval_expr_local(default, &local_env, &global_env)?,
+            (None, None) => unreachable!(),
+        };
+        local_env.def_const(&param.name, value)?;
+    }
+
+    let trace = env.trace().filter(|trace| trace.records_calls());
+    if let Some(trace) = trace {
+        trace.push(Event::Call {
+            depth: env.call_depth(),
+            name: name.to_string(),
+            args: args.iter().flatten().cloned().collect(),
+        });
+    }
+    let value = eval_expr_local(expr, &local_env, &global_env)?;
+    if let Some(trace) = trace {
+        trace.push(Event::Return {
+            depth: env.call_depth(),
+            value: value.clone(),
+        });
+    }
+    Ok(value)
+}
 
-            let mut local_env = global_env.clone();
-            for (arg_name, value) in arg_names.iter().zip(args.iter()) {
-                local_env.def_const(arg_name, *value)?;
+fn apply_unary(op: UnaryOp, x: Value) -> EvalResult<Value> {
+    if op == UnaryOp::Factorial {
+        let n = x.to_exact_integer().and_then(|n| n.to_u64());
+        if let Some(n) = n.filter(|n| *n <= env::MAX_SEQUENCE_INDEX) {
+            let value = env::factorial(n);
+            if matches!(x, Value::Integer(_)) || !is_exact_in_f64(&value) {
+                return Ok(value.into());
             }
+        }
+    }
+    match (op, x) {
+        (UnaryOp::Negate, Value::Integer(x)) => Ok(Value::Integer(-x)),
+        (UnaryOp::Negate, Value::Quantity(x)) => Ok(Value::Quantity(Quantity {
+            value: -x.value,
+            ..x
+        })),
+        (op, x) => Ok(op.apply(x.as_number()?)?.into()),
+    }
+}
+
+/// Applies `op` in the arithmetic of `env`
+fn apply_binary_in(op: BinaryOp, a: Value, b: Value, env: &Environment) -> EvalResult<Value> {
+    match env.settings().modulus {
+        Some(modulus) => Ok(modular::apply(op, a.as_number()?, b.as_number()?, modulus)?.into()),
+        None => apply_binary(op, a, b),
+    }
+}
+
+/// Applies `op`, keeping exact integers exact where possible
+fn apply_binary(op: BinaryOp, a: Value, b: Value) -> EvalResult<Value> {
+    if matches!(a, Value::Quantity(_)) || matches!(b, Value::Quantity(_)) {
+        return units::apply(op, &a, &b);
+    }
+    if op.is_comparison() {
+        return compare(op, &a, &b);
+    }
 
-            eval_expr_local(expr, &local_env, &global_env)
+    let has_integer = matches!(a, Value::Integer(_)) || matches!(b, Value::Integer(_));
+    if let (Some(x), Some(y)) = (a.to_exact_integer(), b.to_exact_integer()) {
+        let value = match op {
+            BinaryOp::Add | BinaryOp::Subtract if !has_integer => None,
+            BinaryOp::Add => Some(x + y),
+            BinaryOp::Subtract => Some(x - y),
+            BinaryOp::Multiply => Some(x * y),
+            BinaryOp::FloorDivide if !y.is_zero() => Some(floor_div(&x, &y)),
+            BinaryOp::Power => exact_pow(&x, &y),
+            _ => None,
+        };
+        // products and powers of plain numbers stay numbers as long as they are exact
+        if let Some(value) = value.filter(|x| has_integer || !is_exact_in_f64(x)) {
+            return Ok(value.into());
         }
     }
+    Ok(op.apply(a.as_number()?, b.as_number()?)?.into())
+}
+
+/// Compares `a` with `b`, exactly if both are integers, and gives 1 if `op` holds and 0 otherwise
+fn compare(op: BinaryOp, a: &Value, b: &Value) -> EvalResult<Value> {
+    let has_integer = matches!(a, Value::Integer(_)) || matches!(b, Value::Integer(_));
+    let ordering = match (a, b) {
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        _ => match (a.to_exact_integer(), b.to_exact_integer()) {
+            // comparisons of exact integers give exact integers, as arithmetic on them does
+            (Some(x), Some(y)) if has_integer => {
+                return Ok(BigInt::from(u8::from(op.holds(Some(x.cmp(&y))))).into())
+            }
+            (Some(x), Some(y)) => Some(x.cmp(&y)),
+            _ => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        },
+    };
+    Ok(Value::from(if op.holds(ordering) { 1.0 } else { 0.0 }))
+}
+
+/// Maximum number of bits of powers computed exactly, beyond which they are approximated
+const MAX_EXACT_POWER_BITS: u64 = 1 << 16;
+
+/// Computes `x^y` exactly if `y` is a non-negative integer and the result is not too large
+fn exact_pow(x: &BigInt, y: &BigInt) -> Option<BigInt> {
+    let y = y.to_u32()?;
+    if x.bits().saturating_mul(y.into()) > MAX_EXACT_POWER_BITS {
+        return None;
+    }
+    Some(x.pow(y))
+}
+
+fn floor_div(x: &BigInt, y: &BigInt) -> BigInt {
+    let q = x / y;
+    // division truncates toward zero, which is one above the floor for inexact negative quotients
+    if (x % y).is_zero() || x.is_negative() == y.is_negative() {
+        q
+    } else {
+        q - 1
+    }
+}
+
+/// Largest magnitude of integer exponents taken by repeated squaring, whose rounding errors
+/// grow with the exponent
+const MAX_SQUARING_EXPONENT: f64 = 32.0;
+
+/// `a` raised to the power of `b`, by repeated squaring for small integer exponents, which
+/// `powf` treats no differently from fractional ones
+pub(crate) fn power(a: f64, b: f64) -> f64 {
+    if b.fract() == 0.0 && b.abs() <= MAX_SQUARING_EXPONENT {
+        a.powi(b as i32)
+    } else {
+        a.powf(b)
+    }
+}
+
+/// Whether `x` is converted to f64 without rounding
+fn is_exact_in_f64(x: &BigInt) -> bool {
+    x.bits() <= f64::MANTISSA_DIGITS.into()
 }
 
 impl UnaryOp {
     pub fn apply(self, x: Number) -> EvalResult<Number> {
         let value = match self {
             Self::Negate => -x.0,
-            Self::Factorial => factorial(x.0),
+            Self::Factorial => check_overflow("factorial", x.0, factorial(x.0))?,
+            Self::BitNot => !to_integer(x)? as f64,
         };
         Ok(Number(value))
     }
@@ -151,13 +1047,77 @@ impl BinaryOp {
             Self::Subtract => a - b,
             Self::Multiply => a * b,
             Self::Divide => a / b,
+            Self::FloorDivide => (a / b).floor(),
             Self::Modulo => a % b,
-            Self::Power => a.powf(b),
+            Self::Power => power(a, b),
+            Self::BitAnd => (to_integer(Number(a))? & to_integer(Number(b))?) as f64,
+            Self::BitOr => (to_integer(Number(a))? | to_integer(Number(b))?) as f64,
+            Self::ShiftLeft | Self::ShiftRight => {
+                let (a, b) = (to_integer(Number(a))?, to_integer(Number(b))?);
+                let shifted = u32::try_from(b).ok().and_then(|b| {
+                    if self == Self::ShiftLeft {
+                        a.checked_shl(b)
+                    } else {
+                        a.checked_shr(b)
+                    }
+                });
+                match shifted {
+                    Some(x) => x as f64,
+                    None => {
+                        return Err(EvalError::TypeError(format!("Cannot shift by {} bits", b)))
+                    }
+                }
+            }
+            Self::Less
+            | Self::LessOrEqual
+            | Self::Greater
+            | Self::GreaterOrEqual
+            | Self::Equal
+            | Self::NotEqual => f64::from(u8::from(self.holds(a.partial_cmp(&b)))),
         };
         Ok(Number(value))
     }
+
+    /// Whether the comparison holds for operands ordered as `ordering`, which is `None` for
+    /// unordered ones such as NaN
+    pub fn holds(self, ordering: Option<Ordering>) -> bool {
+        match self {
+            Self::Less => ordering == Some(Ordering::Less),
+            Self::LessOrEqual => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            Self::Greater => ordering == Some(Ordering::Greater),
+            Self::GreaterOrEqual => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            Self::Equal => ordering == Some(Ordering::Equal),
+            Self::NotEqual => ordering != Some(Ordering::Equal),
+            _ => false,
+        }
+    }
+}
+
+fn to_integer(x: Number) -> EvalResult<i64> {
+    if x.0.fract() == 0.0 && (i64::MIN as f64..=i64::MAX as f64).contains(&x.0) {
+        Ok(x.0 as i64)
+    } else {
+        Err(EvalError::TypeError(format!(
+            "Bitwise operations require integers, but got {}",
+            x
+        )))
+    }
+}
+
+/// Reports an overflow if `function` gave an infinite `result` for a finite positive `x`,
+/// as opposed to hitting a pole
+fn check_overflow(function: &str, x: f64, result: f64) -> EvalResult<f64> {
+    if result.is_infinite() && x.is_finite() && x > 0.0 {
+        Err(EvalError::OverflowError {
+            function: function.to_string(),
+            input: Number(x),
+        })
+    } else {
+        Ok(result)
+    }
 }
 
+#[cfg(feature = "statrs")]
 fn factorial(x: f64) -> f64 {
     use statrs::function::*;
 
@@ -167,3 +1127,40 @@ fn factorial(x: f64) -> f64 {
         gamma::gamma(x + 1.0)
     }
 }
+
+#[cfg(not(feature = "statrs"))]
+fn factorial(x: f64) -> f64 {
+    if x > 170.0 {
+        // 171! overflows, so there is no need to take a step for each factor
+        f64::INFINITY
+    } else if x >= 0.0 && x.fract() == 0.0 {
+        (1..=x as u64).map(|k| k as f64).product()
+    } else {
+        f64::NAN
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pow(a: f64, b: f64) -> f64 {
+        BinaryOp::Power.apply(Number(a), Number(b)).unwrap().0
+    }
+
+    #[test]
+    fn integer_powers_of_negative_bases() {
+        assert_eq!(pow(-2.0, 3.0), -8.0);
+        assert_eq!(pow(-2.5, 3.0), -15.625);
+        assert_eq!(pow(-2.0, -2.0), 0.25);
+        assert_eq!(pow(-1.5, 2.0), 2.25);
+    }
+
+    #[test]
+    fn non_integer_and_large_exponents() {
+        assert!(pow(-2.0, 0.5).is_nan());
+        assert_eq!(pow(4.0, 0.5), 2.0);
+        assert_eq!(pow(2.0, 100.0), 2f64.powi(100));
+        assert_eq!(pow(0.0, -1.0), f64::INFINITY);
+    }
+}
@@ -0,0 +1,130 @@
+use super::{
+    env::{Arity, Environment, HigherOrderFunc},
+    value::Value,
+    EvalError, EvalResult,
+};
+use num_traits::ToPrimitive;
+
+pub(super) const FUNCS: &[HigherOrderFunc] = &[
+    ("load_csv", Arity::exact(2), load_csv),
+    ("read_numbers", Arity::exact(1), read_numbers),
+];
+
+/// Reads a column of a CSV file as a list of numbers. The column is given by its index
+/// counted from 0 or by its name in the header row.
+pub fn load_csv(args: &[Value], env: &Environment) -> EvalResult<Value> {
+    let path = as_path(&args[0])?;
+    let content = read(path, env)?;
+    let mut rows = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| (i + 1, split_row(line)));
+
+    let column = match &args[1] {
+        Value::String(name) => {
+            let header = rows.next().map(|(_, row)| row).unwrap_or_default();
+            header
+                .iter()
+                .position(|x| x == name)
+                .ok_or_else(|| EvalError::TypeError(format!("{} has no column {}", path, name)))?
+        }
+        x => x
+            .to_exact_integer()
+            .and_then(|x| x.to_usize())
+            .ok_or_else(|| {
+                EvalError::TypeError(format!(
+                    "Columns must be names or nonnegative integers, but got {}",
+                    x
+                ))
+            })?,
+    };
+
+    let mut xs = Vec::new();
+    for (i, (line_number, row)) in rows.enumerate() {
+        let cell = row.get(column).map(String::as_str).unwrap_or_default();
+        match parse_number(cell) {
+            Some(x) => xs.push(x.into()),
+            // a header row may precede the data when columns are given by index
+            None if i == 0 && matches!(args[1], Value::Number(_) | Value::Integer(_)) => (),
+            None => {
+                return Err(EvalError::TypeError(format!(
+                    "{}:{}: Expected a number in column {}, but got \"{}\"",
+                    path, line_number, args[1], cell
+                )))
+            }
+        }
+    }
+    Ok(Value::List(xs))
+}
+
+/// Reads all the numbers in a file, separated by whitespace or commas.
+/// Lines starting with `#` are ignored.
+pub fn read_numbers(args: &[Value], env: &Environment) -> EvalResult<Value> {
+    let path = as_path(&args[0])?;
+    let content = read(path, env)?;
+    let mut xs = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        for token in line
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|x| !x.is_empty())
+        {
+            let x = parse_number(token).ok_or_else(|| {
+                EvalError::TypeError(format!(
+                    "{}:{}: Expected a number, but got \"{}\"",
+                    path,
+                    i + 1,
+                    token
+                ))
+            })?;
+            xs.push(x.into());
+        }
+    }
+    Ok(Value::List(xs))
+}
+
+/// Parses a finite number, since infinities and NaNs cannot be values
+fn parse_number(s: &str) -> Option<f64> {
+    s.parse().ok().filter(|x: &f64| x.is_finite())
+}
+
+fn as_path(x: &Value) -> EvalResult<&str> {
+    match x {
+        Value::String(path) => Ok(path),
+        _ => Err(EvalError::TypeError(format!(
+            "Expected a path as a string, but got {}",
+            x
+        ))),
+    }
+}
+
+fn read(path: &str, env: &Environment) -> EvalResult<String> {
+    env.check_file_access()?;
+    std::fs::read_to_string(path)
+        .map_err(|err| EvalError::IncludeError(format!("{}: {}", path, err)))
+}
+
+/// Splits a row of CSV into cells, removing quotes around them
+fn split_row(line: &str) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // doubled quotes in quoted cells stand for quotes themselves
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => cells.push(std::mem::take(&mut cell).trim().to_string()),
+            _ => cell.push(c),
+        }
+    }
+    cells.push(cell.trim().to_string());
+    cells
+}
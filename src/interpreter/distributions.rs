@@ -0,0 +1,167 @@
+use super::{
+    env::{Arity, GenericFunc},
+    value::Value,
+    EvalError, EvalResult,
+};
+use statrs::distribution::{
+    Binomial, ChiSquared, Continuous, ContinuousCDF, Discrete, DiscreteCDF, Exp, Normal, Poisson,
+    StudentsT,
+};
+
+// mean and standard deviation default to those of the standard normal distribution
+const NORMAL_ARITY: Arity = Arity {
+    min: 1,
+    max: Some(3),
+};
+
+pub(super) const FUNCS: &[GenericFunc] = &[
+    ("normpdf", NORMAL_ARITY, normpdf),
+    ("normcdf", NORMAL_ARITY, normcdf),
+    ("norminv", NORMAL_ARITY, norminv),
+    ("binompdf", Arity::exact(3), binompdf),
+    ("binomcdf", Arity::exact(3), binomcdf),
+    ("poissonpdf", Arity::exact(2), poissonpdf),
+    ("poissoncdf", Arity::exact(2), poissoncdf),
+    ("tpdf", Arity::exact(2), tpdf),
+    ("tcdf", Arity::exact(2), tcdf),
+    ("tinv", Arity::exact(2), tinv),
+    ("chi2pdf", Arity::exact(2), chi2pdf),
+    ("chi2cdf", Arity::exact(2), chi2cdf),
+    ("exppdf", Arity::exact(2), exppdf),
+    ("expcdf", Arity::exact(2), expcdf),
+];
+
+pub fn normpdf(args: &[Value]) -> EvalResult<Value> {
+    let (x, dist) = normal("normpdf", args)?;
+    Ok(dist.pdf(x).into())
+}
+
+pub fn normcdf(args: &[Value]) -> EvalResult<Value> {
+    let (x, dist) = normal("normcdf", args)?;
+    Ok(dist.cdf(x).into())
+}
+
+pub fn norminv(args: &[Value]) -> EvalResult<Value> {
+    let (p, dist) = normal("norminv", args)?;
+    Ok(dist.inverse_cdf(probability("norminv", p)?).into())
+}
+
+pub fn binompdf(args: &[Value]) -> EvalResult<Value> {
+    let (dist, k) = binomial("binompdf", args)?;
+    Ok(dist.pmf(k).into())
+}
+
+pub fn binomcdf(args: &[Value]) -> EvalResult<Value> {
+    let (dist, k) = binomial("binomcdf", args)?;
+    Ok(dist.cdf(k).into())
+}
+
+pub fn poissonpdf(args: &[Value]) -> EvalResult<Value> {
+    let (dist, k) = poisson("poissonpdf", args)?;
+    Ok(dist.pmf(k).into())
+}
+
+pub fn poissoncdf(args: &[Value]) -> EvalResult<Value> {
+    let (dist, k) = poisson("poissoncdf", args)?;
+    Ok(dist.cdf(k).into())
+}
+
+pub fn tpdf(args: &[Value]) -> EvalResult<Value> {
+    let (x, dist) = students_t("tpdf", args)?;
+    Ok(dist.pdf(x).into())
+}
+
+pub fn tcdf(args: &[Value]) -> EvalResult<Value> {
+    let (x, dist) = students_t("tcdf", args)?;
+    Ok(dist.cdf(x).into())
+}
+
+pub fn tinv(args: &[Value]) -> EvalResult<Value> {
+    let (p, dist) = students_t("tinv", args)?;
+    Ok(dist.inverse_cdf(probability("tinv", p)?).into())
+}
+
+pub fn chi2pdf(args: &[Value]) -> EvalResult<Value> {
+    let (x, df) = (args[0].as_f64()?, args[1].as_f64()?);
+    let dist = ChiSquared::new(df).map_err(|_| invalid_params("chi2pdf"))?;
+    Ok(dist.pdf(x).into())
+}
+
+pub fn chi2cdf(args: &[Value]) -> EvalResult<Value> {
+    let (x, df) = (args[0].as_f64()?, args[1].as_f64()?);
+    let dist = ChiSquared::new(df).map_err(|_| invalid_params("chi2cdf"))?;
+    Ok(dist.cdf(x).into())
+}
+
+pub fn exppdf(args: &[Value]) -> EvalResult<Value> {
+    let (x, rate) = (args[0].as_f64()?, args[1].as_f64()?);
+    let dist = Exp::new(rate).map_err(|_| invalid_params("exppdf"))?;
+    Ok(dist.pdf(x).into())
+}
+
+pub fn expcdf(args: &[Value]) -> EvalResult<Value> {
+    let (x, rate) = (args[0].as_f64()?, args[1].as_f64()?);
+    let dist = Exp::new(rate).map_err(|_| invalid_params("expcdf"))?;
+    Ok(dist.cdf(x).into())
+}
+
+/// Parses `(x, mu = 0, sigma = 1)`
+fn normal(name: &str, args: &[Value]) -> EvalResult<(f64, Normal)> {
+    let x = args[0].as_f64()?;
+    let mean = args.get(1).map_or(Ok(0.0), Value::as_f64)?;
+    let std_dev = args.get(2).map_or(Ok(1.0), Value::as_f64)?;
+    let dist = Normal::new(mean, std_dev).map_err(|_| invalid_params(name))?;
+    Ok((x, dist))
+}
+
+/// Parses `(n, p, k)`
+fn binomial(name: &str, args: &[Value]) -> EvalResult<(Binomial, u64)> {
+    let n = count(name, &args[0])?;
+    let p = args[1].as_f64()?;
+    let k = count(name, &args[2])?;
+    let dist = Binomial::new(p, n).map_err(|_| invalid_params(name))?;
+    Ok((dist, k))
+}
+
+/// Parses `(lambda, k)`
+fn poisson(name: &str, args: &[Value]) -> EvalResult<(Poisson, u64)> {
+    let lambda = args[0].as_f64()?;
+    let k = count(name, &args[1])?;
+    let dist = Poisson::new(lambda).map_err(|_| invalid_params(name))?;
+    Ok((dist, k))
+}
+
+/// Parses `(x, df)`
+fn students_t(name: &str, args: &[Value]) -> EvalResult<(f64, StudentsT)> {
+    let x = args[0].as_f64()?;
+    let freedom = args[1].as_f64()?;
+    let dist = StudentsT::new(0.0, 1.0, freedom).map_err(|_| invalid_params(name))?;
+    Ok((x, dist))
+}
+
+fn count(name: &str, x: &Value) -> EvalResult<u64> {
+    match x.as_f64()? {
+        x if x >= 0.0 && x.fract() == 0.0 => Ok(x as u64),
+        x => Err(EvalError::TypeError(format!(
+            "The function {} takes a non-negative integer, but got {}",
+            name,
+            Value::from(x)
+        ))),
+    }
+}
+
+fn probability(name: &str, p: f64) -> EvalResult<f64> {
+    if (0.0..=1.0).contains(&p) {
+        Ok(p)
+    } else {
+        Err(EvalError::TypeError(format!(
+            "The function {} takes a probability between 0 and 1, but got {}",
+            name,
+            Value::from(p)
+        )))
+    }
+}
+
+fn invalid_params(name: &str) -> EvalError {
+    EvalError::TypeError(format!("Invalid distribution parameters for {}", name))
+}
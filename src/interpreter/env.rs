@@ -1,15 +1,35 @@
-use super::{EvalError, EvalResult};
-use crate::language::{Expression, Identifier, Number};
+#[cfg(not(target_arch = "wasm32"))]
+use super::data;
+#[cfg(feature = "statrs")]
+use super::distributions;
+use super::{
+    equations, format, modular,
+    settings::{Settings, MAX_MODULUS},
+    statistics,
+    trace::Trace,
+    units::{self, Quantity, Unit},
+    value::{FunctionValue, Value},
+    warning::{Warning, Warnings},
+    EvalError, EvalResult,
+};
+use crate::language::{Expression, Identifier, Number, Parameter};
+use itertools::Itertools;
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive, Zero};
+#[cfg(feature = "rand")]
 use rand::Rng;
 use std::{
+    cell::Cell,
     collections::{HashMap, HashSet},
     fmt,
+    path::{Path, PathBuf},
+    rc::Rc,
 };
 
 #[derive(Debug, Clone)]
 pub enum Field {
-    Variable(Number),
-    Constant(Number),
+    Variable(Value),
+    Constant(Value),
 }
 
 impl fmt::Display for Field {
@@ -22,7 +42,14 @@ impl fmt::Display for Field {
 }
 
 impl Field {
-    fn inner(self) -> Number {
+    pub fn value(&self) -> &Value {
+        match self {
+            Self::Variable(x) => x,
+            Self::Constant(x) => x,
+        }
+    }
+
+    fn inner(self) -> Value {
         match self {
             Self::Variable(x) => x,
             Self::Constant(x) => x,
@@ -35,8 +62,19 @@ pub enum Function {
     NullaryBuiltin(fn() -> f64),
     UnaryBuiltin(fn(f64) -> f64),
     BinaryBuiltin(fn(f64, f64) -> f64),
+    /// Function of any number of arguments, which gives `None` if it is undefined for none
+    VariadicBuiltin(fn(&[f64]) -> Option<f64>),
+    /// Function computing the n-th term of an integer sequence exactly
+    SequenceBuiltin(fn(u64) -> BigInt),
+    /// Function whose behavior depends on settings
+    SettingsBuiltin(fn(f64, &Settings) -> f64),
+    /// Function operating on arbitrary values, such as strings, or taking optional arguments
+    GenericBuiltin(Arity, fn(&[Value]) -> EvalResult<Value>),
+    /// Function that needs the environment, to call functions taken as arguments
+    /// or to check whether it may read files
+    HigherOrderBuiltin(Arity, fn(&[Value], &Environment) -> EvalResult<Value>),
     UserDefined {
-        arg_names: Vec<Identifier>,
+        params: Vec<Parameter>,
         expr: Expression,
     },
 }
@@ -46,12 +84,254 @@ impl Function {
         !matches!(self, Self::UserDefined { .. })
     }
 
-    pub fn num_args(&self) -> usize {
+    /// Signature of the function called `name`, such as `atan2(y, x)`
+    pub fn signature(&self, name: &Identifier) -> String {
+        if let Self::UserDefined { params, .. } = self {
+            return format!("{}({})", name, params.iter().join(", "));
+        }
+        let params = find_builtin(&name.0).map_or_else(
+            || self.arity().param_names(),
+            |builtin| builtin.params.to_string(),
+        );
+        format!("{}({})", name, params)
+    }
+
+    pub fn arity(&self) -> Arity {
         match self {
-            Self::NullaryBuiltin(_) => 0,
-            Self::UnaryBuiltin(_) => 1,
-            Self::BinaryBuiltin(_) => 2,
-            Self::UserDefined { arg_names, .. } => arg_names.len(),
+            Self::NullaryBuiltin(_) => Arity::exact(0),
+            Self::UnaryBuiltin(_) => Arity::exact(1),
+            Self::BinaryBuiltin(_) => Arity::exact(2),
+            Self::SequenceBuiltin(_) | Self::SettingsBuiltin(_) => Arity::exact(1),
+            Self::VariadicBuiltin(_) => Arity { min: 1, max: None },
+            Self::GenericBuiltin(arity, _) | Self::HigherOrderBuiltin(arity, _) => *arity,
+            Self::UserDefined { params, .. } => Arity {
+                min: params.iter().filter(|x| x.default.is_none()).count(),
+                max: Some(params.len()),
+            },
+        }
+    }
+}
+
+/// Range of the number of arguments a function accepts
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Arity {
+    pub min: usize,
+    /// `None` if the function takes arbitrarily many arguments
+    pub max: Option<usize>,
+}
+
+impl Arity {
+    pub const fn exact(n: usize) -> Self {
+        Self {
+            min: n,
+            max: Some(n),
+        }
+    }
+
+    pub fn accepts(self, n: usize) -> bool {
+        self.min <= n && self.max.is_none_or(|max| n <= max)
+    }
+}
+
+impl Arity {
+    /// Parameter names for functions without entries in `BUILTINS`
+    fn param_names(self) -> String {
+        match (self.min, self.max) {
+            (0, Some(0)) => String::new(),
+            (1, Some(1)) => "x".to_string(),
+            (n, None) => (1..=n.max(1))
+                .map(|i| format!("x{}", i))
+                .chain(std::iter::once("...".to_string()))
+                .join(", "),
+            (_, Some(n)) => (1..=n).map(|i| format!("x{}", i)).join(", "),
+        }
+    }
+}
+
+/// Group of built-in constants and functions
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Category {
+    Constant,
+    Arithmetic,
+    Rounding,
+    Exponential,
+    Trigonometry,
+    Special,
+    Integer,
+    Statistics,
+    Probability,
+    List,
+    Formatting,
+    Equation,
+    Data,
+    Testing,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Constant => "Mathematical constants",
+            Self::Arithmetic => "Arithmetic",
+            Self::Rounding => "Rounding",
+            Self::Exponential => "Exponentials and logarithms",
+            Self::Trigonometry => "Trigonometry",
+            Self::Special => "Special functions",
+            Self::Integer => "Integers",
+            Self::Statistics => "Statistics",
+            Self::Probability => "Probability",
+            Self::List => "Lists",
+            Self::Formatting => "Formatting",
+            Self::Equation => "Equations",
+            Self::Data => "Data",
+            Self::Testing => "Testing",
+        })
+    }
+}
+
+/// Description of a built-in constant or function
+#[derive(Debug)]
+pub struct Builtin {
+    pub name: &'static str,
+    /// Parameters shown in signatures, such as `y, x`, or empty for constants
+    pub params: &'static str,
+    pub category: Category,
+    pub doc: &'static str,
+    /// Input showing how the built-in is used
+    pub example: &'static str,
+}
+
+impl Builtin {
+    const fn new(
+        name: &'static str,
+        params: &'static str,
+        category: Category,
+        doc: &'static str,
+        example: &'static str,
+    ) -> Self {
+        Self {
+            name,
+            params,
+            category,
+            doc,
+            example,
+        }
+    }
+}
+
+/// Descriptions of all the built-in constants and functions, including those left out
+/// by disabled features
+#[rustfmt::skip]
+pub const BUILTINS: &[Builtin] = &[
+    Builtin::new("e", "", Category::Constant, "Euler's number", "e"),
+    Builtin::new("pi", "", Category::Constant, "Ratio of a circle's circumference to its diameter", "pi"),
+    Builtin::new("π", "", Category::Constant, "Same as `pi`", "π"),
+    Builtin::new("tau", "", Category::Constant, "Ratio of a circle's circumference to its radius, 2π", "tau"),
+    Builtin::new("τ", "", Category::Constant, "Same as `tau`", "τ"),
+    Builtin::new("phi", "", Category::Constant, "Golden ratio", "phi"),
+    Builtin::new("φ", "", Category::Constant, "Same as `phi`", "φ"),
+    Builtin::new("gamma_e", "", Category::Constant, "Euler–Mascheroni constant", "gamma_e"),
+    Builtin::new("sqrt2", "", Category::Constant, "Square root of 2", "sqrt2"),
+    Builtin::new("ln2", "", Category::Constant, "Natural logarithm of 2", "ln2"),
+    Builtin::new("ln10", "", Category::Constant, "Natural logarithm of 10", "ln10"),
+    Builtin::new("abs", "x", Category::Arithmetic, "Absolute value", "abs(-3)"),
+    Builtin::new("sign", "x", Category::Arithmetic, "Sign, -1, 0 or 1", "sign(-3)"),
+    Builtin::new("sqrt", "x", Category::Arithmetic, "Square root", "sqrt(16)"),
+    Builtin::new("cbrt", "x", Category::Arithmetic, "Cube root", "cbrt(27)"),
+    Builtin::new("pow", "x, y", Category::Arithmetic, "x raised to the power of y", "pow(2, 10)"),
+    Builtin::new("hypot", "x, y", Category::Arithmetic, "Length of the hypotenuse of a right triangle with legs x and y", "hypot(3, 4)"),
+    Builtin::new("mod", "a, b", Category::Arithmetic, "Remainder of Euclidean division of a by b, which is never negative", "mod(-7, 3)"),
+    Builtin::new("divmod", "a, b", Category::Arithmetic, "Quotient rounded down and remainder of a divided by b", "divmod(7, 3)"),
+    Builtin::new("floor", "x", Category::Rounding, "Largest integer not greater than x", "floor(2.7)"),
+    Builtin::new("ceil", "x", Category::Rounding, "Smallest integer not less than x", "ceil(2.1)"),
+    Builtin::new("trunc", "x", Category::Rounding, "Integer part of x", "trunc(-2.7)"),
+    Builtin::new("fract", "x", Category::Rounding, "Fractional part of x", "fract(2.75)"),
+    Builtin::new("round", "x", Category::Rounding, "Nearest integer, rounding as set with `rounding`", "round(2.5)"),
+    Builtin::new("exp", "x", Category::Exponential, "e raised to the power of x", "exp(1)"),
+    Builtin::new("log", "x", Category::Exponential, "Natural logarithm", "log(e)"),
+    Builtin::new("ln", "x", Category::Exponential, "Natural logarithm", "ln(e)"),
+    Builtin::new("log2", "x", Category::Exponential, "Base-2 logarithm", "log2(8)"),
+    Builtin::new("log10", "x", Category::Exponential, "Base-10 logarithm", "log10(1000)"),
+    Builtin::new("sin", "x", Category::Trigonometry, "Sine of an angle, in radians unless given a unit", "sin(pi / 2)"),
+    Builtin::new("cos", "x", Category::Trigonometry, "Cosine of an angle, in radians unless given a unit", "cos(0)"),
+    Builtin::new("tan", "x", Category::Trigonometry, "Tangent of an angle, in radians unless given a unit", "tan(1)"),
+    Builtin::new("asin", "x", Category::Trigonometry, "Arcsine, in radians", "asin(1)"),
+    Builtin::new("acos", "x", Category::Trigonometry, "Arccosine, in radians", "acos(1)"),
+    Builtin::new("atan", "x", Category::Trigonometry, "Arctangent, in radians", "atan(1)"),
+    Builtin::new("atan2", "y, x", Category::Trigonometry, "Angle of the point (x, y) from the positive x-axis, in radians", "atan2(1, 1)"),
+    Builtin::new("sinh", "x", Category::Trigonometry, "Hyperbolic sine", "sinh(1)"),
+    Builtin::new("cosh", "x", Category::Trigonometry, "Hyperbolic cosine", "cosh(1)"),
+    Builtin::new("tanh", "x", Category::Trigonometry, "Hyperbolic tangent", "tanh(1)"),
+    Builtin::new("asinh", "x", Category::Trigonometry, "Inverse hyperbolic sine", "asinh(1)"),
+    Builtin::new("acosh", "x", Category::Trigonometry, "Inverse hyperbolic cosine", "acosh(1)"),
+    Builtin::new("atanh", "x", Category::Trigonometry, "Inverse hyperbolic tangent", "atanh(0.5)"),
+    Builtin::new("degrees", "x", Category::Trigonometry, "Converts radians to degrees", "degrees(pi)"),
+    Builtin::new("radians", "x", Category::Trigonometry, "Converts degrees to radians", "radians(180)"),
+    Builtin::new("erf", "x", Category::Special, "Error function", "erf(1)"),
+    Builtin::new("erfc", "x", Category::Special, "Complementary error function", "erfc(1)"),
+    Builtin::new("gamma", "x", Category::Special, "Gamma function", "gamma(0.5)"),
+    Builtin::new("lgamma", "x", Category::Special, "Natural logarithm of the gamma function", "lgamma(100)"),
+    Builtin::new("factorial", "n", Category::Integer, "Factorial, also written n!", "factorial(5)"),
+    Builtin::new("fib", "n", Category::Integer, "n-th Fibonacci number", "fib(10)"),
+    Builtin::new("catalan", "n", Category::Integer, "n-th Catalan number", "catalan(5)"),
+    Builtin::new("triangular", "n", Category::Integer, "n-th triangular number", "triangular(4)"),
+    Builtin::new("xor", "a, b", Category::Integer, "Bitwise exclusive or", "xor(12, 10)"),
+    Builtin::new("modinv", "a, m", Category::Integer, "Modular multiplicative inverse of a modulo m", "modinv(3, 11)"),
+    Builtin::new("modpow", "a, b, m", Category::Integer, "a raised to the power of b modulo m", "modpow(2, 10, 1000)"),
+    Builtin::new("max", "x, ...", Category::Statistics, "Largest of the arguments or the elements of a list", "max(3, 1, 4)"),
+    Builtin::new("min", "x, ...", Category::Statistics, "Smallest of the arguments or the elements of a list", "min(3, 1, 4)"),
+    Builtin::new("sum", "x, ...", Category::Statistics, "Sum of the arguments or the elements of a list", "sum([1, 2, 3])"),
+    Builtin::new("avg", "x, ...", Category::Statistics, "Mean of the arguments or the elements of a list", "avg(1, 2, 6)"),
+    Builtin::new("linreg", "xs, ys", Category::Statistics, "Slope and intercept of the least-squares line through the points", "linreg([1, 2, 3], [2, 4, 6])"),
+    Builtin::new("corr", "xs, ys", Category::Statistics, "Pearson correlation coefficient", "corr([1, 2, 3], [2, 4, 7])"),
+    Builtin::new("cov", "xs, ys", Category::Statistics, "Sample covariance", "cov([1, 2, 3], [2, 4, 7])"),
+    Builtin::new("random", "", Category::Probability, "Random number between 0 and 1", "random()"),
+    Builtin::new("normpdf", "x, mu = 0, sigma = 1", Category::Probability, "Probability density of the normal distribution", "normpdf(0)"),
+    Builtin::new("normcdf", "x, mu = 0, sigma = 1", Category::Probability, "Cumulative distribution of the normal distribution", "normcdf(1.96)"),
+    Builtin::new("norminv", "p, mu = 0, sigma = 1", Category::Probability, "Quantile of the normal distribution", "norminv(0.975)"),
+    Builtin::new("binompdf", "n, p, k", Category::Probability, "Probability of k successes in n trials with probability p each", "binompdf(10, 0.5, 5)"),
+    Builtin::new("binomcdf", "n, p, k", Category::Probability, "Probability of at most k successes in n trials with probability p each", "binomcdf(10, 0.5, 5)"),
+    Builtin::new("poissonpdf", "lambda, k", Category::Probability, "Probability of k events of the Poisson distribution with mean lambda", "poissonpdf(3, 2)"),
+    Builtin::new("poissoncdf", "lambda, k", Category::Probability, "Probability of at most k events of the Poisson distribution with mean lambda", "poissoncdf(3, 2)"),
+    Builtin::new("tpdf", "x, df", Category::Probability, "Probability density of Student's t-distribution", "tpdf(0, 10)"),
+    Builtin::new("tcdf", "x, df", Category::Probability, "Cumulative distribution of Student's t-distribution", "tcdf(2, 10)"),
+    Builtin::new("tinv", "p, df", Category::Probability, "Quantile of Student's t-distribution", "tinv(0.975, 10)"),
+    Builtin::new("chi2pdf", "x, df", Category::Probability, "Probability density of the chi-squared distribution", "chi2pdf(1, 3)"),
+    Builtin::new("chi2cdf", "x, df", Category::Probability, "Cumulative distribution of the chi-squared distribution", "chi2cdf(7.81, 3)"),
+    Builtin::new("exppdf", "x, rate", Category::Probability, "Probability density of the exponential distribution", "exppdf(1, 2)"),
+    Builtin::new("expcdf", "x, rate", Category::Probability, "Cumulative distribution of the exponential distribution", "expcdf(1, 2)"),
+    Builtin::new("len", "xs", Category::List, "Number of elements of a list, or characters of a string", "len([1, 2, 3])"),
+    Builtin::new("sort", "xs", Category::List, "Elements of a list in ascending order", "sort([3, 1, 2])"),
+    Builtin::new("unique", "xs", Category::List, "Elements of a list with duplicates removed", "unique([1, 2, 1])"),
+    Builtin::new("reverse", "xs", Category::List, "Elements of a list in reverse order", "reverse([1, 2, 3])"),
+    Builtin::new("map", "f, xs", Category::List, "Applies f to each element of a list", "map(sqrt, [1, 4, 9])"),
+    Builtin::new("filter", "f, xs", Category::List, "Elements of a list for which f returns nonzero", "filter(sign, [-1, 0, 2])"),
+    Builtin::new("reduce", "f, init, xs", Category::List, "Folds a list with f, starting from init", "reduce(pow, 2, [3, 2])"),
+    Builtin::new("format", "x, spec", Category::Formatting, "Formats a number as specified by a Python format spec", "format(1234.5678, \",.2f\")"),
+    Builtin::new("to_base", "n, base", Category::Formatting, "Digits of an integer in a base from 2 to 36", "to_base(255, 16)"),
+    Builtin::new("from_base", "digits, base", Category::Formatting, "Integer written in a base from 2 to 36", "from_base(\"ff\", 16)"),
+    Builtin::new("quadratic", "a, b, c", Category::Equation, "Real roots of ax² + bx + c = 0", "quadratic(1, -3, 2)"),
+    Builtin::new("cubic", "a, b, c, d", Category::Equation, "Real roots of ax³ + bx² + cx + d = 0", "cubic(1, 0, -1, 0)"),
+    Builtin::new("linsolve", "A, b", Category::Equation, "Solution of the system of linear equations Ax = b", "linsolve([[2, 1], [1, 3]], [3, 5])"),
+    Builtin::new("load_csv", "path, column", Category::Data, "Column of a CSV file, given by its index or name in the header row", "load_csv(\"data.csv\", \"price\")"),
+    Builtin::new("read_numbers", "path", Category::Data, "All the numbers in a file, separated by whitespace or commas", "read_numbers(\"data.txt\")"),
+    Builtin::new("assert", "condition", Category::Testing, "Fails unless condition is nonzero", "assert(isclose(0.1 + 0.2, 0.3))"),
+    Builtin::new("assert_eq", "a, b, tolerance = 0", Category::Testing, "Fails unless a and b are equal within tolerance", "assert_eq(0.1 + 0.2, 0.3, 1e-9)"),
+    Builtin::new("isclose", "a, b, rtol = 1e-9, atol = 0", Category::Testing, "1 if a and b are equal within the tolerances, 0 otherwise", "isclose(0.1 + 0.2, 0.3)"),
+    Builtin::new("timeit", "f, n", Category::Testing, "Seconds taken per call of f, averaged over n calls", "timeit(random, 1000)"),
+];
+
+/// Description of the built-in named `name`
+pub fn find_builtin(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|x| x.name == name)
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let noun = |n| if n == 1 { "argument" } else { "arguments" };
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{} {}", max, noun(max)),
+            Some(max) => write!(f, "{} to {} arguments", self.min, max),
+            None => write!(f, "at least {} {}", self.min, noun(self.min)),
         }
     }
 }
@@ -63,26 +343,147 @@ pub enum NamedItem {
 }
 
 #[derive(Debug, Clone)]
-pub struct Environment(HashMap<Identifier, NamedItem>);
+pub struct Environment {
+    builtins: HashMap<Identifier, NamedItem>,
+    user: HashMap<Identifier, NamedItem>,
+    units: HashMap<Identifier, Unit>,
+    last_result: Option<Value>,
+    settings: Settings,
+    allow_override: bool,
+    /// Files being executed, innermost last
+    files: Vec<PathBuf>,
+    trace: Option<Trace>,
+    /// Destination of warnings, or `None` if warnings are suppressed
+    warnings: Option<Warnings>,
+    call_depth: usize,
+    /// Limit of evaluation steps, or `None` if evaluation may take any number of steps
+    step_limit: Option<StepLimit>,
+    /// Whether statements may read files, as `include` and the data functions do
+    file_access: bool,
+}
+
+/// Maximum number of evaluation steps, along with the number of steps taken so far,
+/// which is shared across the copies of environments made during evaluation
+#[derive(Debug, Clone)]
+struct StepLimit {
+    max: usize,
+    taken: Rc<Cell<usize>>,
+}
+
+/// Names referring to the last result unless the user defines something else with them
+pub const LAST_RESULT_NAMES: &[&str] = &["ans", "_"];
+
+fn is_last_result_name(ident: &Identifier) -> bool {
+    LAST_RESULT_NAMES.contains(&ident.0.as_str())
+}
 
 impl Environment {
     pub fn new() -> Self {
         Default::default()
     }
 
-    pub fn resolve_field(&self, ident: &Identifier) -> EvalResult<Number> {
-        match self.0.get(ident) {
+    /// Removes all the user-defined items and the last result, keeping settings intact
+    pub fn reset(&mut self) {
+        *self = Self {
+            settings: self.settings.clone(),
+            trace: self.trace.clone(),
+            warnings: self.warnings.clone(),
+            step_limit: self.step_limit.clone(),
+            file_access: self.file_access,
+            ..Self::default()
+        };
+    }
+
+    /// Same as `reset` except that user-defined items named `names` are kept
+    pub fn reset_keeping(&mut self, names: &[Identifier]) -> EvalResult<()> {
+        if let Some(name) = names.iter().find(|name| !self.user.contains_key(name)) {
+            return Err(EvalError::ReferenceError(name.clone()));
+        }
+        let kept = names
+            .iter()
+            .filter_map(|name| self.user.remove_entry(name))
+            .collect();
+        self.reset();
+        self.user = kept;
+        Ok(())
+    }
+
+    /// Removes the user-defined items for which `f` returns `false`
+    pub fn retain(&mut self, mut f: impl FnMut(&Identifier, &NamedItem) -> bool) {
+        self.user.retain(|name, item| f(name, item));
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
+
+    fn get(&self, ident: &Identifier) -> Option<&NamedItem> {
+        self.user.get(ident).or_else(|| self.builtins.get(ident))
+    }
+
+    /// Whether `ident` names a constant, variable or function
+    pub fn is_defined(&self, ident: &Identifier) -> bool {
+        self.get(ident).is_some()
+    }
+
+    pub fn resolve_field(&self, ident: &Identifier) -> EvalResult<Value> {
+        match self.get(ident) {
             Some(NamedItem::Field(field)) => Ok(field.clone().inner()),
-            Some(NamedItem::Function(_)) => Err(EvalError::TypeError(format!(
-                "{} is not a variable or constant",
-                ident
-            ))),
-            None => Err(EvalError::ReferenceError(ident.clone())),
+            // functions named without being called are function values, as in `s = sin`
+            Some(NamedItem::Function(func)) => Ok(Value::Function(Rc::new(FunctionValue {
+                name: ident.clone(),
+                func: func.clone(),
+                bound: Vec::new(),
+            }))),
+            None => match &self.last_result {
+                Some(value) if is_last_result_name(ident) => Ok(value.clone()),
+                _ => match self.find_unit(&ident.0) {
+                    // names of units not defined otherwise stand for one unit, as in `2 hours`
+                    Some(unit) => Ok(Value::Quantity(Quantity::new(1.0, unit))),
+                    None => Err(EvalError::ReferenceError(ident.clone())),
+                },
+            },
         }
     }
 
+    pub fn last_result(&self) -> Option<&Value> {
+        self.last_result.as_ref()
+    }
+
+    pub fn set_last_result(&mut self, value: Value) {
+        self.last_result = Some(value);
+    }
+
+    /// Finds a user-defined or built-in unit named `name`
+    pub fn find_unit(&self, name: &str) -> Option<Unit> {
+        self.units
+            .get(&Identifier(name.to_string()))
+            .cloned()
+            .or_else(|| units::find(name))
+    }
+
+    pub fn def_unit(&mut self, name: &Identifier, unit: Unit) -> EvalResult<()> {
+        if units::find(&name.0).is_some() {
+            return Err(EvalError::DefinitionError(format!(
+                "Cannot redefine a built-in unit {}",
+                name
+            )));
+        }
+        self.units.insert(name.clone(), unit);
+        Ok(())
+    }
+
+    /// User-defined units
+    pub fn units(&self) -> impl Iterator<Item = (&Identifier, &Unit)> {
+        self.units.iter()
+    }
+
     pub fn resolve_func(&self, ident: &Identifier) -> EvalResult<&Function> {
-        match self.0.get(ident) {
+        match self.get(ident) {
             Some(NamedItem::Function(func)) => Ok(func),
             Some(NamedItem::Field(_)) => {
                 Err(EvalError::TypeError(format!("{} is not a function", ident)))
@@ -91,8 +492,42 @@ impl Environment {
         }
     }
 
+    /// Resolves the name of a called function, which may also be a variable or constant holding
+    /// a function value. Returns the name the function was defined with, the function itself,
+    /// and the arguments bound to it by partial application.
+    pub fn resolve_callee<'a>(
+        &'a self,
+        ident: &'a Identifier,
+    ) -> EvalResult<(&'a Identifier, &'a Function, &'a [Value])> {
+        let value = match self.get(ident) {
+            Some(NamedItem::Function(func)) => return Ok((ident, func, &[])),
+            Some(NamedItem::Field(Field::Variable(value) | Field::Constant(value))) => Some(value),
+            None if is_last_result_name(ident) => self.last_result.as_ref(),
+            None => None,
+        };
+        match value {
+            Some(Value::Function(f)) => Ok((&f.name, &f.func, &f.bound)),
+            Some(_) => Err(EvalError::TypeError(format!("{} is not a function", ident))),
+            None => Err(EvalError::ReferenceError(ident.clone())),
+        }
+    }
+
+    /// Value of `ident` if it is a variable or constant that cannot be called, as `x` in `x(2)`
+    pub fn uncallable_value(&self, ident: &Identifier) -> Option<&Value> {
+        let value = match self.get(ident) {
+            Some(NamedItem::Field(Field::Variable(value) | Field::Constant(value))) => value,
+            None if is_last_result_name(ident) => self.last_result.as_ref()?,
+            _ => return None,
+        };
+        match value {
+            Value::Function(_) => None,
+            _ => Some(value),
+        }
+    }
+
+    /// Deletes a user-defined item. If it overrode a built-in, the built-in becomes visible again.
     pub fn delete(&mut self, ident: &Identifier) -> EvalResult<()> {
-        match self.0.get(ident) {
+        match self.get(ident) {
             Some(NamedItem::Field(Field::Constant(_))) => Err(EvalError::TypeError(format!(
                 "Cannot delete a constant {}",
                 ident
@@ -100,37 +535,202 @@ impl Environment {
             Some(NamedItem::Function(func)) if func.is_builtin() => Err(EvalError::TypeError(
                 format!("Cannot delete a built-in function {}", ident),
             )),
-            None => Err(EvalError::ReferenceError(ident.clone())),
+            None if is_last_result_name(ident) && self.last_result.is_some() => {
+                self.last_result = None;
+                Ok(())
+            }
+            None => match self.units.remove(ident) {
+                Some(_) => Ok(()),
+                None => Err(EvalError::ReferenceError(ident.clone())),
+            },
             _ => {
-                self.0.remove(ident).unwrap();
+                self.user.remove(ident).unwrap();
                 Ok(())
             }
         }
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = (&Identifier, &NamedItem)> {
-        self.0.iter()
-    }
-
-    pub fn assign_var(&mut self, name: &Identifier, value: Number) -> EvalResult<()> {
-        match self.0.get(name) {
+    /// Moves a user-defined variable or function to another name without changing anything else
+    pub fn rename(&mut self, from: &Identifier, to: &Identifier) -> EvalResult<()> {
+        match self.get(from) {
             Some(NamedItem::Field(Field::Constant(_))) => Err(EvalError::TypeError(format!(
-                "Cannot assign to a constant {}",
-                name
+                "Cannot rename a constant {}",
+                from
             ))),
             Some(NamedItem::Function(func)) if func.is_builtin() => Err(EvalError::TypeError(
-                format!("Cannot redefine a built-in function {}", name),
+                format!("Cannot rename a built-in function {}", from),
             )),
+            None => Err(EvalError::ReferenceError(from.clone())),
+            _ if self.get(to).is_some() => Err(EvalError::DefinitionError(format!(
+                "{} is already defined",
+                to
+            ))),
             _ => {
-                self.0
-                    .insert(name.clone(), NamedItem::Field(Field::Variable(value)));
+                let item = self.user.remove(from).unwrap();
+                self.user.insert(to.clone(), item);
                 Ok(())
             }
         }
     }
 
-    pub fn def_const(&mut self, name: &Identifier, value: Number) -> EvalResult<()> {
-        self.0
+    /// Description of the built-in that `ident` refers to, or `None` if it refers to
+    /// a user-defined item or nothing
+    pub fn builtin(&self, ident: &Identifier) -> Option<&'static Builtin> {
+        if self.user.contains_key(ident) || !self.builtins.contains_key(ident) {
+            return None;
+        }
+        find_builtin(&ident.0)
+    }
+
+    /// Item the user defined as `ident`, ignoring built-ins
+    pub fn user_item(&self, ident: &Identifier) -> Option<&NamedItem> {
+        self.user.get(ident)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Identifier, &NamedItem)> {
+        self.user.iter().chain(
+            self.builtins
+                .iter()
+                .filter(move |(name, _)| !self.user.contains_key(name)),
+        )
+    }
+
+    /// Runs `f` with redefinition of built-in constants and functions allowed.
+    /// The original built-ins stay intact and come back when the overriding items are deleted.
+    pub fn with_override<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.allow_override = true;
+        let result = f(self);
+        self.allow_override = false;
+        result
+    }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    pub fn set_trace(&mut self, trace: Option<Trace>) {
+        self.trace = trace;
+    }
+
+    pub fn warnings(&self) -> Option<&Warnings> {
+        self.warnings.as_ref()
+    }
+
+    pub fn set_warnings(&mut self, warnings: Option<Warnings>) {
+        self.warnings = warnings;
+    }
+
+    pub fn warn(&self, warning: Warning) {
+        if let Some(warnings) = &self.warnings {
+            warnings.push(warning);
+        }
+    }
+
+    /// Limits evaluation to `max` steps from now on, counting every sub-expression evaluated,
+    /// including those in the bodies of called functions. `None` lifts the limit.
+    pub fn set_step_limit(&mut self, max: Option<usize>) {
+        self.step_limit = max.map(|max| StepLimit {
+            max,
+            taken: Default::default(),
+        });
+    }
+
+    /// Allows or denies reading files, which is denied to environments serving untrusted users
+    pub fn set_file_access(&mut self, allowed: bool) {
+        self.file_access = allowed;
+    }
+
+    /// Fails unless reading files is allowed
+    pub fn check_file_access(&self) -> EvalResult<()> {
+        if self.file_access {
+            Ok(())
+        } else {
+            Err(EvalError::IncludeError(
+                "Reading files is not allowed here".to_string(),
+            ))
+        }
+    }
+
+    /// Counts a step of evaluation, failing if it exceeds the limit
+    pub fn step(&self) -> EvalResult<()> {
+        if let Some(limit) = &self.step_limit {
+            let taken = limit.taken.get() + 1;
+            if taken > limit.max {
+                return Err(EvalError::StepLimitError(limit.max));
+            }
+            limit.taken.set(taken);
+        }
+        Ok(())
+    }
+
+    /// Depth of nested calls of user-defined functions
+    pub fn call_depth(&self) -> usize {
+        self.call_depth
+    }
+
+    pub fn enter_call(&mut self) {
+        self.call_depth += 1;
+    }
+
+    /// Runs `f` as the execution of the script in `path`
+    pub fn with_file<T>(&mut self, path: PathBuf, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.files.push(path);
+        let result = f(self);
+        self.files.pop();
+        result
+    }
+
+    pub fn current_file(&self) -> Option<&Path> {
+        self.files.last().map(PathBuf::as_path)
+    }
+
+    pub fn is_running_file(&self, path: &Path) -> bool {
+        self.files.iter().any(|x| x == path)
+    }
+
+    fn check_redefinable(&self, name: &Identifier) -> EvalResult<()> {
+        let (item, hint) = match self.user.get(name) {
+            Some(item) => (item, ""),
+            None if self.allow_override => return Ok(()),
+            None => match self.builtins.get(name) {
+                Some(item) => (item, " (use override to shadow it)"),
+                None => return Ok(()),
+            },
+        };
+        match item {
+            NamedItem::Field(Field::Constant(_)) => Err(EvalError::TypeError(format!(
+                "Cannot assign to a constant {}{}",
+                name, hint
+            ))),
+            NamedItem::Function(func) if func.is_builtin() => Err(EvalError::TypeError(format!(
+                "Cannot redefine a built-in function {}{}",
+                name, hint
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn assign_var(&mut self, name: &Identifier, value: Value) -> EvalResult<()> {
+        if is_last_result_name(name) && self.get(name).is_none() {
+            self.set_last_result(value);
+            return Ok(());
+        }
+
+        self.check_redefinable(name)?;
+        self.user
+            .insert(name.clone(), NamedItem::Field(Field::Variable(value)));
+        Ok(())
+    }
+
+    /// Defines a constant on behalf of the user, refusing to overwrite other constants
+    /// and built-in functions
+    pub fn declare_const(&mut self, name: &Identifier, value: Value) -> EvalResult<()> {
+        self.check_redefinable(name)?;
+        self.def_const(name, value)
+    }
+
+    pub fn def_const(&mut self, name: &Identifier, value: Value) -> EvalResult<()> {
+        self.user
             .insert(name.clone(), NamedItem::Field(Field::Constant(value)));
         Ok(())
     }
@@ -138,46 +738,52 @@ impl Environment {
     pub fn def_func(
         &mut self,
         name: &Identifier,
-        arg_names: &[Identifier],
+        params: &[Parameter],
         expr: &Expression,
     ) -> EvalResult<()> {
-        if let Some(dup) = find_duplicate(arg_names) {
+        let arg_names: Vec<_> = params.iter().map(|x| x.name.clone()).collect();
+        if let Some(dup) = find_duplicate(&arg_names) {
             return Err(EvalError::DefinitionError(format!(
                 "Duplicate argument {}",
                 dup
             )));
         }
-
-        match self.0.get(name) {
-            Some(NamedItem::Field(Field::Constant(_))) => Err(EvalError::TypeError(format!(
-                "Cannot assign to a constant {}",
-                name
-            ))),
-            Some(NamedItem::Function(func)) if func.is_builtin() => Err(EvalError::TypeError(
-                format!("Cannot redefine a built-in function {}", name),
-            )),
-            _ => {
-                self.0.insert(
-                    name.clone(),
-                    NamedItem::Function(Function::UserDefined {
-                        arg_names: arg_names.to_vec(),
-                        expr: expr.clone(),
-                    }),
-                );
-                Ok(())
-            }
+        if let Some(param) = params
+            .iter()
+            .skip_while(|x| x.default.is_none())
+            .find(|x| x.default.is_none())
+        {
+            return Err(EvalError::DefinitionError(format!(
+                "Argument {} without a default value follows an argument with a default value",
+                param.name
+            )));
         }
+
+        self.check_redefinable(name)?;
+        self.user.insert(
+            name.clone(),
+            NamedItem::Function(Function::UserDefined {
+                params: params.to_vec(),
+                expr: expr.clone(),
+            }),
+        );
+        Ok(())
     }
 }
 
-fn find_duplicate(xs: &[Identifier]) -> Option<&Identifier> {
+/// Name, arity and implementation of a built-in `Function::GenericBuiltin`
+pub(super) type GenericFunc = (&'static str, Arity, fn(&[Value]) -> EvalResult<Value>);
+
+/// Name, arity and implementation of a built-in `Function::HigherOrderBuiltin`
+pub(super) type HigherOrderFunc = (
+    &'static str,
+    Arity,
+    fn(&[Value], &Environment) -> EvalResult<Value>,
+);
+
+pub(super) fn find_duplicate(xs: &[Identifier]) -> Option<&Identifier> {
     let mut uniq = HashSet::new();
-    for x in xs.iter() {
-        if !uniq.insert(x) {
-            return Some(x);
-        }
-    }
-    None
+    xs.iter().find(|x| !uniq.insert(*x))
 }
 
 impl Default for Environment {
@@ -187,13 +793,35 @@ impl Default for Environment {
         type NullaryFunc = (&'static str, fn() -> f64);
         type UnaryFunc = (&'static str, fn(f64) -> f64);
         type BinaryFunc = (&'static str, fn(f64, f64) -> f64);
+        type VariadicFunc = (&'static str, fn(&[f64]) -> Option<f64>);
+        type SequenceFunc = (&'static str, fn(u64) -> BigInt);
+        type SettingsFunc = (&'static str, fn(f64, &Settings) -> f64);
 
-        const CONSTS: &[(&str, f64)] = &[("e", E), ("pi", PI), ("π", PI), ("tau", TAU), ("τ", TAU)];
-        const NULLARY_FUNCS: &[NullaryFunc] = &[("random", random)];
+        /// Golden ratio
+        const PHI: f64 = 1.618_033_988_749_895;
+        /// Euler–Mascheroni constant
+        const GAMMA_E: f64 = 0.577_215_664_901_532_9;
+
+        const CONSTS: &[(&str, f64)] = &[
+            ("e", E),
+            ("pi", PI),
+            ("π", PI),
+            ("tau", TAU),
+            ("τ", TAU),
+            ("phi", PHI),
+            ("φ", PHI),
+            ("gamma_e", GAMMA_E),
+            ("sqrt2", SQRT_2),
+            ("ln2", LN_2),
+            ("ln10", LN_10),
+        ];
+        const NULLARY_FUNCS: &[NullaryFunc] = &[
+            #[cfg(feature = "rand")]
+            ("random", random),
+        ];
         const UNARY_FUNCS: &[UnaryFunc] = &[
             ("floor", f64::floor),
             ("ceil", f64::ceil),
-            ("round", f64::round),
             ("trunc", f64::trunc),
             ("fract", f64::fract),
             ("abs", f64::abs),
@@ -218,24 +846,81 @@ impl Default for Environment {
             ("atanh", f64::atanh),
             ("degrees", f64::to_degrees),
             ("radians", f64::to_radians),
+            #[cfg(feature = "statrs")]
             ("erf", statrs::function::erf::erf),
+            #[cfg(feature = "statrs")]
             ("erfc", statrs::function::erf::erfc),
-            ("gamma", statrs::function::gamma::gamma),
+            #[cfg(feature = "statrs")]
             ("lgamma", statrs::function::gamma::ln_gamma),
             ("sign", sign),
         ];
         const BINARY_FUNCS: &[BinaryFunc] = &[
-            ("pow", f64::powf),
+            ("pow", super::power),
             ("hypot", f64::hypot),
             ("atan2", f64::atan2),
-            ("max", f64::max),
-            ("min", f64::min),
         ];
+        const VARIADIC_FUNCS: &[VariadicFunc] =
+            &[("max", max), ("min", min), ("sum", sum), ("avg", avg)];
+        const SEQUENCE_FUNCS: &[SequenceFunc] = &[
+            ("factorial", factorial),
+            ("fib", fib),
+            ("catalan", catalan),
+            ("triangular", triangular),
+        ];
+        const SETTINGS_FUNCS: &[SettingsFunc] = &[("round", round)];
+        const GENERIC_FUNCS: &[GenericFunc] = &[
+            ("xor", Arity::exact(2), xor),
+            ("modinv", Arity::exact(2), modinv),
+            ("modpow", Arity::exact(3), modpow),
+            ("mod", Arity::exact(2), modulo),
+            ("divmod", Arity::exact(2), divmod),
+            ("to_base", Arity::exact(2), to_base),
+            ("from_base", Arity::exact(2), from_base),
+            #[cfg(feature = "statrs")]
+            ("gamma", Arity::exact(1), gamma),
+            ("len", Arity::exact(1), len),
+            ("sort", Arity::exact(1), sort),
+            ("unique", Arity::exact(1), unique),
+            ("reverse", Arity::exact(1), reverse),
+            ("assert", Arity::exact(1), assert),
+            (
+                "assert_eq",
+                Arity {
+                    min: 2,
+                    max: Some(3),
+                },
+                assert_eq,
+            ),
+            (
+                "isclose",
+                Arity {
+                    min: 2,
+                    max: Some(4),
+                },
+                isclose,
+            ),
+        ];
+        const HIGHER_ORDER_FUNCS: &[HigherOrderFunc] = &[
+            ("map", Arity::exact(2), map),
+            ("filter", Arity::exact(2), filter),
+            ("reduce", Arity::exact(3), reduce),
+            // clocks are unavailable in the browser
+            #[cfg(not(target_arch = "wasm32"))]
+            ("timeit", Arity::exact(2), timeit),
+        ];
+        #[cfg(not(target_arch = "wasm32"))]
+        const DATA_FUNCS: &[HigherOrderFunc] = data::FUNCS;
+        #[cfg(target_arch = "wasm32")]
+        const DATA_FUNCS: &[HigherOrderFunc] = &[];
+        #[cfg(feature = "statrs")]
+        const DISTRIBUTION_FUNCS: &[GenericFunc] = distributions::FUNCS;
+        #[cfg(not(feature = "statrs"))]
+        const DISTRIBUTION_FUNCS: &[GenericFunc] = &[];
 
         let consts = CONSTS.iter().map(|(name, value)| {
             (
                 Identifier(name.to_string()),
-                NamedItem::Field(Field::Constant(Number(*value))),
+                NamedItem::Field(Field::Constant(Value::from(*value))),
             )
         });
         let nullary_funcs = NULLARY_FUNCS.iter().map(|(name, ptr)| {
@@ -256,20 +941,81 @@ impl Default for Environment {
                 NamedItem::Function(Function::BinaryBuiltin(*ptr)),
             )
         });
-        Environment(
-            consts
+        let variadic_funcs = VARIADIC_FUNCS.iter().map(|(name, ptr)| {
+            (
+                Identifier(name.to_string()),
+                NamedItem::Function(Function::VariadicBuiltin(*ptr)),
+            )
+        });
+        let sequence_funcs = SEQUENCE_FUNCS.iter().map(|(name, ptr)| {
+            (
+                Identifier(name.to_string()),
+                NamedItem::Function(Function::SequenceBuiltin(*ptr)),
+            )
+        });
+        let settings_funcs = SETTINGS_FUNCS.iter().map(|(name, ptr)| {
+            (
+                Identifier(name.to_string()),
+                NamedItem::Function(Function::SettingsBuiltin(*ptr)),
+            )
+        });
+        let generic_funcs = GENERIC_FUNCS
+            .iter()
+            .chain(DISTRIBUTION_FUNCS)
+            .chain(statistics::FUNCS)
+            .chain(format::FUNCS)
+            .chain(equations::FUNCS)
+            .map(|(name, arity, ptr)| {
+                (
+                    Identifier(name.to_string()),
+                    NamedItem::Function(Function::GenericBuiltin(*arity, *ptr)),
+                )
+            });
+        let higher_order_funcs =
+            HIGHER_ORDER_FUNCS
+                .iter()
+                .chain(DATA_FUNCS)
+                .map(|(name, arity, ptr)| {
+                    (
+                        Identifier(name.to_string()),
+                        NamedItem::Function(Function::HigherOrderBuiltin(*arity, *ptr)),
+                    )
+                });
+        Environment {
+            builtins: consts
                 .chain(nullary_funcs)
                 .chain(unary_funcs)
                 .chain(binary_funcs)
+                .chain(variadic_funcs)
+                .chain(sequence_funcs)
+                .chain(settings_funcs)
+                .chain(generic_funcs)
+                .chain(higher_order_funcs)
                 .collect(),
-        )
+            user: HashMap::new(),
+            units: HashMap::new(),
+            last_result: None,
+            settings: Settings::default(),
+            allow_override: false,
+            files: Vec::new(),
+            trace: None,
+            warnings: None,
+            call_depth: 0,
+            step_limit: None,
+            file_access: true,
+        }
     }
 }
 
+#[cfg(feature = "rand")]
 fn random() -> f64 {
     rand::thread_rng().gen()
 }
 
+fn round(x: f64, settings: &Settings) -> f64 {
+    settings.rounding.round(x)
+}
+
 fn sign(x: f64) -> f64 {
     if x == 0.0 {
         if x.is_sign_positive() {
@@ -281,3 +1027,317 @@ fn sign(x: f64) -> f64 {
         x.signum()
     }
 }
+
+fn max(xs: &[f64]) -> Option<f64> {
+    xs.iter().copied().reduce(f64::max)
+}
+
+fn min(xs: &[f64]) -> Option<f64> {
+    xs.iter().copied().reduce(f64::min)
+}
+
+fn sum(xs: &[f64]) -> Option<f64> {
+    // `Sum` starts from -0, which would make an empty sum negative zero
+    Some(xs.iter().fold(0.0, |acc, x| acc + x))
+}
+
+fn avg(xs: &[f64]) -> Option<f64> {
+    if xs.is_empty() {
+        None
+    } else {
+        Some(xs.iter().sum::<f64>() / xs.len() as f64)
+    }
+}
+
+/// Largest index accepted by integer sequence functions, to keep computation time reasonable
+pub const MAX_SEQUENCE_INDEX: u64 = 10000;
+
+pub(crate) fn factorial(n: u64) -> BigInt {
+    (1..=n).map(BigInt::from).product()
+}
+
+fn fib(n: u64) -> BigInt {
+    let (mut a, mut b) = (BigInt::from(0), BigInt::from(1));
+    for _ in 0..n {
+        let next = &a + &b;
+        a = std::mem::replace(&mut b, next);
+    }
+    a
+}
+
+fn catalan(n: u64) -> BigInt {
+    // C(k + 1) = C(k) * 2(2k + 1) / (k + 2)
+    (0..n).fold(BigInt::from(1), |c, k| c * (2 * (2 * k + 1)) / (k + 2))
+}
+
+fn triangular(n: u64) -> BigInt {
+    BigInt::from(n) * (n + 1) / 2
+}
+
+/// Bitwise exclusive or, which requires integers as the other bitwise operators do
+fn xor(args: &[Value]) -> EvalResult<Value> {
+    let a = super::to_integer(Number(args[0].as_f64()?))?;
+    let b = super::to_integer(Number(args[1].as_f64()?))?;
+    Ok(((a ^ b) as f64).into())
+}
+
+fn modinv(args: &[Value]) -> EvalResult<Value> {
+    let (a, m) = (modular_operand(&args[0])?, modulus(&args[1])?);
+    let inv = modular_inverse(a, m)?;
+    Ok((inv as f64).into())
+}
+
+fn modpow(args: &[Value]) -> EvalResult<Value> {
+    let (a, b, m) = (
+        modular_operand(&args[0])?,
+        modular_operand(&args[1])?,
+        modulus(&args[2])?,
+    );
+    let value = if b >= 0 {
+        modular::pow(a, b, m)
+    } else {
+        modular::pow(modular_inverse(a, m)?, -b, m)
+    };
+    Ok((value as f64).into())
+}
+
+fn modular_inverse(a: i128, m: i128) -> EvalResult<i128> {
+    modular::inverse(a, m)
+        .ok_or_else(|| EvalError::TypeError(format!("{} has no inverse modulo {}", a, m)))
+}
+
+fn modular_operand(x: &Value) -> EvalResult<i128> {
+    let n = x.as_f64()?;
+    if n.fract() == 0.0 && n.abs() <= MAX_MODULUS as f64 {
+        Ok(n as i128)
+    } else {
+        Err(EvalError::TypeError(format!(
+            "Modular arithmetic requires integers of at most {} in absolute value, but got {}",
+            MAX_MODULUS, x
+        )))
+    }
+}
+
+fn modulus(m: &Value) -> EvalResult<i128> {
+    match modular_operand(m) {
+        Ok(m) if m >= 2 => Ok(m),
+        _ => Err(EvalError::TypeError(format!(
+            "Modulus must be an integer between 2 and {}, but got {}",
+            MAX_MODULUS, m
+        ))),
+    }
+}
+
+/// Remainder of Euclidean division, which is never negative, unlike that of `%`
+fn modulo(args: &[Value]) -> EvalResult<Value> {
+    let (_, r) = div_euclid(&args[0], &args[1])?;
+    Ok(r)
+}
+
+/// Quotient and remainder of Euclidean division as a list
+fn divmod(args: &[Value]) -> EvalResult<Value> {
+    let (q, r) = div_euclid(&args[0], &args[1])?;
+    Ok(Value::List(vec![q, r]))
+}
+
+/// Divides `a` by `b` so that the remainder is between 0 and `|b|`, keeping exact integers exact
+fn div_euclid(a: &Value, b: &Value) -> EvalResult<(Value, Value)> {
+    if matches!(a, Value::Integer(_)) || matches!(b, Value::Integer(_)) {
+        if let (Some(a), Some(b)) = (a.to_exact_integer(), b.to_exact_integer()) {
+            if !b.is_zero() {
+                let mut r = &a % &b;
+                if r.is_negative() {
+                    r += b.abs();
+                }
+                let q = (a - &r) / b;
+                return Ok((q.into(), r.into()));
+            }
+        }
+    }
+    let (a, b) = (a.as_f64()?, b.as_f64()?);
+    Ok((a.div_euclid(b).into(), a.rem_euclid(b).into()))
+}
+
+fn to_base(args: &[Value]) -> EvalResult<Value> {
+    let x = args[0].to_exact_integer().ok_or_else(|| {
+        EvalError::TypeError(format!("to_base requires an integer, but got {}", args[0]))
+    })?;
+    Ok(Value::String(x.to_str_radix(to_radix(&args[1])?)))
+}
+
+fn from_base(args: &[Value]) -> EvalResult<Value> {
+    let digits = match &args[0] {
+        Value::String(x) => x,
+        x => {
+            return Err(EvalError::TypeError(format!(
+                "from_base requires a string, but got {}",
+                x
+            )))
+        }
+    };
+    let radix = to_radix(&args[1])?;
+    BigInt::parse_bytes(digits.as_bytes(), radix)
+        .map(Value::Integer)
+        .ok_or_else(|| {
+            EvalError::TypeError(format!(
+                "{} is not a valid number in base {}",
+                args[0], radix
+            ))
+        })
+}
+
+fn to_radix(base: &Value) -> EvalResult<u32> {
+    base.to_exact_integer()
+        .and_then(|x| x.to_u32())
+        .filter(|x| (2..=36).contains(x))
+        .ok_or_else(|| {
+            EvalError::TypeError(format!(
+                "Base must be an integer between 2 and 36, but got {}",
+                base
+            ))
+        })
+}
+
+/// Number of elements of a list, or characters of a string
+fn len(args: &[Value]) -> EvalResult<Value> {
+    let len = match &args[0] {
+        Value::String(x) => x.chars().count(),
+        x => x.as_list()?.len(),
+    };
+    Ok((len as f64).into())
+}
+
+fn sort(args: &[Value]) -> EvalResult<Value> {
+    let mut xs = args[0].as_list()?.to_vec();
+    // elements are finite, so that they are totally ordered
+    xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    Ok(Value::List(xs))
+}
+
+/// Removes repeated elements, keeping the first occurrences in order
+fn unique(args: &[Value]) -> EvalResult<Value> {
+    let mut xs: Vec<Value> = Vec::new();
+    for x in args[0].as_list()? {
+        if !xs.contains(x) {
+            xs.push(x.clone());
+        }
+    }
+    Ok(Value::List(xs))
+}
+
+fn reverse(args: &[Value]) -> EvalResult<Value> {
+    Ok(Value::List(
+        args[0].as_list()?.iter().rev().cloned().collect(),
+    ))
+}
+
+/// Applies a function to each element of a list
+fn map(args: &[Value], env: &Environment) -> EvalResult<Value> {
+    let xs = args[1]
+        .as_list()?
+        .iter()
+        .map(|x| super::call_value(&args[0], std::slice::from_ref(x), env))
+        .collect::<EvalResult<_>>()?;
+    Ok(Value::List(xs))
+}
+
+/// Keeps the elements of a list for which a function returns nonzero
+fn filter(args: &[Value], env: &Environment) -> EvalResult<Value> {
+    let mut kept = Vec::new();
+    for x in args[1].as_list()? {
+        if super::call_value(&args[0], std::slice::from_ref(x), env)?.as_f64()? != 0.0 {
+            kept.push(x.clone());
+        }
+    }
+    Ok(Value::List(kept))
+}
+
+/// Combines the elements of a list from the left with a function taking two arguments,
+/// starting from an initial value
+fn reduce(args: &[Value], env: &Environment) -> EvalResult<Value> {
+    args[2]
+        .as_list()?
+        .iter()
+        .try_fold(args[1].clone(), |acc, x| {
+            super::call_value(&args[0], &[acc, x.clone()], env)
+        })
+}
+
+/// Calls a function taking no arguments `n` times and returns the average time in seconds
+#[cfg(not(target_arch = "wasm32"))]
+fn timeit(args: &[Value], env: &Environment) -> EvalResult<Value> {
+    let n = match args[1].to_exact_integer().and_then(|n| n.to_u32()) {
+        Some(n) if n > 0 => n,
+        _ => {
+            return Err(EvalError::TypeError(format!(
+                "The function timeit takes a positive number of calls, but got {}",
+                args[1]
+            )))
+        }
+    };
+    let start = std::time::Instant::now();
+    for _ in 0..n {
+        super::call_value(&args[0], &[], env)?;
+    }
+    Ok((start.elapsed().as_secs_f64() / f64::from(n)).into())
+}
+
+#[cfg(feature = "statrs")]
+fn gamma(args: &[Value]) -> EvalResult<Value> {
+    let x = args[0].as_f64()?;
+    Ok(super::check_overflow("gamma", x, statrs::function::gamma::gamma(x))?.into())
+}
+
+fn assert(args: &[Value]) -> EvalResult<Value> {
+    if args[0].as_f64()? != 0.0 {
+        Ok(args[0].clone())
+    } else {
+        Err(EvalError::AssertionError {
+            expr: String::new(),
+            detail: String::new(),
+        })
+    }
+}
+
+/// Asserts that two values are equal, or differ by at most the tolerance given as the third argument
+fn assert_eq(args: &[Value]) -> EvalResult<Value> {
+    let (a, b) = (&args[0], &args[1]);
+    let tol = args.get(2).map_or(Ok(0.0), Value::as_f64)?;
+    let equal = match (a, b) {
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::Quantity(a), Value::Quantity(b)) => {
+            a.dimension() == b.dimension() && (a.value - b.value).abs() <= tol
+        }
+        _ if a.is_numeric() && b.is_numeric() => match (a.to_exact_integer(), b.to_exact_integer())
+        {
+            (Some(x), Some(y)) if tol == 0.0 => x == y,
+            _ => (a.as_f64()? - b.as_f64()?).abs() <= tol,
+        },
+        _ => false,
+    };
+
+    if equal {
+        Ok(a.clone())
+    } else {
+        Err(EvalError::AssertionError {
+            expr: String::new(),
+            detail: format!(" ({} ≠ {})", a, b),
+        })
+    }
+}
+
+/// Relative tolerance of `isclose` by default, which allows for rounding errors
+/// accumulated over a number of operations
+const ISCLOSE_RTOL: f64 = 1e-9;
+
+/// Returns 1 if `a` and `b` differ by at most `rtol` times the larger of them
+/// or by at most `atol`, and 0 otherwise
+fn isclose(args: &[Value]) -> EvalResult<Value> {
+    let (a, b) = (args[0].as_f64()?, args[1].as_f64()?);
+    let rtol = args.get(2).map_or(Ok(ISCLOSE_RTOL), Value::as_f64)?;
+    let atol = args.get(3).map_or(Ok(0.0), Value::as_f64)?;
+    let tol = (rtol * a.abs().max(b.abs())).max(atol);
+    // infinities are only close to themselves
+    let close = a == b || (a - b).abs() <= tol;
+    Ok(Value::from(if close { 1.0 } else { 0.0 }))
+}
@@ -0,0 +1,226 @@
+use super::{
+    env::{Arity, GenericFunc},
+    value::Value,
+    EvalError, EvalResult,
+};
+use crate::language::Number;
+
+pub(super) const FUNCS: &[GenericFunc] = &[
+    ("quadratic", Arity::exact(3), quadratic),
+    ("cubic", Arity::exact(4), cubic),
+    ("linsolve", Arity::exact(2), linsolve),
+];
+
+/// Distinct real roots of ax^2 + bx + c in ascending order
+pub fn quadratic(args: &[Value]) -> EvalResult<Value> {
+    let coeffs = numbers(args)?;
+    roots_to_list(solve_quadratic(coeffs[0], coeffs[1], coeffs[2])?)
+}
+
+/// Distinct real roots of ax^3 + bx^2 + cx + d in ascending order
+pub fn cubic(args: &[Value]) -> EvalResult<Value> {
+    let coeffs = numbers(args)?;
+    roots_to_list(solve_cubic(coeffs[0], coeffs[1], coeffs[2], coeffs[3])?)
+}
+
+/// Solution x of Ax = b, where A is a square matrix given as a list of rows
+pub fn linsolve(args: &[Value]) -> EvalResult<Value> {
+    let rows = args[0].as_list()?;
+    let mut a = rows
+        .iter()
+        .map(|row| numbers(row.as_list()?))
+        .collect::<EvalResult<Vec<_>>>()?;
+    let mut b = numbers(args[1].as_list()?)?;
+
+    let n = a.len();
+    if n == 0 || a.iter().any(|row| row.len() != n) {
+        return Err(EvalError::TypeError(
+            "The coefficient matrix must be a non-empty square matrix".to_string(),
+        ));
+    }
+    if b.len() != n {
+        return Err(EvalError::TypeError(format!(
+            "Expected {} right-hand side values, but got {}",
+            n,
+            b.len()
+        )));
+    }
+
+    // Gaussian elimination with partial pivoting
+    let scale = a.iter().flatten().fold(0.0f64, |acc, x| acc.max(x.abs()));
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+            .unwrap();
+        if a[pivot][col].abs() <= scale * n as f64 * f64::EPSILON {
+            return Err(EvalError::TypeError(
+                "The system has no unique solution because the matrix is singular".to_string(),
+            ));
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let (upper, lower) = a.split_at_mut(col + 1);
+        let pivot_row = &upper[col];
+        for (i, row) in lower.iter_mut().enumerate() {
+            let factor = row[col] / pivot_row[col];
+            for (x, p) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                *x -= factor * p;
+            }
+            b[col + 1 + i] -= factor * b[col];
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    if let Some(x) = x.iter().find(|x| !x.is_finite()) {
+        return Err(EvalError::NumericalError(Number(*x)));
+    }
+    Ok(Value::List(x.into_iter().map(Value::from).collect()))
+}
+
+fn solve_linear(a: f64, b: f64) -> EvalResult<Vec<f64>> {
+    if a != 0.0 {
+        Ok(vec![-b / a])
+    } else if b != 0.0 {
+        Ok(Vec::new())
+    } else {
+        Err(EvalError::TypeError(
+            "Every number is a root when all the coefficients are 0".to_string(),
+        ))
+    }
+}
+
+fn solve_quadratic(a: f64, b: f64, c: f64) -> EvalResult<Vec<f64>> {
+    if a == 0.0 {
+        return solve_linear(b, c);
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return Ok(Vec::new());
+    }
+    // avoids cancellation between -b and the square root
+    let q = -(b + b.signum() * discriminant.sqrt()) / 2.0;
+    if q == 0.0 {
+        // b and c are both 0
+        return Ok(vec![0.0]);
+    }
+    Ok(vec![q / a, c / q])
+}
+
+fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> EvalResult<Vec<f64>> {
+    if a == 0.0 {
+        return solve_quadratic(b, c, d);
+    }
+
+    // substituting x = t - b/3a gives the depressed cubic t^3 + pt + q
+    let (b, c, d) = (b / a, c / a, d / a);
+    let shift = -b / 3.0;
+    let p = c - b * b / 3.0;
+    let q = 2.0 * b * b * b / 27.0 - b * c / 3.0 + d;
+    let discriminant = (q / 2.0).powi(2) + (p / 3.0).powi(3);
+
+    let ts = if p == 0.0 && q == 0.0 {
+        vec![0.0]
+    } else if discriminant > 0.0 {
+        let sqrt = discriminant.sqrt();
+        vec![(-q / 2.0 + sqrt).cbrt() + (-q / 2.0 - sqrt).cbrt()]
+    } else if discriminant == 0.0 {
+        vec![3.0 * q / p, -3.0 * q / (2.0 * p)]
+    } else {
+        // three real roots, found with the trigonometric method
+        let m = 2.0 * (-p / 3.0).sqrt();
+        let theta = (3.0 * q / (p * m)).clamp(-1.0, 1.0).acos() / 3.0;
+        (0..3)
+            .map(|k| m * (theta - 2.0 * std::f64::consts::PI * k as f64 / 3.0).cos())
+            .collect()
+    };
+
+    // polish the roots with Newton's method to reduce rounding errors
+    let f = |x: f64| ((x + b) * x + c) * x + d;
+    let df = |x: f64| (3.0 * x + 2.0 * b) * x + c;
+    Ok(ts
+        .into_iter()
+        .map(|t| {
+            let mut x = t + shift;
+            for _ in 0..8 {
+                let slope = df(x);
+                if slope == 0.0 {
+                    break;
+                }
+                x -= f(x) / slope;
+            }
+            x
+        })
+        .collect())
+}
+
+fn roots_to_list(mut roots: Vec<f64>) -> EvalResult<Value> {
+    // intermediate values overflow for coefficients of extreme magnitudes
+    if let Some(x) = roots.iter().find(|x| !x.is_finite()) {
+        return Err(EvalError::NumericalError(Number(*x)));
+    }
+    roots.sort_by(f64::total_cmp);
+
+    // multiple roots found separately differ only by rounding errors, which are around
+    // the square root of the machine epsilon for double roots. Their errors tend to cancel out
+    // when averaged.
+    let mut groups: Vec<Vec<f64>> = Vec::new();
+    for x in roots {
+        match groups.last_mut() {
+            Some(group) if (x - group[0]).abs() <= 1e-6 * x.abs().max(group[0].abs()).max(1.0) => {
+                group.push(x)
+            }
+            _ => groups.push(vec![x]),
+        }
+    }
+    Ok(Value::List(
+        groups
+            .iter()
+            .map(|group| (group.iter().sum::<f64>() / group.len() as f64).into())
+            .collect(),
+    ))
+}
+
+fn numbers(args: &[Value]) -> EvalResult<Vec<f64>> {
+    args.iter().map(Value::as_f64).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(x: f64) -> Value {
+        x.into()
+    }
+
+    #[test]
+    fn quadratic_roots() {
+        let roots = quadratic(&[num(1.0), num(-3.0), num(2.0)]).unwrap();
+        assert_eq!(roots, Value::List(vec![num(1.0), num(2.0)]));
+    }
+
+    #[test]
+    fn extreme_coefficients_are_numerical_errors() {
+        assert!(matches!(
+            quadratic(&[num(1e300), num(1e300), num(1e300)]),
+            Err(EvalError::NumericalError(_))
+        ));
+        assert!(matches!(
+            cubic(&[num(1e-300), num(1.0), num(1.0), num(1.0)]),
+            Err(EvalError::NumericalError(_))
+        ));
+    }
+
+    #[test]
+    fn linsolve_with_non_finite_coefficients_is_an_error() {
+        let matrix = Value::List(vec![
+            Value::List(vec![num(f64::NAN), num(1.0)]),
+            Value::List(vec![num(1.0), num(1.0)]),
+        ]);
+        let rhs = Value::List(vec![num(1.0), num(2.0)]);
+        assert!(linsolve(&[matrix, rhs]).is_err());
+    }
+}
@@ -0,0 +1,452 @@
+use super::{
+    env::{Arity, GenericFunc},
+    settings,
+    value::Value,
+    EvalError, EvalResult,
+};
+use itertools::Itertools;
+use num_bigint::BigInt;
+
+pub(super) const FUNCS: &[GenericFunc] = &[("format", Arity::exact(2), format)];
+
+/// Formats a number as specified by a string of the form
+/// `[[fill]align][sign][0][width][,][.precision][type]`, following Python's format specs
+pub fn format(args: &[Value]) -> EvalResult<Value> {
+    let spec = match &args[1] {
+        Value::String(spec) => spec,
+        x => {
+            return Err(EvalError::TypeError(format!(
+                "Expected a format spec such as \"0.3f\", but got {}",
+                x
+            )))
+        }
+    };
+    let spec = Spec::parse(spec)?;
+    Ok(Value::String(spec.apply(&args[0])?))
+}
+
+/// Largest width and precision, which keep specs from asking for huge strings
+const MAX_WIDTH: usize = 1000;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Align {
+    Left,
+    Right,
+    Center,
+    /// Padding goes between the sign and the digits, as with the `0` flag
+    AfterSign,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Type {
+    /// As results are shown
+    Default,
+    /// Integer, with `d`
+    Integer,
+    /// Fixed number of decimals, with `f`
+    Fixed,
+    /// E-notation, with `e`
+    Exponent,
+    /// Multiplied by 100 with a percent sign, with `%`
+    Percent,
+}
+
+#[derive(Debug)]
+struct Spec {
+    fill: char,
+    align: Align,
+    /// Sign shown for non-negative numbers
+    plus: Option<char>,
+    width: usize,
+    /// Whether thousands are separated with commas
+    grouping: bool,
+    precision: Option<usize>,
+    ty: Type,
+}
+
+impl Spec {
+    fn parse(s: &str) -> EvalResult<Self> {
+        let invalid = || EvalError::TypeError(format!("Invalid format spec \"{}\"", s));
+        let to_align = |c| match c {
+            '<' => Some(Align::Left),
+            '>' => Some(Align::Right),
+            '^' => Some(Align::Center),
+            '=' => Some(Align::AfterSign),
+            _ => None,
+        };
+        let mut chars: Vec<char> = s.chars().collect();
+        chars.reverse();
+        let mut spec = Self {
+            fill: ' ',
+            align: Align::Right,
+            plus: None,
+            width: 0,
+            grouping: false,
+            precision: None,
+            ty: Type::Default,
+        };
+
+        let n = chars.len();
+        if let Some(align) = chars.get(n.wrapping_sub(2)).copied().and_then(to_align) {
+            spec.fill = chars[n - 1];
+            spec.align = align;
+            chars.truncate(n - 2);
+        } else if let Some(align) = chars.last().copied().and_then(to_align) {
+            spec.align = align;
+            chars.pop();
+        }
+        if let Some(c @ ('+' | ' ')) = chars.last().copied() {
+            spec.plus = Some(c);
+            chars.pop();
+        } else if chars.last() == Some(&'-') {
+            chars.pop();
+        }
+        if chars.last() == Some(&'0') {
+            spec.fill = '0';
+            spec.align = Align::AfterSign;
+            chars.pop();
+        }
+        spec.width = take_number(&mut chars)?.unwrap_or(0);
+        if chars.last() == Some(&',') {
+            spec.grouping = true;
+            chars.pop();
+        }
+        if chars.last() == Some(&'.') {
+            chars.pop();
+            spec.precision = Some(take_number(&mut chars)?.ok_or_else(invalid)?);
+        }
+        spec.ty = match chars.pop() {
+            None => return Ok(spec),
+            Some('d') => Type::Integer,
+            Some('f') => Type::Fixed,
+            Some('e') => Type::Exponent,
+            Some('%') => Type::Percent,
+            Some(_) => return Err(invalid()),
+        };
+        if chars.is_empty() {
+            Ok(spec)
+        } else {
+            Err(invalid())
+        }
+    }
+
+    fn apply(&self, x: &Value) -> EvalResult<String> {
+        let (negative, digits) = match x.to_exact_integer() {
+            // exact integers keep all of their digits
+            Some(n) if matches!(x, Value::Integer(_)) || self.ty == Type::Integer => {
+                (n < BigInt::from(0), self.integer_digits(&n))
+            }
+            _ if self.ty == Type::Integer => {
+                return Err(EvalError::TypeError(format!(
+                    "Format d requires an integer, but got {}",
+                    x
+                )))
+            }
+            _ => {
+                let x = x.as_f64()?;
+                (x.is_sign_negative() && x != 0.0, self.float_digits(x.abs()))
+            }
+        };
+        let digits = if self.grouping {
+            group_thousands(&digits)
+        } else {
+            digits
+        };
+
+        let sign = if negative {
+            "-".to_string()
+        } else {
+            self.plus.map(String::from).unwrap_or_default()
+        };
+        let len = sign.chars().count() + digits.chars().count();
+        let padding = self.width.saturating_sub(len);
+        let fill = |n| self.fill.to_string().repeat(n);
+        Ok(match self.align {
+            Align::Left => format!("{}{}{}", sign, digits, fill(padding)),
+            Align::Right => format!("{}{}{}", fill(padding), sign, digits),
+            Align::Center => format!(
+                "{}{}{}{}",
+                fill(padding / 2),
+                sign,
+                digits,
+                fill(padding - padding / 2)
+            ),
+            Align::AfterSign => format!("{}{}{}", sign, fill(padding), digits),
+        })
+    }
+
+    /// Digits of the magnitude of an exact integer
+    fn integer_digits(&self, n: &BigInt) -> String {
+        let digits = n.magnitude().to_string();
+        match (self.ty, self.precision) {
+            (Type::Fixed, precision) => match precision.unwrap_or(6) {
+                0 => digits,
+                precision => format!("{}.{}", digits, "0".repeat(precision)),
+            },
+            (Type::Exponent | Type::Percent, _) => self.float_digits(digits.parse().unwrap()),
+            _ => digits,
+        }
+    }
+
+    /// Digits of a non-negative floating-point number
+    fn float_digits(&self, x: f64) -> String {
+        match self.ty {
+            Type::Default => match self.precision {
+                Some(precision) => format!("{:.*}", precision, x),
+                None => shortest(x),
+            },
+            Type::Fixed | Type::Integer => format!("{:.*}", self.precision.unwrap_or(6), x),
+            Type::Exponent => {
+                // Python writes the exponent with a sign and at least two digits, as in 1.5e+03
+                let formatted = format!("{:.*e}", self.precision.unwrap_or(6), x);
+                let (mantissa, exponent) = formatted.split_once('e').unwrap();
+                format!("{}e{:+03}", mantissa, exponent.parse::<i32>().unwrap())
+            }
+            Type::Percent => format!("{:.*}%", self.precision.unwrap_or(6), x * 100.0),
+        }
+    }
+}
+
+/// Takes a width or precision, which is `None` if there are no digits
+fn take_number(chars: &mut Vec<char>) -> EvalResult<Option<usize>> {
+    let mut digits = String::new();
+    while let Some(c) = chars.last().filter(|c| c.is_ascii_digit()) {
+        digits.push(*c);
+        chars.pop();
+    }
+    if digits.is_empty() {
+        return Ok(None);
+    }
+    match digits.parse() {
+        Ok(n) if n <= MAX_WIDTH => Ok(Some(n)),
+        _ => Err(EvalError::TypeError(format!(
+            "Widths and precisions in format specs must be at most {}, but got {}",
+            MAX_WIDTH, digits
+        ))),
+    }
+}
+
+/// Separates groups of three digits of the integer part of `digits` with commas
+fn group_thousands(digits: &str) -> String {
+    let int_len = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    let (int, rest) = digits.split_at(int_len);
+    let mut grouped = String::new();
+    for (i, c) in int.chars().enumerate() {
+        if i > 0 && (int_len - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped + rest
+}
+
+/// Shortest representation of `x` as in results, without colors
+fn shortest(x: f64) -> String {
+    let mut buffer = ryu::Buffer::new();
+    let formatted = buffer.format(x);
+    formatted
+        .strip_suffix(".0")
+        .unwrap_or(formatted)
+        .to_string()
+}
+
+/// Name of a notation and the function writing a value in it, given the bit width if any
+type Notation = (&'static str, fn(&Value, Option<u32>) -> EvalResult<String>);
+
+/// Notations that `to` converts numbers to, as in `255 to hex`
+const NOTATIONS: &[Notation] = &[
+    ("hex", |x, bits| {
+        radix(x, bits, "hexadecimal", |n| format!("{:#x}", n))
+    }),
+    ("oct", |x, bits| {
+        radix(x, bits, "octal", |n| format!("{:#o}", n))
+    }),
+    ("bin", |x, bits| {
+        radix(x, bits, "binary", |n| format!("{:#b}", n))
+    }),
+    ("scientific", |x, _| Ok(format!("{:e}", x.as_f64()?))),
+    ("sci", |x, _| Ok(format!("{:e}", x.as_f64()?))),
+    ("percent", |x, _| percent(x)),
+    ("fraction", |x, _| fraction(x)),
+];
+
+/// Writes `x` in the notation `name` as a string, or returns `None` if there is no such notation.
+/// With a bit width of `bits`, integers are written in radixes as their two's-complement bit
+/// patterns, as results are shown.
+pub fn to_notation(x: &Value, name: &str, bits: Option<u32>) -> Option<EvalResult<Value>> {
+    let (_, convert) = NOTATIONS.iter().find(|(x, _)| *x == name)?;
+    Some(convert(x, bits).map(Value::String))
+}
+
+fn radix(x: &Value, bits: Option<u32>, base: &str, f: fn(&BigInt) -> String) -> EvalResult<String> {
+    match (x.to_exact_integer(), bits) {
+        (Some(_), Some(bits)) => Ok(f(&settings::to_bit_pattern(x.as_f64()?, bits).into())),
+        (Some(n), None) => Ok(f(&n)),
+        (None, _) => Err(EvalError::TypeError(format!(
+            "Only integers can be written in {}, but got {}",
+            base, x
+        ))),
+    }
+}
+
+/// Writes `x` times 100 with a percent sign, moving the decimal point of its shortest
+/// representation so that no rounding errors creep in
+fn percent(x: &Value) -> EvalResult<String> {
+    let shown = match x.to_exact_integer() {
+        Some(n) if matches!(x, Value::Integer(_)) => n.to_string(),
+        _ => shortest(x.as_f64()?),
+    };
+    let (sign, shown) = match shown.strip_prefix('-') {
+        Some(magnitude) => ("-", magnitude),
+        None => ("", shown.as_str()),
+    };
+    let shifted = match shown.split_once('e') {
+        Some((mantissa, exp)) => match exp.parse::<i32>().unwrap() + 2 {
+            0 => mantissa.to_string(),
+            exp => format!("{}e{}", mantissa, exp),
+        },
+        None => {
+            let (int, frac) = shown.split_once('.').unwrap_or((shown, ""));
+            let frac = format!("{:0<2}", frac);
+            let int = format!("{}{}", int, &frac[..2]);
+            let int = match int.trim_start_matches('0') {
+                "" => "0",
+                int => int,
+            };
+            match &frac[2..] {
+                "" => int.to_string(),
+                frac => format!("{}.{}", int, frac),
+            }
+        }
+    };
+    Ok(format!("{}{}%", sign, shifted))
+}
+
+fn fraction(x: &Value) -> EvalResult<String> {
+    if let Some(n) = x.to_exact_integer() {
+        return Ok(n.to_string());
+    }
+    settings::fraction_form(x.as_f64()?)
+        .ok_or_else(|| EvalError::TypeError(format!("Cannot write {} as a simple fraction", x)))
+}
+
+/// Notation `format_value` writes numbers in
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum NumberFormat {
+    /// Fewest digits that read back as the same number, as results are shown
+    #[default]
+    Shortest,
+    /// Fixed number of decimals
+    Fixed(usize),
+    /// E-notation with the given number of decimals in the mantissa, or the fewest
+    /// digits that read back as the same number if `None`
+    Scientific(Option<usize>),
+    /// E-notation with exponents that are multiples of 3, with the given number of decimals
+    /// in the mantissa, or the fewest digits that read back as the same number if `None`
+    Engineering(Option<usize>),
+}
+
+/// Writes `value` without colors, with numbers in `format`, so that the output depends only on
+/// the value and the format, not on settings, the platform or whether colors are enabled.
+///
+/// Exact integers keep all of their digits, except in E-notation with a given number of decimals.
+pub fn format_value(value: &Value, format: NumberFormat) -> String {
+    match value {
+        Value::Number(x) => format_number(x.0, format),
+        Value::Integer(n) => match format {
+            NumberFormat::Shortest | NumberFormat::Fixed(0) => n.to_string(),
+            NumberFormat::Fixed(decimals) => format!("{}.{}", n, "0".repeat(decimals)),
+            NumberFormat::Scientific(None) | NumberFormat::Engineering(None) => {
+                let digits = n.magnitude().to_string();
+                let exp = digits.len() as i32 - 1;
+                let digits = match digits.trim_end_matches('0') {
+                    "" => "0",
+                    digits => digits,
+                };
+                exponent_form(
+                    n < &BigInt::from(0),
+                    digits,
+                    exp,
+                    format == NumberFormat::Engineering(None),
+                )
+            }
+            _ => format_number(value.as_f64().unwrap(), format),
+        },
+        Value::Quantity(x) => format!("{} {}", format_number(x.magnitude(), format), x.unit.symbol),
+        Value::String(s) => format!("\"{}\"", s),
+        Value::List(xs) => format!(
+            "[{}]",
+            xs.iter().map(|x| format_value(x, format)).join(", ")
+        ),
+        Value::Function(f) if f.bound.is_empty() => f.name.0.clone(),
+        Value::Function(f) => format!(
+            "{}({}, …)",
+            f.name.0,
+            f.bound.iter().map(|x| format_value(x, format)).join(", ")
+        ),
+    }
+}
+
+/// Writes `x` in `format` as `format_value` does
+pub fn format_number(x: f64, format: NumberFormat) -> String {
+    if !x.is_finite() {
+        return shortest(x);
+    }
+    match format {
+        NumberFormat::Shortest => shortest(x),
+        NumberFormat::Fixed(decimals) => format!("{:.*}", decimals, x),
+        NumberFormat::Scientific(decimals) => {
+            let (digits, exp) = decompose(x, decimals);
+            exponent_form(x.is_sign_negative(), &digits, exp, false)
+        }
+        NumberFormat::Engineering(None) => {
+            let (digits, exp) = decompose(x, None);
+            exponent_form(x.is_sign_negative(), &digits, exp, true)
+        }
+        NumberFormat::Engineering(Some(decimals)) => {
+            // rounding may carry over to the next power of 10, which moves the decimal point
+            let (_, mut exp) = decompose(x, None);
+            loop {
+                let shift = exp.rem_euclid(3) as usize;
+                let (digits, rounded_exp) = decompose(x, Some(shift + decimals));
+                if rounded_exp == exp {
+                    return exponent_form(x.is_sign_negative(), &digits, exp, true);
+                }
+                exp = rounded_exp;
+            }
+        }
+    }
+}
+
+/// Significant digits of the magnitude of `x` and the exponent of the first one,
+/// rounded to `decimals` digits after the first one if given
+fn decompose(x: f64, decimals: Option<usize>) -> (String, i32) {
+    let formatted = match decimals {
+        Some(decimals) => format!("{:.*e}", decimals, x.abs()),
+        None => format!("{:e}", x.abs()),
+    };
+    let (mantissa, exp) = formatted.split_once('e').unwrap();
+    (mantissa.replace('.', ""), exp.parse().unwrap())
+}
+
+/// Writes significant `digits` with the exponent `exp` of the first one in E-notation,
+/// with an exponent that is a multiple of 3 if `engineering`
+fn exponent_form(negative: bool, digits: &str, exp: i32, engineering: bool) -> String {
+    let shift = if engineering {
+        exp.rem_euclid(3) as usize
+    } else {
+        0
+    };
+    let digits = format!("{:0<width$}", digits, width = shift + 1);
+    let (int, frac) = digits.split_at(shift + 1);
+    format!(
+        "{}{}{}{}e{}",
+        if negative { "-" } else { "" },
+        int,
+        if frac.is_empty() { "" } else { "." },
+        frac,
+        exp - shift as i32
+    )
+}
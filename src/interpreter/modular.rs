@@ -0,0 +1,86 @@
+use super::{EvalError, EvalResult};
+use crate::language::{BinaryOp, Number};
+
+/// Reduces an integer `x` into the range `[0, modulus)`
+pub fn reduce(x: Number, modulus: u64) -> EvalResult<Number> {
+    Ok(Number(to_residue(x, modulus)? as f64))
+}
+
+/// Applies `op` in the ring of integers modulo `modulus`
+pub fn apply(op: BinaryOp, a: Number, b: Number, modulus: u64) -> EvalResult<Number> {
+    let m = i128::from(modulus);
+    let value = match op {
+        BinaryOp::Add => (to_residue(a, modulus)? + to_residue(b, modulus)?) % m,
+        BinaryOp::Subtract => (to_residue(a, modulus)? - to_residue(b, modulus)?).rem_euclid(m),
+        BinaryOp::Multiply => to_residue(a, modulus)? * to_residue(b, modulus)? % m,
+        BinaryOp::Divide => {
+            let inv = inverse(to_residue(b, modulus)?, m).ok_or_else(|| {
+                EvalError::TypeError(format!("{} has no inverse modulo {}", b, modulus))
+            })?;
+            to_residue(a, modulus)? * inv % m
+        }
+        BinaryOp::Power => {
+            let base = to_residue(a, modulus)?;
+            let exp = to_integer(b)?;
+            if exp >= 0 {
+                pow(base, exp, m)
+            } else {
+                let inv = inverse(base, m).ok_or_else(|| {
+                    EvalError::TypeError(format!("{} has no inverse modulo {}", a, modulus))
+                })?;
+                pow(inv, -exp, m)
+            }
+        }
+        _ if op.is_comparison() => {
+            let ordering = to_residue(a, modulus)?.cmp(&to_residue(b, modulus)?);
+            i128::from(op.holds(Some(ordering)))
+        }
+        _ => return reduce(op.apply(a, b)?, modulus),
+    };
+    Ok(Number(value as f64))
+}
+
+/// Computes `base^exp mod m` by repeated squaring
+pub fn pow(base: i128, mut exp: i128, m: i128) -> i128 {
+    let mut base = base.rem_euclid(m);
+    let mut result = 1 % m;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result * base % m;
+        }
+        base = base * base % m;
+        exp >>= 1;
+    }
+    result
+}
+
+/// Finds `x` such that `a * x ≡ 1 (mod m)` with the extended Euclidean algorithm
+pub fn inverse(a: i128, m: i128) -> Option<i128> {
+    let (mut old_r, mut r) = (a.rem_euclid(m), m);
+    let (mut old_s, mut s) = (1, 0);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_s, s) = (s, old_s - q * s);
+    }
+    if old_r == 1 {
+        Some(old_s.rem_euclid(m))
+    } else {
+        None
+    }
+}
+
+fn to_residue(x: Number, modulus: u64) -> EvalResult<i128> {
+    Ok(to_integer(x)?.rem_euclid(i128::from(modulus)))
+}
+
+fn to_integer(x: Number) -> EvalResult<i128> {
+    if x.0.fract() == 0.0 {
+        Ok(x.0 as i128)
+    } else {
+        Err(EvalError::TypeError(format!(
+            "Modular arithmetic requires integers, but got {}",
+            x
+        )))
+    }
+}
@@ -0,0 +1,202 @@
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    /// Bit width of integers in programmer mode, or `None` if programmer mode is off
+    pub bits: Option<u32>,
+    /// Modulus of modular arithmetic, or `None` if arithmetic is ordinary
+    pub modulus: Option<u64>,
+    /// Number of significant figures results are displayed with, or `None` if not rounded
+    pub sigfigs: Option<u32>,
+    /// How `round` and display with limited significant figures round numbers
+    pub rounding: RoundingMode,
+    /// Whether results are also displayed as fractions or multiples of pi where possible
+    pub exact: bool,
+    /// Whether calling a number, as in `x(2)`, multiplies it by the argument
+    pub call_multiplies: bool,
+    /// Whether implicit multiplication is rejected and definitions are checked more thoroughly
+    pub strict: bool,
+    /// Whether implicit multiplication, as in `2x`, is rejected even outside strict mode
+    pub explicit_mul: bool,
+    /// Whether overwriting a variable with a value of a very different magnitude,
+    /// or a function with a variable, is warned about
+    pub guard: bool,
+    /// Width that echoed statements and listed definitions are wrapped at,
+    /// or `None` if they are not wrapped
+    pub wrap_width: Option<usize>,
+}
+
+impl Settings {
+    /// Whether juxtaposed operands, as in `2x` or `2 (3)`, are multiplied
+    pub fn implicit_mul(&self) -> bool {
+        !self.strict && !self.explicit_mul
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum RoundingMode {
+    /// Rounds half away from zero
+    #[default]
+    HalfUp,
+    /// Rounds half to even, as in banker's rounding
+    HalfEven,
+    /// Rounds toward zero
+    Truncate,
+}
+
+impl RoundingMode {
+    pub const NAMES: &'static [(&'static str, Self)] = &[
+        ("half-up", Self::HalfUp),
+        ("half-even", Self::HalfEven),
+        ("truncate", Self::Truncate),
+    ];
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES
+            .iter()
+            .find(|(_, mode)| *mode == self)
+            .unwrap()
+            .0
+    }
+
+    /// Rounds `x` to an integer
+    pub fn round(self, x: f64) -> f64 {
+        match self {
+            Self::HalfUp => x.round(),
+            Self::HalfEven => x.round_ties_even(),
+            Self::Truncate => x.trunc(),
+        }
+    }
+}
+
+pub const BIT_WIDTHS: &[u32] = &[8, 16, 32, 64];
+
+/// Largest modulus for which every residue is exactly representable
+pub const MAX_MODULUS: u64 = 1 << 53;
+
+/// Most significant figures distinguishable in f64
+pub const MAX_SIGFIGS: u32 = 17;
+
+/// Rounds `x` to `n` significant figures
+pub fn round_to_sigfigs(x: f64, n: u32, mode: RoundingMode) -> f64 {
+    if x == 0.0 || !x.is_finite() {
+        return x;
+    }
+
+    // round the shortest decimal representation, so that 0.125 is a tie as it reads
+    let formatted = format!("{:e}", x.abs());
+    let (mantissa, exp) = formatted.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    let digits: Vec<u64> = mantissa
+        .bytes()
+        .filter(u8::is_ascii_digit)
+        .map(|c| (c - b'0').into())
+        .collect();
+    let n = n as usize;
+    if digits.len() <= n {
+        return x;
+    }
+
+    let (kept, rest) = digits.split_at(n);
+    let round_up = match mode {
+        RoundingMode::HalfUp => rest[0] >= 5,
+        RoundingMode::HalfEven => {
+            rest[0] > 5
+                || (rest[0] == 5 && (rest[1..].iter().any(|d| *d > 0) || kept[n - 1] % 2 == 1))
+        }
+        RoundingMode::Truncate => false,
+    };
+    let mantissa = kept.iter().fold(0, |acc, d| acc * 10 + d) + round_up as u64;
+    let rounded: f64 = format!("{}e{}", mantissa, exp - (n as i32 - 1))
+        .parse()
+        .unwrap();
+    rounded.copysign(x)
+}
+
+/// Narrowest width statements can be wrapped at
+pub const MIN_WRAP_WIDTH: usize = 20;
+
+/// Largest denominator of the fractions in exact forms
+pub const MAX_DENOMINATOR: i64 = 1000;
+
+/// Writes a non-integer `x` as a simple fraction, or a fraction times pi, such as `3/4` or `π/2`
+pub fn exact_form(x: f64) -> Option<String> {
+    if x.fract() == 0.0 {
+        return None;
+    }
+    fraction_form(x).or_else(|| pi_form(x))
+}
+
+/// Writes `x` as a simple fraction such as `3/4`
+pub fn fraction_form(x: f64) -> Option<String> {
+    let (p, q) = to_fraction(x)?;
+    Some(format!("{}/{}", p, q))
+}
+
+/// Writes a nonzero `x` as a fraction times pi such as `π/2`
+pub fn pi_form(x: f64) -> Option<String> {
+    if x == 0.0 {
+        return None;
+    }
+    let (p, q) = to_fraction(x / std::f64::consts::PI)?;
+    let numerator = match p {
+        1 => "π".to_string(),
+        -1 => "-π".to_string(),
+        _ => format!("{}π", p),
+    };
+    Some(match q {
+        1 => numerator,
+        _ => format!("{}/{}", numerator, q),
+    })
+}
+
+/// Finds the fraction with the smallest denominator up to `MAX_DENOMINATOR` that equals `x`
+/// to within rounding errors, using continued fractions
+fn to_fraction(x: f64) -> Option<(i64, i64)> {
+    if !x.is_finite() || x.abs() >= 1e9 {
+        return None;
+    }
+
+    // numerators and denominators of the last two convergents
+    let (mut h0, mut h1) = (0, 1);
+    let (mut k0, mut k1) = (1, 0);
+    let mut r = x;
+    loop {
+        let a = r.floor();
+        if k1 > 0 && a > MAX_DENOMINATOR as f64 {
+            return None;
+        }
+        let (h, k) = (a as i64 * h1 + h0, a as i64 * k1 + k0);
+        if k > MAX_DENOMINATOR {
+            return None;
+        }
+        if (h as f64 / k as f64 - x).abs() <= 4.0 * f64::EPSILON * x.abs() {
+            return Some((h, k));
+        }
+        if r == a {
+            return None;
+        }
+        r = 1.0 / (r - a);
+        (h0, h1) = (h1, h);
+        (k0, k1) = (k1, k);
+    }
+}
+
+/// Truncates `x` to an integer and wraps it around into the range of a signed `bits`-bit integer
+pub fn wrap_to_bits(x: f64, bits: u32) -> f64 {
+    let modulus = 1i128 << bits;
+    let wrapped = (x as i128).rem_euclid(modulus);
+    if wrapped >= modulus / 2 {
+        (wrapped - modulus) as f64
+    } else {
+        wrapped as f64
+    }
+}
+
+/// Two's-complement bit pattern of `x` as a `bits`-bit integer
+pub fn to_bit_pattern(x: f64, bits: u32) -> u64 {
+    let mask = if bits >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << bits) - 1
+    };
+    (x as i64 as u64) & mask
+}
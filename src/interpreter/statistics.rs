@@ -0,0 +1,95 @@
+use super::{
+    env::{Arity, GenericFunc},
+    value::Value,
+    EvalError, EvalResult,
+};
+
+pub(super) const FUNCS: &[GenericFunc] = &[
+    ("linreg", Arity::exact(2), linreg),
+    ("corr", Arity::exact(2), corr),
+    ("cov", Arity::exact(2), cov),
+];
+
+/// Fits a line to points by least squares, returning the slope, the intercept
+/// and the coefficient of determination
+pub fn linreg(args: &[Value]) -> EvalResult<Value> {
+    let (xs, ys) = paired("linreg", args)?;
+    let moments = Moments::new(&xs, &ys);
+    let slope = moments.sxy / moments.sxx;
+    let intercept = moments.mean_y - slope * moments.mean_x;
+    let r = moments.sxy / (moments.sxx * moments.syy).sqrt();
+    Ok(Value::List(vec![
+        slope.into(),
+        intercept.into(),
+        (r * r).into(),
+    ]))
+}
+
+/// Pearson correlation coefficient
+pub fn corr(args: &[Value]) -> EvalResult<Value> {
+    let (xs, ys) = paired("corr", args)?;
+    let moments = Moments::new(&xs, &ys);
+    Ok((moments.sxy / (moments.sxx * moments.syy).sqrt()).into())
+}
+
+/// Sample covariance
+pub fn cov(args: &[Value]) -> EvalResult<Value> {
+    let (xs, ys) = paired("cov", args)?;
+    let moments = Moments::new(&xs, &ys);
+    Ok((moments.sxy / (xs.len() - 1) as f64).into())
+}
+
+/// Means and sums of squared deviations of paired samples
+struct Moments {
+    mean_x: f64,
+    mean_y: f64,
+    sxx: f64,
+    syy: f64,
+    sxy: f64,
+}
+
+impl Moments {
+    fn new(xs: &[f64], ys: &[f64]) -> Self {
+        let mean = |xs: &[f64]| xs.iter().sum::<f64>() / xs.len() as f64;
+        let (mean_x, mean_y) = (mean(xs), mean(ys));
+        let sum = |f: &dyn Fn(f64, f64) -> f64| -> f64 {
+            xs.iter()
+                .zip(ys)
+                .map(|(x, y)| f(x - mean_x, y - mean_y))
+                .sum()
+        };
+        Self {
+            mean_x,
+            mean_y,
+            sxx: sum(&|dx, _| dx * dx),
+            syy: sum(&|_, dy| dy * dy),
+            sxy: sum(&|dx, dy| dx * dy),
+        }
+    }
+}
+
+/// Takes two lists of numbers of the same length, with at least two elements
+fn paired(name: &str, args: &[Value]) -> EvalResult<(Vec<f64>, Vec<f64>)> {
+    let numbers = |x: &Value| {
+        x.as_list()?
+            .iter()
+            .map(Value::as_f64)
+            .collect::<EvalResult<Vec<_>>>()
+    };
+    let (xs, ys) = (numbers(&args[0])?, numbers(&args[1])?);
+    if xs.len() != ys.len() {
+        return Err(EvalError::TypeError(format!(
+            "The function {} takes lists of the same length, but got {} and {} elements",
+            name,
+            xs.len(),
+            ys.len()
+        )));
+    }
+    if xs.len() < 2 {
+        return Err(EvalError::TypeError(format!(
+            "The function {} takes at least 2 points",
+            name
+        )));
+    }
+    Ok((xs, ys))
+}
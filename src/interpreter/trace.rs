@@ -0,0 +1,64 @@
+use super::value::Value;
+use std::{cell::RefCell, rc::Rc};
+
+/// Something that happened during evaluation
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// Sub-expression, with its operands already evaluated, was evaluated to a value
+    Rewrite {
+        /// Depth of nested user-defined function calls
+        depth: usize,
+        expr: String,
+        value: Value,
+    },
+    /// User-defined function was called
+    Call {
+        depth: usize,
+        name: String,
+        args: Vec<Value>,
+    },
+    /// User-defined function returned
+    Return { depth: usize, value: Value },
+}
+
+/// Shared log of events, which is kept across the copies of environments made during evaluation
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    events: Rc<RefCell<Vec<Event>>>,
+    rewrites: bool,
+    calls: bool,
+}
+
+impl Trace {
+    /// Trace recording every evaluated sub-expression
+    pub fn rewrites() -> Self {
+        Self {
+            rewrites: true,
+            ..Default::default()
+        }
+    }
+
+    /// Trace recording calls of user-defined functions
+    pub fn calls() -> Self {
+        Self {
+            calls: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn records_rewrites(&self) -> bool {
+        self.rewrites
+    }
+
+    pub fn records_calls(&self) -> bool {
+        self.calls
+    }
+
+    pub fn push(&self, event: Event) {
+        self.events.borrow_mut().push(event);
+    }
+
+    pub fn take(&self) -> Vec<Event> {
+        self.events.take()
+    }
+}
@@ -0,0 +1,361 @@
+use super::{value::Value, EvalError, EvalResult};
+use crate::language::{BinaryOp, Number};
+use std::{collections::BTreeMap, fmt};
+
+/// Exponents of base dimensions, e.g. `{"information": 1, "time": -1}` for data rates
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Dimension(BTreeMap<&'static str, i32>);
+
+const LENGTH: &str = "length";
+const TIME: &str = "time";
+const INFORMATION: &str = "information";
+const ANGLE: &str = "angle";
+
+/// Base dimensions and the symbols of their base units
+const BASE_UNITS: &[(&str, &str)] = &[
+    (LENGTH, "m"),
+    (TIME, "s"),
+    (INFORMATION, "B"),
+    (ANGLE, "rad"),
+];
+
+impl Dimension {
+    fn base(name: &'static str) -> Self {
+        Self(std::iter::once((name, 1)).collect())
+    }
+
+    pub fn is_dimensionless(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn combine(&self, other: &Self, sign: i32) -> Self {
+        let mut exps = self.0.clone();
+        for (name, exp) in &other.0 {
+            *exps.entry(name).or_default() += sign * exp;
+        }
+        exps.retain(|_, exp| *exp != 0);
+        Self(exps)
+    }
+
+    fn powi(&self, n: i32) -> Self {
+        Self(
+            self.0
+                .iter()
+                .map(|(name, exp)| (*name, exp * n))
+                .filter(|(_, exp)| *exp != 0)
+                .collect(),
+        )
+    }
+
+    /// Writes the dimension as a product of powers of `names` of base dimensions
+    fn fmt_with(&self, f: &mut fmt::Formatter<'_>, names: impl Fn(&str) -> String) -> fmt::Result {
+        let mut numerator = Vec::new();
+        let mut denominator = Vec::new();
+        for (name, exp) in &self.0 {
+            let (list, exp) = if *exp > 0 {
+                (&mut numerator, *exp)
+            } else {
+                (&mut denominator, -exp)
+            };
+            if exp == 1 {
+                list.push(names(name));
+            } else {
+                list.push(format!("{}^{}", names(name), exp));
+            }
+        }
+
+        match (numerator.is_empty(), denominator.is_empty()) {
+            (_, true) => write!(f, "{}", numerator.join("·")),
+            (true, false) => write!(f, "1/{}", denominator.join("·")),
+            (false, false) => write!(f, "{}/{}", numerator.join("·"), denominator.join("·")),
+        }
+    }
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_dimensionless() {
+            f.write_str("dimensionless")
+        } else {
+            self.fmt_with(f, |name| name.to_string())
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    pub symbol: String,
+    /// Size of the unit in base units
+    pub factor: f64,
+    pub dimension: Dimension,
+}
+
+impl Unit {
+    /// Coherent unit composed of base units
+    fn base_of(dimension: Dimension) -> Self {
+        struct Symbol<'a>(&'a Dimension);
+        impl fmt::Display for Symbol<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt_with(f, |name| {
+                    BASE_UNITS
+                        .iter()
+                        .find(|(dim, _)| *dim == name)
+                        .map_or(name, |(_, symbol)| symbol)
+                        .to_string()
+                })
+            }
+        }
+
+        Self {
+            symbol: Symbol(&dimension).to_string(),
+            factor: 1.0,
+            dimension,
+        }
+    }
+
+    /// Size of the unit expressed in base units
+    pub fn size(&self) -> Quantity {
+        Quantity {
+            value: self.factor,
+            unit: Self::base_of(self.dimension.clone()),
+        }
+    }
+}
+
+/// Binary prefix
+const KI: f64 = 1024.0;
+
+/// Names of units, the first of which is the symbol used to display them,
+/// and their sizes in base units
+const UNITS: &[(&[&str], f64, &str)] = &[
+    (&["mm", "millimeter", "millimeters"], 1e-3, LENGTH),
+    (&["cm", "centimeter", "centimeters"], 1e-2, LENGTH),
+    (&["m", "meter", "meters", "metre", "metres"], 1.0, LENGTH),
+    (&["km", "kilometer", "kilometers"], 1e3, LENGTH),
+    (&["inch", "inches"], 0.0254, LENGTH),
+    (&["ft", "foot", "feet"], 0.3048, LENGTH),
+    (&["yd", "yard", "yards"], 0.9144, LENGTH),
+    (&["mi", "mile", "miles"], 1609.344, LENGTH),
+    (&["ms", "millisecond", "milliseconds"], 1e-3, TIME),
+    (&["s", "sec", "second", "seconds"], 1.0, TIME),
+    (&["min", "minute", "minutes"], 60.0, TIME),
+    (&["h", "hr", "hour", "hours"], 3600.0, TIME),
+    (&["d", "day", "days"], 86400.0, TIME),
+    (&["wk", "w", "week", "weeks"], 604800.0, TIME),
+    (&["bit", "bits"], 0.125, INFORMATION),
+    (&["B", "byte", "bytes"], 1.0, INFORMATION),
+    (&["kB", "kilobyte", "kilobytes"], 1e3, INFORMATION),
+    (&["MB", "megabyte", "megabytes"], 1e6, INFORMATION),
+    (&["GB", "gigabyte", "gigabytes"], 1e9, INFORMATION),
+    (&["TB", "terabyte", "terabytes"], 1e12, INFORMATION),
+    (&["PB", "petabyte", "petabytes"], 1e15, INFORMATION),
+    (&["KiB", "kibibyte", "kibibytes"], KI, INFORMATION),
+    (&["MiB", "mebibyte", "mebibytes"], KI * KI, INFORMATION),
+    (&["GiB", "gibibyte", "gibibytes"], KI * KI * KI, INFORMATION),
+    (
+        &["TiB", "tebibyte", "tebibytes"],
+        KI * KI * KI * KI,
+        INFORMATION,
+    ),
+    (
+        &["PiB", "pebibyte", "pebibytes"],
+        KI * KI * KI * KI * KI,
+        INFORMATION,
+    ),
+    (&["rad"], 1.0, ANGLE),
+    (&["deg", "°"], std::f64::consts::PI / 180.0, ANGLE),
+];
+
+pub fn find(name: &str) -> Option<Unit> {
+    UNITS
+        .iter()
+        .find(|(names, _, _)| names.contains(&name))
+        .map(|(names, factor, dimension)| Unit {
+            symbol: names[0].to_string(),
+            factor: *factor,
+            dimension: Dimension::base(dimension),
+        })
+}
+
+/// Name of the unit that `name` stands for in a compound quantity of `names`, as in `1h30m`,
+/// where `m` means minutes rather than meters next to other units of time
+pub fn in_compound<'a>(name: &'a str, names: &[String]) -> &'a str {
+    let is_time =
+        |name: &str| find(name).is_some_and(|unit| unit.dimension == Dimension::base(TIME));
+    if name == "m" && names.iter().any(|name| is_time(name)) {
+        "min"
+    } else {
+        name
+    }
+}
+
+/// Amount of a dimensional quantity
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    /// Magnitude in base units
+    pub value: f64,
+    /// Unit the quantity is displayed in
+    pub unit: Unit,
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", Number(self.magnitude()), self.unit.symbol)
+    }
+}
+
+impl Quantity {
+    pub fn new(magnitude: f64, unit: Unit) -> Self {
+        Self {
+            value: magnitude * unit.factor,
+            unit,
+        }
+    }
+
+    pub fn magnitude(&self) -> f64 {
+        self.value / self.unit.factor
+    }
+
+    pub fn dimension(&self) -> &Dimension {
+        &self.unit.dimension
+    }
+
+    /// Size of an angle in radians, or `None` if the quantity is not an angle
+    pub fn radians(&self) -> Option<f64> {
+        (*self.dimension() == Dimension::base(ANGLE)).then_some(self.value)
+    }
+
+    fn with_value(&self, value: f64) -> Self {
+        Self {
+            value,
+            unit: self.unit.clone(),
+        }
+    }
+}
+
+/// Expresses `value` in `unit`
+pub fn convert(value: Value, unit: Unit) -> EvalResult<Value> {
+    let dimension = match &value {
+        Value::Quantity(q) if *q.dimension() == unit.dimension => {
+            return Ok(Value::Quantity(Quantity {
+                value: q.value,
+                unit,
+            }))
+        }
+        Value::Quantity(q) => q.dimension().clone(),
+        _ => {
+            value.as_f64()?;
+            Dimension::default()
+        }
+    };
+    Err(EvalError::DimensionError {
+        message: format!(
+            "Cannot convert {} to {}",
+            describe(&value),
+            describe_unit(&unit)
+        ),
+        left: dimension,
+        right: unit.dimension,
+    })
+}
+
+/// Applies `op` to operands at least one of which is a quantity
+pub fn apply(op: BinaryOp, a: &Value, b: &Value) -> EvalResult<Value> {
+    let (a_value, a_dim) = split(a)?;
+    let (b_value, b_dim) = split(b)?;
+
+    let (value, dimension) = match op {
+        _ if op.is_comparison() => {
+            if a_dim != b_dim {
+                return Err(EvalError::DimensionError {
+                    message: format!("Cannot compare {} with {}", describe(a), describe(b)),
+                    left: a_dim,
+                    right: b_dim,
+                });
+            }
+            return Ok(op.apply(Number(a_value), Number(b_value))?.into());
+        }
+        BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Modulo => {
+            if a_dim != b_dim {
+                let (a_desc, b_desc) = (describe(a), describe(b));
+                let mut message = match op {
+                    BinaryOp::Add => format!("Cannot add {} to {}", b_desc, a_desc),
+                    BinaryOp::Subtract => format!("Cannot subtract {} from {}", b_desc, a_desc),
+                    _ => format!(
+                        "Cannot take the remainder of {} divided by {}",
+                        a_desc, b_desc
+                    ),
+                };
+                // a plain number next to a quantity was likely meant to have the same unit
+                match (a, b) {
+                    (Value::Quantity(q), x) | (x, Value::Quantity(q))
+                        if !matches!(x, Value::Quantity(_)) =>
+                    {
+                        message += &format!("; did you mean {} {}?", x, q.unit.symbol);
+                    }
+                    _ => (),
+                }
+                return Err(EvalError::DimensionError {
+                    message,
+                    left: a_dim,
+                    right: b_dim,
+                });
+            }
+            (op.apply(Number(a_value), Number(b_value))?.0, a_dim)
+        }
+        BinaryOp::Multiply => (a_value * b_value, a_dim.combine(&b_dim, 1)),
+        BinaryOp::Divide => (a_value / b_value, a_dim.combine(&b_dim, -1)),
+        BinaryOp::Power if b_dim.is_dimensionless() && b_value.fract() == 0.0 => {
+            (super::power(a_value, b_value), a_dim.powi(b_value as i32))
+        }
+        BinaryOp::Power => {
+            return Err(EvalError::TypeError(
+                "Quantities can only be raised to integer powers".to_string(),
+            ))
+        }
+        _ => {
+            return Err(EvalError::TypeError(format!(
+                "Cannot apply {} to quantities",
+                op
+            )))
+        }
+    };
+
+    if dimension.is_dimensionless() {
+        return Ok(value.into());
+    }
+
+    // keep the unit of an operand where it makes sense, so that `1h + 30min` stays in hours
+    let kept = [a, b].iter().find_map(|x| match x {
+        Value::Quantity(q) if *q.dimension() == dimension => Some(q),
+        _ => None,
+    });
+    let quantity = match kept {
+        Some(q) => q.with_value(value),
+        None => Quantity {
+            value,
+            unit: Unit::base_of(dimension),
+        },
+    };
+    Ok(Value::Quantity(quantity))
+}
+
+/// Describes the dimension of an operand along with its unit, as in `time (h)`
+fn describe(x: &Value) -> String {
+    match x {
+        Value::Quantity(q) => describe_unit(&q.unit),
+        _ => "a dimensionless number".to_string(),
+    }
+}
+
+fn describe_unit(unit: &Unit) -> String {
+    format!("{} ({})", unit.dimension, unit.symbol)
+}
+
+/// Splits a value into its magnitude in base units and dimension
+fn split(x: &Value) -> EvalResult<(f64, Dimension)> {
+    match x {
+        Value::Quantity(q) => Ok((q.value, q.dimension().clone())),
+        x => Ok((x.as_f64()?, Dimension::default())),
+    }
+}
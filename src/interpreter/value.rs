@@ -0,0 +1,182 @@
+use super::{env::Function, units::Quantity, EvalError, EvalResult};
+use crate::{
+    color::Colorize,
+    language::{Identifier, Number},
+};
+use itertools::Itertools;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use std::{cmp::Ordering, fmt, rc::Rc};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(Number),
+    /// Exact integer which may exceed the range of f64
+    Integer(BigInt),
+    Quantity(Quantity),
+    String(String),
+    List(Vec<Value>),
+    Function(Rc<FunctionValue>),
+}
+
+/// Function as a value, with leading arguments possibly fixed by partial application
+#[derive(Debug, Clone)]
+pub struct FunctionValue {
+    /// Name the function was defined with
+    pub name: Identifier,
+    pub func: Function,
+    /// Arguments passed before the ones given at each call
+    pub bound: Vec<Value>,
+}
+
+impl PartialEq for FunctionValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.bound == other.bound
+    }
+}
+
+impl fmt::Display for FunctionValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.bound.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{}({}, …)", self.name, self.bound.iter().join(", "))
+        }
+    }
+}
+
+impl From<Number> for Value {
+    fn from(x: Number) -> Self {
+        Self::Number(x)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Self {
+        Self::Number(Number(x))
+    }
+}
+
+impl From<String> for Value {
+    fn from(x: String) -> Self {
+        Self::String(x)
+    }
+}
+
+impl From<Quantity> for Value {
+    fn from(x: Quantity) -> Self {
+        Self::Quantity(x)
+    }
+}
+
+impl From<BigInt> for Value {
+    fn from(x: BigInt) -> Self {
+        Self::Integer(x)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(x) => write!(f, "{}", x),
+            Self::Integer(x) => write!(f, "{}", x.to_string().cyan()),
+            Self::Quantity(x) => write!(f, "{}", x),
+            Self::String(x) => write!(f, "{}", format!("\"{}\"", x).green()),
+            Self::List(xs) => write!(f, "[{}]", xs.iter().join(", ")),
+            Self::Function(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            // numbers come first, then quantities, strings, lists and functions
+            (Self::Function(a), Self::Function(b)) => a.to_string().partial_cmp(&b.to_string()),
+            (Self::Function(_), _) => Some(Ordering::Greater),
+            (_, Self::Function(_)) => Some(Ordering::Less),
+            (Self::List(a), Self::List(b)) => a.partial_cmp(b),
+            (Self::List(_), _) => Some(Ordering::Greater),
+            (_, Self::List(_)) => Some(Ordering::Less),
+            (Self::String(a), Self::String(b)) => a.partial_cmp(b),
+            (Self::String(_), _) => Some(Ordering::Greater),
+            (_, Self::String(_)) => Some(Ordering::Less),
+            (Self::Quantity(a), Self::Quantity(b)) => {
+                (a.dimension(), a.value).partial_cmp(&(b.dimension(), b.value))
+            }
+            (Self::Quantity(_), _) => Some(Ordering::Greater),
+            (_, Self::Quantity(_)) => Some(Ordering::Less),
+            (Self::Integer(a), Self::Integer(b)) => a.partial_cmp(b),
+            _ => self.as_f64().ok()?.partial_cmp(&other.as_f64().ok()?),
+        }
+    }
+}
+
+impl Value {
+    /// Converts to a floating-point number, rounding integers that are too large to be exact
+    pub fn as_f64(&self) -> EvalResult<f64> {
+        match self {
+            Self::Number(x) => Ok(x.0),
+            Self::Integer(x) => Ok(x.to_f64().unwrap_or(f64::NAN)),
+            Self::Quantity(_) => Err(EvalError::TypeError(format!(
+                "Expected a number, but got a quantity {}",
+                self
+            ))),
+            Self::String(_) => Err(EvalError::TypeError(format!(
+                "Expected a number, but got a string {}",
+                self
+            ))),
+            Self::List(_) => Err(EvalError::TypeError(format!(
+                "Expected a number, but got a list {}",
+                self
+            ))),
+            Self::Function(_) => Err(EvalError::TypeError(format!(
+                "Expected a number, but got a function {}",
+                self
+            ))),
+        }
+    }
+
+    pub fn as_number(&self) -> EvalResult<Number> {
+        self.as_f64().map(Number)
+    }
+
+    /// Returns the value as an integer if it is exactly one
+    pub fn to_exact_integer(&self) -> Option<BigInt> {
+        const MAX_EXACT: f64 = (1u64 << f64::MANTISSA_DIGITS) as f64;
+        match self {
+            Self::Number(x) if x.0.fract() == 0.0 && x.0.abs() <= MAX_EXACT => {
+                Some(BigInt::from(x.0 as i64))
+            }
+            Self::Number(_)
+            | Self::Quantity(_)
+            | Self::String(_)
+            | Self::List(_)
+            | Self::Function(_) => None,
+            Self::Integer(x) => Some(x.clone()),
+        }
+    }
+
+    pub fn as_list(&self) -> EvalResult<&[Value]> {
+        match self {
+            Self::List(xs) => Ok(xs),
+            _ => Err(EvalError::TypeError(format!(
+                "Expected a list, but got {}",
+                self
+            ))),
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Self::Number(_) | Self::Integer(_))
+    }
+
+    pub fn is_finite(&self) -> bool {
+        match self {
+            Self::Number(x) => x.0.is_finite(),
+            Self::Quantity(x) => x.value.is_finite(),
+            Self::List(xs) => xs.iter().all(Self::is_finite),
+            Self::Integer(_) | Self::String(_) | Self::Function(_) => true,
+        }
+    }
+}
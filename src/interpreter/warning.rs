@@ -0,0 +1,40 @@
+use super::value::Value;
+use crate::language::Identifier;
+use std::{cell::RefCell, rc::Rc};
+use thiserror::Error;
+
+/// Something suspicious about an evaluation that does not stop it
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum Warning {
+    #[error("{0} shadows the unit of the same name")]
+    ShadowsUnit(Identifier),
+
+    #[error("Parameter {0} shadows the definition of the same name")]
+    ShadowsDefinition(Identifier),
+
+    #[error("Precision may be lost in converting {0} to a floating-point number")]
+    PrecisionLoss(Value),
+
+    #[error("Overwrote {0} = {1} with a value of a very different magnitude")]
+    MagnitudeChange(Identifier, Value),
+
+    #[error("Overwrote the function {0} with a variable")]
+    FunctionOverwritten(String),
+}
+
+/// Shared list of warnings, which is kept across the copies of environments made during evaluation
+#[derive(Debug, Clone, Default)]
+pub struct Warnings(Rc<RefCell<Vec<Warning>>>);
+
+impl Warnings {
+    pub fn push(&self, warning: Warning) {
+        let mut warnings = self.0.borrow_mut();
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    }
+
+    pub fn take(&self) -> Vec<Warning> {
+        self.0.take()
+    }
+}
@@ -1,8 +1,13 @@
 mod parser;
-pub use parser::parse;
+pub use parser::{
+    parse, parse_explicit, parse_spanned, parse_spanned_explicit, parse_spanned_with_max_depth,
+    reparse, reparse_explicit, DEFAULT_MAX_DEPTH, MAX_AST_DEPTH,
+};
 
-use colored::Colorize;
+use crate::color::Colorize;
+use itertools::Itertools;
 use std::fmt;
+use unicode_normalization::UnicodeNormalization;
 
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
 pub struct Number(pub f64);
@@ -25,6 +30,21 @@ impl fmt::Display for Number {
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Identifier(pub String);
 
+impl Identifier {
+    /// Identifier named `name` in NFC, so that composed and decomposed forms of the same
+    /// characters refer to the same item
+    pub fn new(name: &str) -> Self {
+        Self(name.nfc().collect())
+    }
+
+    /// Whether the identifier is qualified with the namespace `ns`, as in `ns.name`
+    pub fn is_in_namespace(&self, ns: &str) -> bool {
+        self.0
+            .strip_prefix(ns)
+            .is_some_and(|rest| rest.starts_with('.'))
+    }
+}
+
 impl fmt::Display for Identifier {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0.to_string().yellow())
@@ -35,7 +55,70 @@ impl fmt::Display for Identifier {
 pub enum Statement {
     Expression(Expression),
     VariableAssignment(VariableAssignment),
+    MultipleAssignment(MultipleAssignment),
+    ConstantDefinition(ConstantDefinition),
     FunctionDefinition(FunctionDefinition),
+    UnitDefinition(UnitDefinition),
+    /// Definition that is allowed to shadow a built-in
+    Override(Box<Statement>),
+    /// Execution of the script in a file
+    Include(String),
+}
+
+impl Statement {
+    /// Number of nodes in the expressions of the statement, including default values of parameters
+    pub fn size(&self) -> usize {
+        self.exprs().into_iter().map(Expression::size).sum()
+    }
+
+    /// Depth of the deepest expression of the statement
+    pub fn depth(&self) -> usize {
+        self.exprs()
+            .into_iter()
+            .map(Expression::depth)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Expressions of the statement, including default values of parameters
+    fn exprs(&self) -> Vec<&Expression> {
+        match self {
+            Self::Expression(expr)
+            | Self::VariableAssignment(VariableAssignment { expr, .. })
+            | Self::ConstantDefinition(ConstantDefinition { expr, .. })
+            | Self::UnitDefinition(UnitDefinition { expr, .. }) => vec![expr],
+            Self::MultipleAssignment(MultipleAssignment { exprs, .. }) => exprs.iter().collect(),
+            Self::FunctionDefinition(FunctionDefinition { params, expr, .. }) => {
+                std::iter::once(expr)
+                    .chain(params.iter().filter_map(|param| param.default.as_ref()))
+                    .collect()
+            }
+            Self::Override(stmt) => stmt.exprs(),
+            Self::Include(_) => Vec::new(),
+        }
+    }
+
+    /// Drops the statement without recursing into its expressions, as `Expression::dispose` does
+    pub fn dispose(self) {
+        match self {
+            Self::Expression(expr)
+            | Self::VariableAssignment(VariableAssignment { expr, .. })
+            | Self::ConstantDefinition(ConstantDefinition { expr, .. })
+            | Self::UnitDefinition(UnitDefinition { expr, .. }) => expr.dispose(),
+            Self::MultipleAssignment(MultipleAssignment { exprs, .. }) => {
+                exprs.into_iter().for_each(Expression::dispose)
+            }
+            Self::FunctionDefinition(FunctionDefinition { params, expr, .. }) => {
+                expr.dispose();
+                params
+                    .into_iter()
+                    .filter_map(|param| param.default)
+                    .for_each(Expression::dispose);
+            }
+            Self::Override(stmt) => stmt.dispose(),
+            Self::Include(_) => (),
+        }
+    }
 }
 
 impl fmt::Display for Statement {
@@ -43,7 +126,12 @@ impl fmt::Display for Statement {
         match self {
             Self::Expression(expr) => write!(f, "{}", expr),
             Self::VariableAssignment(assign) => write!(f, "{}", assign),
+            Self::MultipleAssignment(assign) => write!(f, "{}", assign),
+            Self::ConstantDefinition(def) => write!(f, "{}", def),
             Self::FunctionDefinition(def) => write!(f, "{}", def),
+            Self::UnitDefinition(def) => write!(f, "{}", def),
+            Self::Override(stmt) => write!(f, "override {}", stmt),
+            Self::Include(path) => write!(f, "include {}", format!("\"{}\"", path).green()),
         }
     }
 }
@@ -52,6 +140,7 @@ impl fmt::Display for Statement {
 pub enum UnaryOp {
     Negate,
     Factorial,
+    BitNot,
 }
 
 impl fmt::Display for UnaryOp {
@@ -59,6 +148,7 @@ impl fmt::Display for UnaryOp {
         f.write_str(match self {
             Self::Negate => "-",
             Self::Factorial => "!",
+            Self::BitNot => "~",
         })
     }
 }
@@ -69,8 +159,21 @@ pub enum BinaryOp {
     Subtract,
     Multiply,
     Divide,
+    /// Division rounded down to an integer
+    FloorDivide,
     Modulo,
     Power,
+    BitAnd,
+    BitOr,
+    ShiftLeft,
+    ShiftRight,
+    /// Comparisons, which give 1 if they hold and 0 otherwise
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
 }
 
 impl fmt::Display for BinaryOp {
@@ -80,8 +183,19 @@ impl fmt::Display for BinaryOp {
             Self::Subtract => "-",
             Self::Multiply => "×",
             Self::Divide => "/",
+            Self::FloorDivide => "//",
             Self::Modulo => "%",
             Self::Power => "^",
+            Self::BitAnd => "&",
+            Self::BitOr => "|",
+            Self::ShiftLeft => "<<",
+            Self::ShiftRight => ">>",
+            Self::Less => "<",
+            Self::LessOrEqual => "≤",
+            Self::Greater => ">",
+            Self::GreaterOrEqual => "≥",
+            Self::Equal => "==",
+            Self::NotEqual => "≠",
         })
     }
 }
@@ -89,49 +203,226 @@ impl fmt::Display for BinaryOp {
 impl BinaryOp {
     fn precedence(self) -> u8 {
         match self {
-            BinaryOp::Add | BinaryOp::Subtract => 0,
-            BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo => 1,
-            BinaryOp::Power => 2,
+            BinaryOp::Less
+            | BinaryOp::LessOrEqual
+            | BinaryOp::Greater
+            | BinaryOp::GreaterOrEqual
+            | BinaryOp::Equal
+            | BinaryOp::NotEqual => 0,
+            BinaryOp::BitOr => 1,
+            BinaryOp::BitAnd => 2,
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => 3,
+            BinaryOp::Add | BinaryOp::Subtract => 4,
+            BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::FloorDivide | BinaryOp::Modulo => 5,
+            BinaryOp::Power => 6,
         }
     }
+
+    /// Whether the operator compares its operands, giving 1 or 0
+    pub fn is_comparison(self) -> bool {
+        self.precedence() == 0
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Expression {
     Number(Number),
+    String(String),
+    /// Number followed by a unit, as in `30m`
+    Quantity(Number, String),
     Field(Identifier),
-    Function(Identifier, Vec<Expression>),
+    /// Call with positional arguments followed by named ones, as in `f(2, k = 10)`
+    Function(Identifier, Vec<Expression>, Vec<(Identifier, Expression)>),
+    /// List of elements in brackets, as in `[1, 2, 3]`
+    List(Vec<Expression>),
+    Subscript(Box<Expression>, Subscript),
     UnaryOp(UnaryOp, Box<Expression>),
     BinaryOp(BinaryOp, Box<Expression>, Box<Expression>),
+    /// Expression converted to a unit with `to`
+    Conversion(Box<Expression>, Identifier),
+    /// Value of the second expression if the first one is nonzero, or of the third one otherwise.
+    /// Functions defined by cases, as in `{ x if x ≥ 0; -x otherwise }`, are chains of these,
+    /// where a missing last expression means that none of the cases applies.
+    Conditional(Box<Expression>, Box<Expression>, Option<Box<Expression>>),
+}
+
+/// Element or range of elements taken from a list
+#[derive(Debug, Clone)]
+pub enum Subscript {
+    /// `xs[i]`
+    Index(Box<Expression>),
+    /// `xs[start:end]`, where omitted bounds stand for the ends of the list
+    Slice(Option<Box<Expression>>, Option<Box<Expression>>),
 }
 
 impl fmt::Display for Expression {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(f, false)
+    }
+}
+
+impl Expression {
+    /// Whether `name` appears in the expression, as a value or as a called function
+    pub fn uses(&self, name: &Identifier) -> bool {
+        match self {
+            Self::Number(_) | Self::String(_) | Self::Quantity(_, _) => false,
+            Self::Field(x) => x == name,
+            Self::Function(f, xs, named) => {
+                f == name
+                    || xs.iter().any(|x| x.uses(name))
+                    || named.iter().any(|(_, x)| x.uses(name))
+            }
+            Self::List(xs) => xs.iter().any(|x| x.uses(name)),
+            Self::Subscript(x, Subscript::Index(i)) => x.uses(name) || i.uses(name),
+            Self::Subscript(x, Subscript::Slice(start, end)) => {
+                x.uses(name) || start.iter().chain(end).any(|x| x.uses(name))
+            }
+            Self::UnaryOp(_, x) | Self::Conversion(x, _) => x.uses(name),
+            Self::BinaryOp(_, a, b) => a.uses(name) || b.uses(name),
+            Self::Conditional(cond, then, otherwise) => {
+                cond.uses(name) || then.uses(name) || otherwise.iter().any(|x| x.uses(name))
+            }
+        }
+    }
+
+    /// Number of nodes in the expression tree
+    pub fn size(&self) -> usize {
+        let children = match self {
+            Self::Number(_) | Self::String(_) | Self::Quantity(_, _) | Self::Field(_) => 0,
+            Self::Function(_, xs, named) => {
+                xs.iter().map(Self::size).sum::<usize>()
+                    + named.iter().map(|(_, x)| x.size()).sum::<usize>()
+            }
+            Self::List(xs) => xs.iter().map(Self::size).sum(),
+            Self::Subscript(x, Subscript::Index(i)) => x.size() + i.size(),
+            Self::Subscript(x, Subscript::Slice(start, end)) => {
+                x.size() + start.iter().chain(end).map(|x| x.size()).sum::<usize>()
+            }
+            Self::UnaryOp(_, x) | Self::Conversion(x, _) => x.size(),
+            Self::BinaryOp(_, a, b) => a.size() + b.size(),
+            Self::Conditional(cond, then, otherwise) => {
+                cond.size() + then.size() + otherwise.as_ref().map_or(0, |x| x.size())
+            }
+        };
+        1 + children
+    }
+
+    /// Depth of the expression tree, found without recursion so that any tree can be measured
+    pub fn depth(&self) -> usize {
+        let mut max = 0;
+        let mut stack = vec![(self, 1)];
+        while let Some((expr, depth)) = stack.pop() {
+            max = max.max(depth);
+            stack.extend(expr.children().into_iter().map(|x| (x, depth + 1)));
+        }
+        max
+    }
+
+    /// Drops the expression without recursion, which would overflow the stack for deep trees
+    pub fn dispose(self) {
+        let mut stack = vec![self];
+        while let Some(expr) = stack.pop() {
+            stack.extend(expr.into_children());
+        }
+    }
+
+    fn children(&self) -> Vec<&Self> {
+        match self {
+            Self::Number(_) | Self::String(_) | Self::Quantity(_, _) | Self::Field(_) => Vec::new(),
+            Self::Function(_, xs, named) => xs.iter().chain(named.iter().map(|(_, x)| x)).collect(),
+            Self::List(xs) => xs.iter().collect(),
+            Self::Subscript(x, Subscript::Index(i)) => vec![x, i],
+            Self::Subscript(x, Subscript::Slice(start, end)) => std::iter::once(&**x)
+                .chain(start.iter().chain(end).map(|x| &**x))
+                .collect(),
+            Self::UnaryOp(_, x) | Self::Conversion(x, _) => vec![x],
+            Self::BinaryOp(_, a, b) => vec![a, b],
+            Self::Conditional(cond, then, otherwise) => vec![cond, then]
+                .into_iter()
+                .chain(otherwise)
+                .map(|x| &**x)
+                .collect(),
+        }
+    }
+
+    fn into_children(self) -> Vec<Self> {
+        match self {
+            Self::Number(_) | Self::String(_) | Self::Quantity(_, _) | Self::Field(_) => Vec::new(),
+            Self::Function(_, xs, named) => xs
+                .into_iter()
+                .chain(named.into_iter().map(|(_, x)| x))
+                .collect(),
+            Self::List(xs) => xs,
+            Self::Subscript(x, Subscript::Index(i)) => vec![*x, *i],
+            Self::Subscript(x, Subscript::Slice(start, end)) => std::iter::once(x)
+                .chain(start)
+                .chain(end)
+                .map(|x| *x)
+                .collect(),
+            Self::UnaryOp(_, x) | Self::Conversion(x, _) => vec![*x],
+            Self::BinaryOp(_, a, b) => vec![*a, *b],
+            Self::Conditional(cond, then, otherwise) => vec![cond, then]
+                .into_iter()
+                .chain(otherwise)
+                .map(|x| *x)
+                .collect(),
+        }
+    }
+
+    /// Writes the expression. If `explicit` is true, every operand that is itself an operation
+    /// is parenthesized so that precedences don't matter.
+    fn fmt_with(&self, f: &mut fmt::Formatter<'_>, explicit: bool) -> fmt::Result {
+        let sub = |x| Sub(x, explicit);
+        let is_op = |x: &Self| {
+            matches!(
+                x,
+                Self::UnaryOp(_, _) | Self::BinaryOp(_, _, _) | Self::Conversion(_, _)
+            )
+        };
+
         match self {
             Self::Number(x) => write!(f, "{}", x),
+            Self::String(x) => write!(f, "{}", format!("\"{}\"", x).green()),
+            Self::Quantity(x, unit) => write!(f, "{}{}", x, unit),
             Self::Field(x) => write!(f, "{}", x),
-            Self::Function(name, xs) => write!(
+            Self::Function(name, xs, named) => write!(
                 f,
                 "{}({})",
                 name,
                 xs.iter()
-                    .map(|x| x.to_string())
+                    .map(|x| sub(x).to_string())
+                    .chain(named.iter().map(|(k, x)| format!("{} = {}", k, sub(x))))
                     .collect::<Vec<_>>()
                     .join(", ")
             ),
+            Self::List(xs) => write!(f, "[{}]", xs.iter().map(sub).join(", ")),
+            Self::Subscript(x, subscript) => {
+                if is_op(x) {
+                    write!(f, "({})", sub(x))?;
+                } else {
+                    write!(f, "{}", sub(x))?;
+                }
+                match subscript {
+                    Subscript::Index(i) => write!(f, "[{}]", sub(i)),
+                    Subscript::Slice(start, end) => {
+                        let bound = |x: &Option<Box<Self>>| {
+                            x.as_ref()
+                                .map_or_else(String::new, |x| Sub(x, explicit).to_string())
+                        };
+                        write!(f, "[{}:{}]", bound(start), bound(end))
+                    }
+                }
+            }
             Self::UnaryOp(op, x) => {
-                if *op == UnaryOp::Negate {
-                    write!(f, "{}", UnaryOp::Negate)?;
+                if *op != UnaryOp::Factorial {
+                    write!(f, "{}", op)?;
                 }
 
-                match **x {
-                    Self::UnaryOp(_, _) | Self::BinaryOp(_, _, _) => {
-                        write!(f, "({})", x)?;
-                    }
-                    _ => {
-                        write!(f, "{}", x)?;
-                    }
-                };
+                if is_op(x) {
+                    write!(f, "({})", sub(x))?;
+                } else {
+                    write!(f, "{}", sub(x))?;
+                }
 
                 if *op == UnaryOp::Factorial {
                     write!(f, "{}", UnaryOp::Factorial)?;
@@ -141,37 +432,243 @@ impl fmt::Display for Expression {
             }
             Self::BinaryOp(BinaryOp::Power, a, b) => {
                 // show parentheses regardless of precedences to clarify right-associativity
-                match **a {
-                    Self::UnaryOp(_, _) | Self::BinaryOp(_, _, _) => write!(f, "({})", a)?,
-                    _ => write!(f, "{}", a)?,
-                };
+                if is_op(a) {
+                    write!(f, "({})", sub(a))?;
+                } else {
+                    write!(f, "{}", sub(a))?;
+                }
 
                 write!(f, "{}", BinaryOp::Power)?;
 
-                match **b {
-                    Self::UnaryOp(_, _) | Self::BinaryOp(_, _, _) => write!(f, "({})", b),
-                    _ => write!(f, "{}", b),
+                if is_op(b) {
+                    write!(f, "({})", sub(b))
+                } else {
+                    write!(f, "{}", sub(b))
                 }
             }
             Self::BinaryOp(op, a, b) => {
                 match **a {
+                    _ if explicit && is_op(a) => write!(f, "({})", sub(a))?,
                     Self::BinaryOp(sub_op, _, _) if sub_op.precedence() < op.precedence() => {
-                        write!(f, "({})", a)?
+                        write!(f, "({})", sub(a))?
                     }
-                    _ => write!(f, "{}", a)?,
+                    Self::Conversion(_, _) => write!(f, "({})", sub(a))?,
+                    _ => write!(f, "{}", sub(a))?,
                 };
 
                 write!(f, " {} ", op)?;
 
                 match **b {
+                    _ if explicit && is_op(b) => write!(f, "({})", sub(b)),
                     Self::BinaryOp(sub_op, _, _) if sub_op.precedence() <= op.precedence() => {
-                        write!(f, "({})", b)
+                        write!(f, "({})", sub(b))
                     }
-                    _ => write!(f, "{}", b),
+                    Self::Conversion(_, _) => write!(f, "({})", sub(b)),
+                    _ => write!(f, "{}", sub(b)),
+                }
+            }
+            Self::Conversion(x, unit) => write!(f, "{} to {}", sub(x), unit),
+            Self::Conditional(_, _, _) => {
+                f.write_str("{ ")?;
+                let mut rest = Some(self);
+                while let Some(x) = rest {
+                    match x {
+                        Self::Conditional(cond, then, otherwise) => {
+                            write!(f, "{} if {}", sub(then), sub(cond))?;
+                            rest = otherwise.as_deref();
+                            if rest.is_some() {
+                                f.write_str("; ")?;
+                            }
+                        }
+                        _ => {
+                            write!(f, "{} otherwise", sub(x))?;
+                            rest = None;
+                        }
+                    }
+                }
+                f.write_str(" }")
+            }
+        }
+    }
+}
+
+/// Sub-expression written in the same style as its parent
+struct Sub<'a>(&'a Expression, bool);
+
+impl fmt::Display for Sub<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_with(f, self.1)
+    }
+}
+
+/// Wrapper to display a statement or an expression with every operation parenthesized
+pub struct Explicit<'a, T>(pub &'a T);
+
+impl fmt::Display for Explicit<'_, Expression> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_with(f, true)
+    }
+}
+
+impl fmt::Display for Explicit<'_, Statement> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |exprs: &[Expression]| exprs.iter().map(|x| Explicit(x).to_string()).join(", ");
+        match self.0 {
+            Statement::Expression(expr) => write!(f, "{}", Explicit(expr)),
+            Statement::VariableAssignment(VariableAssignment { name, expr })
+            | Statement::ConstantDefinition(ConstantDefinition { name, expr }) => {
+                if matches!(self.0, Statement::ConstantDefinition(_)) {
+                    f.write_str("const ")?;
+                }
+                write!(f, "{} = {}", name, Explicit(expr))
+            }
+            Statement::MultipleAssignment(MultipleAssignment { names, exprs }) => {
+                write!(f, "{} = {}", names.iter().join(", "), join(exprs))
+            }
+            Statement::FunctionDefinition(FunctionDefinition { name, params, expr }) => {
+                let params = params
+                    .iter()
+                    .map(|param| match &param.default {
+                        Some(default) => format!("{} = {}", param.name, Explicit(default)),
+                        None => param.name.to_string(),
+                    })
+                    .join(", ");
+                write!(f, "{}({}) = {}", name, params, Explicit(expr))
+            }
+            Statement::UnitDefinition(UnitDefinition { name, expr }) => {
+                write!(f, "unit {} = {}", name, Explicit(expr))
+            }
+            Statement::Override(stmt) => write!(f, "override {}", Explicit(&**stmt)),
+            Statement::Include(_) => write!(f, "{}", self.0),
+        }
+    }
+}
+
+/// Wrapper to display a statement or an expression on lines of at most the given width where
+/// possible. Long operations are broken before their operators, and long calls and lists
+/// between their elements, with continuation lines indented.
+pub struct Wrapped<'a, T>(pub &'a T, pub usize);
+
+/// Indentation added for each level of continuation lines
+const INDENT: usize = 4;
+
+impl fmt::Display for Wrapped<'_, Expression> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&wrap(self.0, 0, 0, self.1))
+    }
+}
+
+impl fmt::Display for Wrapped<'_, Statement> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (prefix, expr) = match self.0 {
+            Statement::Expression(expr) => (String::new(), expr),
+            Statement::VariableAssignment(VariableAssignment { name, expr }) => {
+                (format!("{} = ", name), expr)
+            }
+            Statement::ConstantDefinition(ConstantDefinition { name, expr }) => {
+                (format!("const {} = ", name), expr)
+            }
+            Statement::UnitDefinition(UnitDefinition { name, expr }) => {
+                (format!("unit {} = ", name), expr)
+            }
+            Statement::FunctionDefinition(FunctionDefinition { name, params, expr }) => {
+                (format!("{}({}) = ", name, params.iter().join(", ")), expr)
+            }
+            Statement::Override(stmt) => return write!(f, "override {}", Wrapped(&**stmt, self.1)),
+            Statement::MultipleAssignment(_) | Statement::Include(_) => {
+                return write!(f, "{}", self.0)
+            }
+        };
+        let column = display_width(&prefix);
+        write!(f, "{}{}", prefix, wrap(expr, 0, column, self.1))
+    }
+}
+
+/// Writes `expr` starting at `column` of a line, breaking it into lines of at most `width`
+/// characters where possible. Continuation lines are indented by `indent` spaces.
+fn wrap(expr: &Expression, indent: usize, column: usize, width: usize) -> String {
+    let flat = expr.to_string();
+    if column + display_width(&flat) <= width {
+        return flat;
+    }
+
+    let inner = indent + INDENT;
+    let elements = |open: String, xs: &[Expression], close: char| {
+        let xs = xs
+            .iter()
+            .map(|x| format!("{}{}", " ".repeat(inner), wrap(x, inner, inner, width)))
+            .join(",\n");
+        format!("{}\n{}\n{}{}", open, xs, " ".repeat(indent), close)
+    };
+    let operand = |x: &Expression, parenthesized: bool, column: usize| {
+        if parenthesized {
+            format!("({})", wrap(x, inner, column + 1, width))
+        } else {
+            wrap(x, inner, column, width)
+        }
+    };
+
+    match expr {
+        Expression::Function(name, xs, named) if !xs.is_empty() && named.is_empty() => {
+            elements(format!("{}(", name), xs, ')')
+        }
+        Expression::List(xs) if !xs.is_empty() => elements("[".to_string(), xs, ']'),
+        Expression::BinaryOp(op, _, _) if *op != BinaryOp::Power => {
+            // operations chained with the same precedence go on lines of their own
+            let precedence = op.precedence();
+            let mut operands = Vec::new();
+            let mut lhs = expr;
+            while let Expression::BinaryOp(sub_op, a, b) = lhs {
+                if *sub_op == BinaryOp::Power || sub_op.precedence() != precedence {
+                    break;
                 }
+                operands.push((Some(*sub_op), &**b));
+                lhs = a;
             }
+            operands.push((None, lhs));
+            operands.reverse();
+
+            operands
+                .into_iter()
+                .map(|(op, x)| match op {
+                    None => {
+                        let parenthesized = match x {
+                            Expression::BinaryOp(sub_op, _, _) => sub_op.precedence() < precedence,
+                            Expression::Conversion(_, _) => true,
+                            _ => false,
+                        };
+                        operand(x, parenthesized, column)
+                    }
+                    Some(op) => {
+                        let parenthesized = match x {
+                            Expression::BinaryOp(sub_op, _, _) => sub_op.precedence() <= precedence,
+                            Expression::Conversion(_, _) => true,
+                            _ => false,
+                        };
+                        let head = format!("{}{} ", " ".repeat(inner), op);
+                        let column = display_width(&head);
+                        format!("{}{}", head, operand(x, parenthesized, column))
+                    }
+                })
+                .join("\n")
+        }
+        _ => flat,
+    }
+}
+
+/// Number of characters `s` takes up on a terminal, not counting escape sequences of colors
+fn display_width(s: &str) -> usize {
+    let mut width = 0;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // sequences end with a letter, as in `\x1b[33m`
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            width += 1;
         }
     }
+    width
 }
 
 #[derive(Debug, Clone)]
@@ -186,10 +683,75 @@ impl fmt::Display for VariableAssignment {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct MultipleAssignment {
+    pub names: Vec<Identifier>,
+    pub exprs: Vec<Expression>,
+}
+
+impl fmt::Display for MultipleAssignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} = {}",
+            self.names
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.exprs
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConstantDefinition {
+    pub name: Identifier,
+    pub expr: Expression,
+}
+
+impl fmt::Display for ConstantDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "const {} = {}", self.name, self.expr)
+    }
+}
+
+/// Definition of a unit in terms of existing ones, as in `unit fortnight = 2 weeks`
+#[derive(Debug, Clone)]
+pub struct UnitDefinition {
+    pub name: Identifier,
+    pub expr: Expression,
+}
+
+impl fmt::Display for UnitDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unit {} = {}", self.name, self.expr)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    pub name: Identifier,
+    pub default: Option<Expression>,
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.default {
+            Some(default) => write!(f, "{} = {}", self.name, default),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FunctionDefinition {
     pub name: Identifier,
-    pub arg_names: Vec<Identifier>,
+    pub params: Vec<Parameter>,
     pub expr: Expression,
 }
 
@@ -199,7 +761,7 @@ impl fmt::Display for FunctionDefinition {
             f,
             "{}({}) = {}",
             self.name,
-            self.arg_names
+            self.params
                 .iter()
                 .map(|x| x.to_string())
                 .collect::<Vec<_>>()
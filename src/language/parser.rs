@@ -1,31 +1,186 @@
 use super::{
-    BinaryOp, Expression, FunctionDefinition, Identifier, Number, Statement, UnaryOp,
-    VariableAssignment,
+    BinaryOp, ConstantDefinition, Expression, FunctionDefinition, Identifier, MultipleAssignment,
+    Number, Parameter, Statement, Subscript, UnaryOp, UnitDefinition, VariableAssignment,
 };
+use crate::interpreter::units;
 use combine::{
     attempt, between, choice,
     easy::{self, Error},
-    eof, many, one_of, optional, parser,
+    eof, many, many1, not_followed_by, one_of, optional, parser,
     parser::{
         char::{alpha_num, char, crlf, digit, letter, newline, string},
         combinator::recognize,
     },
-    satisfy, sep_by, skip_many, skip_many1, EasyParser, ParseError, Parser, Stream,
+    position, satisfy, sep_by, sep_by1, skip_many, skip_many1, EasyParser, ParseError, Parser,
+    Stream,
 };
+use itertools::{Either, Itertools};
+use std::{cell::Cell, ops::Range};
+use unicode_normalization::char::is_combining_mark;
+
+/// Default limit of how deeply parentheses and brackets can be nested,
+/// which keeps the recursive descent from overflowing the stack
+pub const DEFAULT_MAX_DEPTH: usize = 32;
+
+/// Limit of how deep syntax trees can be, which keeps chains of operators as in `1+1+…+1`,
+/// whose evaluation recurses as deeply as their trees, from overflowing the stack
+pub const MAX_AST_DEPTH: usize = 256;
+
+thread_local! {
+    /// Whether implicit multiplication is rejected, which is set while parsing with
+    /// `parse_explicit`, `parse_spanned_explicit` or `reparse_explicit`
+    static EXPLICIT: Cell<bool> = const { Cell::new(false) };
+}
 
 pub fn parse(input: &str) -> Result<Vec<Statement>, easy::Errors<char, &str, usize>> {
+    parse_spanned(input).map(|stmts| stmts.into_iter().map(|(stmt, _)| stmt).collect())
+}
+
+/// Same as `parse`, but rejects implicit multiplication as in `2x`
+pub fn parse_explicit(input: &str) -> Result<Vec<Statement>, easy::Errors<char, &str, usize>> {
+    parse_spanned_explicit(input).map(|stmts| stmts.into_iter().map(|(stmt, _)| stmt).collect())
+}
+
+/// Same as `parse_spanned`, but rejects implicit multiplication as in `2x`
+#[allow(clippy::type_complexity)]
+pub fn parse_spanned_explicit(
+    input: &str,
+) -> Result<Vec<(Statement, Range<usize>)>, easy::Errors<char, &str, usize>> {
+    EXPLICIT.with(|explicit| explicit.set(true));
+    let result = parse_spanned(input);
+    EXPLICIT.with(|explicit| explicit.set(false));
+    result
+}
+
+/// Parses statements along with the byte ranges of `input` they span
+#[allow(clippy::type_complexity)]
+pub fn parse_spanned(
+    input: &str,
+) -> Result<Vec<(Statement, Range<usize>)>, easy::Errors<char, &str, usize>> {
+    parse_spanned_with_max_depth(input, DEFAULT_MAX_DEPTH)
+}
+
+/// Same as `parse_spanned`, but fails if parentheses or brackets are nested deeper than `max_depth`
+#[allow(clippy::type_complexity)]
+pub fn parse_spanned_with_max_depth(
+    input: &str,
+    max_depth: usize,
+) -> Result<Vec<(Statement, Range<usize>)>, easy::Errors<char, &str, usize>> {
+    if let Some(position) = find_too_deep(input, max_depth) {
+        return Err(easy::Errors::new(
+            position,
+            Error::Message("Expression too deeply nested".into()),
+        ));
+    }
+
     let mut script = lex(stmt_list()).skip(eof());
 
-    script
+    let stmts: Vec<_> = script
         .easy_parse(input)
         .map(|(parsed, rem)| {
             assert!(rem.is_empty());
             parsed
+                .into_iter()
+                .map(|(start, stmt, end)| {
+                    let start = start.translate_position(input);
+                    let end = end.translate_position(input);
+                    // statements swallow trailing whitespace
+                    let end = start + input[start..end].trim_end().len();
+                    (stmt, start..end)
+                })
+                .collect()
         })
-        .map_err(|err| err.map_position(|p| p.translate_position(input)))
+        .map_err(|err| err.map_position(|p| p.translate_position(input)))?;
+
+    if let Some(position) = stmts
+        .iter()
+        .find(|(stmt, _)| stmt.depth() > MAX_AST_DEPTH)
+        .map(|(_, span)| span.start)
+    {
+        stmts.into_iter().for_each(|(stmt, _)| stmt.dispose());
+        return Err(easy::Errors::new(
+            position,
+            Error::Message("Expression too deeply nested".into()),
+        ));
+    }
+    Ok(stmts)
+}
+
+/// Parses `input`, which is `old_input` edited, reusing the statements `old_stmts` parsed from
+/// `old_input` on lines that were left intact before the first change.
+///
+/// Statements never span lines, so only the lines from the first changed one onward are
+/// parsed again. This keeps reparsing cheap for editors where typing mostly happens at the end.
+#[allow(clippy::type_complexity)]
+pub fn reparse<'a>(
+    old_stmts: &[(Statement, Range<usize>)],
+    old_input: &str,
+    input: &'a str,
+) -> Result<Vec<(Statement, Range<usize>)>, easy::Errors<char, &'a str, usize>> {
+    let common = old_input
+        .char_indices()
+        .zip(input.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(old_input.len().min(input.len()), |((i, _), _)| i);
+    let boundary = input[..common].rfind('\n').map_or(0, |i| i + 1);
+
+    let mut stmts: Vec<_> = old_stmts
+        .iter()
+        .filter(|(_, span)| span.end < boundary)
+        .cloned()
+        .collect();
+    let rest =
+        parse_spanned(&input[boundary..]).map_err(|err| err.map_position(|p| p + boundary))?;
+    stmts.extend(
+        rest.into_iter()
+            .map(|(stmt, span)| (stmt, span.start + boundary..span.end + boundary)),
+    );
+    Ok(stmts)
+}
+
+/// Same as `reparse`, but rejects implicit multiplication as in `2x`
+#[allow(clippy::type_complexity)]
+pub fn reparse_explicit<'a>(
+    old_stmts: &[(Statement, Range<usize>)],
+    old_input: &str,
+    input: &'a str,
+) -> Result<Vec<(Statement, Range<usize>)>, easy::Errors<char, &'a str, usize>> {
+    EXPLICIT.with(|explicit| explicit.set(true));
+    let result = reparse(old_stmts, old_input, input);
+    EXPLICIT.with(|explicit| explicit.set(false));
+    result
 }
 
-fn stmt_list<I>() -> impl Parser<I, Output = Vec<Statement>>
+/// Finds the position of the first opening parenthesis or bracket (or brace) nested deeper than
+/// `max_depth`, skipping string literals and comments
+fn find_too_deep(input: &str, max_depth: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut in_comment = false;
+    for (i, c) in input.char_indices() {
+        match c {
+            '\n' | '\r' => {
+                in_string = false;
+                in_comment = false;
+            }
+            _ if in_comment => (),
+            '"' => in_string = !in_string,
+            _ if in_string => (),
+            '#' => in_comment = true,
+            '(' | '[' | '{' => {
+                depth += 1;
+                if depth > max_depth {
+                    return Some(i);
+                }
+            }
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+    None
+}
+
+fn stmt_list<I>() -> impl Parser<I, Output = Vec<(I::Position, Statement, I::Position)>>
 where
     I: Stream<Token = char, Error = easy::ParseError<I>>,
     I::Range: PartialEq,
@@ -36,7 +191,7 @@ where
 
     // map(|_| ()) is just for matching types
     sep_by(
-        optional(spaces()).with(lex(optional(stmt()))),
+        optional(spaces()).with(lex(optional((position(), stmt(), position())))),
         choice((
             lex(char(';')).map(|_| ()),
             attempt(nl()).map(|_| ()),
@@ -47,13 +202,7 @@ where
         lex(char(';')).map(|_| ()),
         attempt(comment()).map(|_| ()),
     ))))
-    .map(|maybe_stmts: Vec<_>| {
-        maybe_stmts
-            .iter()
-            .filter_map(|x| x.as_ref())
-            .cloned()
-            .collect()
-    })
+    .map(|maybe_stmts: Vec<_>| maybe_stmts.into_iter().flatten().collect())
 }
 
 fn stmt<I>() -> impl Parser<I, Output = Statement>
@@ -63,8 +212,15 @@ where
     I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
 {
     lex(choice((
-        attempt(def_func().map(Statement::FunctionDefinition)),
-        attempt(assign_var().map(Statement::VariableAssignment)),
+        include(),
+        attempt(def_override().map(|stmt| Statement::Override(Box::new(stmt)))),
+        // definitions commit to their right-hand sides once `=` is seen,
+        // so that errors there are reported instead of being tried as expressions
+        def_const().map(Statement::ConstantDefinition),
+        def_unit().map(Statement::UnitDefinition),
+        def_func().map(Statement::FunctionDefinition),
+        assign_multi().map(Statement::MultipleAssignment),
+        assign_var().map(Statement::VariableAssignment),
         expr().map(Statement::Expression),
     )))
     .expected("statement")
@@ -88,7 +244,12 @@ parser! {
             ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
     ]
     {
-        lex(add()).expected("expression")
+        (lex(compare()), optional(keyword("to").with(ident())))
+            .map(|(expr, unit)| match unit {
+                Some(unit) => Expression::Conversion(Box::new(expr), unit),
+                None => expr,
+            })
+            .expected("expression")
     }
 }
 
@@ -98,43 +259,204 @@ where
     I::Range: PartialEq,
     I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
 {
-    (ident(), lex(char('=')), expr())
-        .map(|a| VariableAssignment {
-            name: a.0,
-            expr: a.2,
-        })
+    (attempt(ident().skip(equals())), expr())
+        .map(|(name, expr)| VariableAssignment { name, expr })
         .expected("variable assignment")
 }
 
+fn assign_multi<I>() -> impl Parser<I, Output = MultipleAssignment>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    let names = (ident(), many1(lex(char(',')).with(ident())))
+        .map(|(first, rest): (_, Vec<_>)| std::iter::once(first).chain(rest).collect());
+
+    (
+        attempt(names.skip(equals())),
+        sep_by1(expr(), lex(char(','))),
+    )
+        .map(|(names, exprs)| MultipleAssignment { names, exprs })
+        .expected("multiple assignment")
+}
+
+fn include<I>() -> impl Parser<I, Output = Statement>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    // `include` is a statement only when followed by a path, so that it remains usable as a name
+    attempt(keyword("include").with(string_literal()))
+        .map(Statement::Include)
+        .expected("include")
+}
+
+fn def_override<I>() -> impl Parser<I, Output = Statement>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    keyword("override")
+        .with(choice((
+            def_const().map(Statement::ConstantDefinition),
+            def_func().map(Statement::FunctionDefinition),
+            assign_var().map(Statement::VariableAssignment),
+        )))
+        .expected("override")
+}
+
+fn def_const<I>() -> impl Parser<I, Output = ConstantDefinition>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    (
+        attempt(keyword("const").with(ident()).skip(equals())),
+        expr(),
+    )
+        .map(|(name, expr)| ConstantDefinition { name, expr })
+        .expected("constant definition")
+}
+
+fn def_unit<I>() -> impl Parser<I, Output = UnitDefinition>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    (
+        attempt(keyword("unit").with(ident()).skip(equals())),
+        expr(),
+    )
+        .map(|(name, expr)| UnitDefinition { name, expr })
+        .expected("unit definition")
+}
+
 fn def_func<I>() -> impl Parser<I, Output = FunctionDefinition>
 where
     I: Stream<Token = char, Error = easy::ParseError<I>>,
     I::Range: PartialEq,
     I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
 {
+    let param = (lex(ident()), optional(equals().with(expr())))
+        .map(|(name, default)| Parameter { name, default })
+        .expected("parameter");
     let func = ident()
         .and(between(
             lex(char('(')),
             lex(char(')')),
-            sep_by(lex(ident()), lex(char(','))),
+            sep_by(param, lex(char(','))),
         ))
         .expected("function");
 
-    (func, lex(char('=')), expr())
-        .map(|((name, arg_names), _, expr)| FunctionDefinition {
-            name,
-            arg_names,
-            expr,
-        })
+    (attempt(func.skip(equals())), expr())
+        .map(|((name, params), expr)| FunctionDefinition { name, params, expr })
         .expected("function definition")
 }
 
+fn compare<I>() -> impl Parser<I, Output = Expression>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    let op = choice((
+        attempt(string("<="))
+            .or(string("≤"))
+            .map(|_| BinaryOp::LessOrEqual),
+        attempt(string(">="))
+            .or(string("≥"))
+            .map(|_| BinaryOp::GreaterOrEqual),
+        string("<").map(|_| BinaryOp::Less),
+        string(">").map(|_| BinaryOp::Greater),
+        attempt(string("==")).map(|_| BinaryOp::Equal),
+        attempt(string("!="))
+            .or(string("≠"))
+            .map(|_| BinaryOp::NotEqual),
+    ));
+
+    // comparisons don't chain, as `a < b < c` would compare the result of `a < b` with c
+    bit_or()
+        .and(optional(lex(op).and(bit_or())))
+        .map(|(lhs, rhs)| match rhs {
+            Some((op, rhs)) => Expression::BinaryOp(op, Box::new(lhs), Box::new(rhs)),
+            None => lhs,
+        })
+}
+
+fn bit_or<I>() -> impl Parser<I, Output = Expression>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    bit_and()
+        .and(many(lex(char('|').map(|_| BinaryOp::BitOr)).and(bit_and())))
+        .map(|(lhs, rhs): (_, Vec<_>)| {
+            rhs.into_iter().fold(lhs, |a, (op, b)| {
+                Expression::BinaryOp(op, Box::new(a), Box::new(b))
+            })
+        })
+}
+
+fn bit_and<I>() -> impl Parser<I, Output = Expression>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    shift()
+        .and(many(lex(char('&').map(|_| BinaryOp::BitAnd)).and(shift())))
+        .map(|(lhs, rhs): (_, Vec<_>)| {
+            rhs.into_iter().fold(lhs, |a, (op, b)| {
+                Expression::BinaryOp(op, Box::new(a), Box::new(b))
+            })
+        })
+}
+
+fn shift<I>() -> impl Parser<I, Output = Expression>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    add()
+        .and(many(
+            lex(attempt(string("<<"))
+                .map(|_| BinaryOp::ShiftLeft)
+                .or(attempt(string(">>")).map(|_| BinaryOp::ShiftRight)))
+            .and(add()),
+        ))
+        .map(|(lhs, rhs): (_, Vec<_>)| {
+            rhs.into_iter().fold(lhs, |a, (op, b)| {
+                Expression::BinaryOp(op, Box::new(a), Box::new(b))
+            })
+        })
+}
+
 fn add<I>() -> impl Parser<I, Output = Expression>
 where
     I: Stream<Token = char, Error = easy::ParseError<I>>,
     I::Range: PartialEq,
     I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
 {
+    add_()
+}
+
+// wrapped in parser! to keep combine's error offset counter from overflowing
+parser! {
+    fn add_[I]()(I) -> Expression
+    where [
+        I: Stream<Token = char, Error = easy::ParseError<I>>,
+        I::Range: PartialEq,
+        I::Error:
+            ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+    ]
+    {
     mul()
         .and(many(
             lex(char('+')
@@ -147,6 +469,7 @@ where
                 Expression::BinaryOp(op, Box::new(a), Box::new(b))
             })
         })
+    }
 }
 
 fn mul<I>() -> impl Parser<I, Output = Expression>
@@ -159,6 +482,7 @@ where
         .and(many(
             lex(choice((char('*'), char('·'), char('×')))
                 .map(|_| BinaryOp::Multiply)
+                .or(attempt(string("//")).map(|_| BinaryOp::FloorDivide))
                 .or(char('/').or(char('÷')).map(|_| BinaryOp::Divide))
                 .or(char('%').map(|_| BinaryOp::Modulo)))
             .and(negate()),
@@ -176,12 +500,10 @@ where
     I::Range: PartialEq,
     I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
 {
-    lex((optional(lex(sign())), implicit_mul())).map(|(sign, expr)| {
-        if let Some('-') = sign {
-            Expression::UnaryOp(UnaryOp::Negate, Box::new(expr))
-        } else {
-            expr
-        }
+    lex((optional(lex(sign().or(char('~')))), implicit_mul())).map(|(sign, expr)| match sign {
+        Some('-') => Expression::UnaryOp(UnaryOp::Negate, Box::new(expr)),
+        Some('~') => Expression::UnaryOp(UnaryOp::BitNot, Box::new(expr)),
+        _ => expr,
     })
 }
 
@@ -192,7 +514,22 @@ where
     I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
 {
     exp()
-        .and(many(spaces().with(exp())))
+        .and(many(
+            spaces()
+                .with(not_followed_by(
+                    choice((keyword("to"), keyword("if"), keyword("otherwise"))).map(|_| "keyword"),
+                ))
+                .with(exp())
+                .and_then(|x| {
+                    if EXPLICIT.with(Cell::get) {
+                        Err(Error::Message(
+                            "Implicit multiplication is off; write * explicitly".into(),
+                        ))
+                    } else {
+                        Ok(x)
+                    }
+                }),
+        ))
         .map(|(lhs, rhs): (_, Vec<_>)| {
             rhs.into_iter().fold(lhs, |a, b| {
                 Expression::BinaryOp(BinaryOp::Multiply, Box::new(a), Box::new(b))
@@ -213,7 +550,7 @@ where
         .map(|(lhs, rhs): (_, Vec<_>)| {
             // power is right associative
             std::iter::once(lhs)
-                .chain(rhs.into_iter())
+                .chain(rhs)
                 .rev()
                 .reduce(|a, b| Expression::BinaryOp(BinaryOp::Power, Box::new(b), Box::new(a)))
                 .unwrap()
@@ -226,13 +563,39 @@ where
     I::Range: PartialEq,
     I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
 {
-    lex((atom(), optional(char('!'))).map(|(atom, fact)| {
+    let subscripted = (atom(), many(subscript())).map(|(atom, subscripts): (_, Vec<_>)| {
+        subscripts.into_iter().fold(atom, |x, subscript| {
+            Expression::Subscript(Box::new(x), subscript)
+        })
+    });
+    let fact = attempt(char('!').skip(not_followed_by(char('='))));
+    lex((subscripted, optional(fact))).map(|(atom, fact)| {
         if fact.is_some() {
             Expression::UnaryOp(UnaryOp::Factorial, Box::new(atom))
         } else {
             atom
         }
-    }))
+    })
+}
+
+fn subscript<I>() -> impl Parser<I, Output = Subscript>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    let bound = || optional(lex(expr())).map(|x| x.map(Box::new));
+    between(
+        lex(char('[')),
+        char(']'),
+        (bound(), optional(lex(char(':')).with(bound()))),
+    )
+    .and_then(|(start, end)| match (start, end) {
+        (Some(i), None) => Ok(Subscript::Index(i)),
+        (start, Some(end)) => Ok(Subscript::Slice(start, end)),
+        (None, None) => Err(Error::Expected("index".into())),
+    })
+    .expected("subscript")
 }
 
 fn atom<I>() -> impl Parser<I, Output = Expression>
@@ -255,7 +618,11 @@ parser! {
     {
         choice((
             parens(),
+            list(),
+            cases(),
+            attempt(quantity()),
             number().map(Expression::Number),
+            string_literal().map(Expression::String),
             attempt(apply_func()),
             ident().map(Expression::Field),
         ))
@@ -271,23 +638,139 @@ where
     between(lex(char('(')), lex(char(')')), lex(expr())).expected("parentheses")
 }
 
+fn list<I>() -> impl Parser<I, Output = Expression>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    between(
+        lex(char('[')),
+        lex(char(']')),
+        sep_by(lex(expr()), lex(char(','))),
+    )
+    .map(Expression::List)
+    .expected("list")
+}
+
+/// Expression defined by cases, as in `{ x if x >= 0; -x otherwise }`, which is turned into
+/// a chain of conditionals
+fn cases<I>() -> impl Parser<I, Output = Expression>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    let case = (
+        lex(expr()),
+        choice((
+            keyword("if").with(lex(expr())).map(Some),
+            keyword("otherwise").map(|_| None),
+        )),
+    )
+        .expected("case");
+
+    between(
+        lex(char('{')),
+        lex(char('}')),
+        sep_by1(case, lex(char(';'))),
+    )
+    .and_then(|cases: Vec<(Expression, Option<Expression>)>| {
+        let (last, rest) = cases.split_last().unwrap();
+        if rest.iter().any(|(_, cond)| cond.is_none()) {
+            return Err(Error::Message(
+                "`otherwise` can only be given to the last case".into(),
+            ));
+        }
+        let last = match last {
+            (expr, Some(cond)) => {
+                Expression::Conditional(Box::new(cond.clone()), Box::new(expr.clone()), None)
+            }
+            (expr, None) => expr.clone(),
+        };
+        Ok(rest.iter().rev().fold(last, |otherwise, (expr, cond)| {
+            Expression::Conditional(
+                Box::new(cond.clone().unwrap()),
+                Box::new(expr.clone()),
+                Some(Box::new(otherwise)),
+            )
+        }))
+    })
+    .expected("cases")
+}
+
 fn apply_func<I>() -> impl Parser<I, Output = Expression>
 where
     I: Stream<Token = char, Error = easy::ParseError<I>>,
     I::Range: PartialEq,
     I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
 {
+    let arg = optional(attempt(ident().skip(equals()))).and(lex(expr()));
+
     ident()
         .and(between(
             lex(char('(')),
             lex(char(')')),
-            sep_by(lex(expr()), lex(char(','))),
+            sep_by(arg, lex(char(','))),
         ))
-        .map(|(name, args)| Expression::Function(name, args))
+        .map(|(name, args): (_, Vec<_>)| {
+            // named arguments take their parameters regardless of where they are written
+            let (named, positional): (Vec<_>, Vec<_>) =
+                args.into_iter().partition_map(|arg| match arg {
+                    (Some(k), x) => Either::Left((k, x)),
+                    (None, x) => Either::Right(x),
+                });
+            Expression::Function(name, positional, named)
+        })
         .expected("function")
 }
 
+fn quantity<I>() -> impl Parser<I, Output = Expression>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    let unit = || {
+        recognize(skip_many1(letter()))
+            .or(char('°').map(String::from))
+            .and_then(|unit: String| match units::find(&unit) {
+                Some(_) => Ok(unit),
+                None => Err(Error::Expected("unit".into())),
+            })
+            .skip(not_followed_by(char('_')))
+    };
+    let term = (unsigned_number(), unit()).expected("quantity");
+
+    // terms are written without spaces in between, as in `1h30m`
+    lex((term, many(attempt(unsigned_number().and(unit()))))).map(|(first, rest): (_, Vec<_>)| {
+        let terms: Vec<_> = std::iter::once(first).chain(rest).collect();
+        let names: Vec<_> = terms.iter().map(|(_, unit)| unit.clone()).collect();
+        terms
+            .into_iter()
+            .map(|(x, unit)| {
+                let unit = if names.len() > 1 {
+                    units::in_compound(&unit, &names).to_string()
+                } else {
+                    unit
+                };
+                Expression::Quantity(x, unit)
+            })
+            .reduce(|a, b| Expression::BinaryOp(BinaryOp::Add, Box::new(a), Box::new(b)))
+            .unwrap()
+    })
+}
+
 fn number<I>() -> impl Parser<I, Output = Number>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    lex(unsigned_number()).expected("number")
+}
+
+fn unsigned_number<I>() -> impl Parser<I, Output = Number>
 where
     I: Stream<Token = char, Error = easy::ParseError<I>>,
     I::Range: PartialEq,
@@ -301,10 +784,20 @@ where
         .map(|_| ());
     let mantissa = without_int_part.or(with_int_part);
 
-    let exponent = (one_of("eE".chars()), optional(sign()), skip_many1(digit()));
+    // without digits, `e` is left to be the constant, so that `2e` is `2 × e`
+    let exponent = attempt((one_of("eE".chars()), optional(sign()), skip_many1(digit())));
 
-    lex(recognize((mantissa, optional(exponent))).map(|x: String| Number(x.parse().unwrap())))
-        .expected("number")
+    recognize((mantissa, optional(exponent))).map(|x: String| Number(x.parse().unwrap()))
+}
+
+fn string_literal<I>() -> impl Parser<I, Output = String>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    let content = many(satisfy(|c| c != '"' && c != '\n' && c != '\r'));
+    lex(between(char('"'), char('"'), content)).expected("string")
 }
 
 fn ident<I>() -> impl Parser<I, Output = Identifier>
@@ -313,12 +806,41 @@ where
     I::Range: PartialEq,
     I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
 {
-    let ident_start = letter().or(char('_'));
-    let ident_letter = alpha_num().or(char('_'));
+    // combining marks are allowed so that decomposed forms such as `e\u{301}` can be written
+    let segment = || {
+        let rest = satisfy(|c: char| c.is_alphanumeric() || c == '_' || is_combining_mark(c));
+        (letter().or(char('_')), skip_many(rest))
+    };
+
+    // qualified names like `geom.area` put definitions in namespaces
+    lex(recognize((
+        segment(),
+        skip_many(attempt((char('.'), segment()))),
+    )))
+    .map(|name: String| Identifier::new(&name))
+    .expected("identifier")
+}
+
+fn keyword<I>(word: &'static str) -> impl Parser<I, Output = ()>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    lex(attempt(
+        string(word).skip(not_followed_by(alpha_num().or(char('_')))),
+    ))
+    .map(|_| ())
+}
 
-    lex(recognize((ident_start, skip_many(ident_letter))))
-        .map(Identifier)
-        .expected("identifier")
+/// `=` of assignments and definitions, as opposed to the comparison `==`
+fn equals<I>() -> impl Parser<I, Output = char>
+where
+    I: Stream<Token = char, Error = easy::ParseError<I>>,
+    I::Range: PartialEq,
+    I::Error: ParseError<I::Token, I::Range, I::Position, StreamError = Error<I::Token, I::Range>>,
+{
+    lex(attempt(char('=').skip(not_followed_by(char('=')))))
 }
 
 fn lex<I, P>(p: P) -> impl Parser<I, Output = P::Output>
@@ -349,3 +871,118 @@ where
         satisfy(|c: char| c != '\n' && c != '\r' && c.is_whitespace()).expected("whitespace");
     skip_many(space).expected("whitespaces")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that reparsing `old` edited into `new` gives what parsing `new` from scratch does
+    fn assert_reparses(old: &str, new: &str) {
+        let old_stmts = parse_spanned(old).unwrap();
+        let reparsed = reparse(&old_stmts, old, new).unwrap();
+        let parsed = parse_spanned(new).unwrap();
+        assert_eq!(format!("{:?}", reparsed), format!("{:?}", parsed));
+    }
+
+    #[test]
+    fn reparse_edit_on_first_line() {
+        assert_reparses("x = 1\ny = 2\n", "x = 10\ny = 2\n");
+        assert_reparses("x = 1\ny = 2", "z = 1\ny = 2");
+    }
+
+    #[test]
+    fn reparse_edit_on_last_line() {
+        assert_reparses("x = 1\ny = 2", "x = 1\ny = 3");
+        assert_reparses("x = 1; y = 2\nz = 3", "x = 1; y = 2\nz = 3 + y");
+        assert_reparses("a = 1; b = 2", "a = 1; b = 5");
+    }
+
+    #[test]
+    fn reparse_append() {
+        assert_reparses("x = 1", "x = 12");
+        assert_reparses("x = 1\n", "x = 1\ny = x + 1");
+        assert_reparses(
+            "x = 1\ny = 2",
+            "x = 1\ny = 2\n\nz = {x if x > 0; 0 otherwise}",
+        );
+    }
+
+    #[test]
+    fn reparse_deletion() {
+        assert_reparses("x = 1\ny = 2\nz = 3", "x = 1\nz = 3");
+        assert_reparses("x = 1\ny = 2", "x = 1\n");
+        assert_reparses("x = 1\ny = 2", "x = 1");
+        assert_reparses("x = 1\ny = 2", "");
+    }
+
+    #[test]
+    fn reparse_after_multibyte_characters() {
+        assert_reparses("café = 1\nx = 2", "café = 1\nx = 3");
+        assert_reparses("café = 1; ü = 2\nx = 2", "café = 1; ü = 5\nx = 2");
+        assert_reparses("s = \"αβγ\"\nx = 2", "s = \"αβγ\"\nx = 2 + 2");
+        assert_reparses("π2 = 1", "π3 = 1");
+    }
+
+    #[test]
+    fn reparse_errors_are_positioned_in_whole_input() {
+        let old = "café = 1\nx = 2";
+        let new = "café = 1\nx = ";
+        let old_stmts = parse_spanned(old).unwrap();
+        let reparsed = reparse(&old_stmts, old, new).unwrap_err();
+        let parsed = parse_spanned(new).unwrap_err();
+        assert_eq!(reparsed.position, parsed.position);
+    }
+
+    #[test]
+    fn reparse_explicit_rejects_implicit_multiplication() {
+        let old = "x = 1\n";
+        let old_stmts = parse_spanned(old).unwrap();
+        assert!(reparse(&old_stmts, old, "x = 1\n2x").is_ok());
+        assert!(reparse_explicit(&old_stmts, old, "x = 1\n2x").is_err());
+    }
+
+    /// Chain of `n` ones joined by `op`
+    fn chain(op: &str, n: usize) -> String {
+        vec!["1"; n].join(op)
+    }
+
+    #[test]
+    fn long_operator_chains_are_too_deeply_nested() {
+        assert!(parse(&chain("^", MAX_AST_DEPTH)).is_ok());
+        let input = chain("^", MAX_AST_DEPTH + 1);
+        let err = parse(&input).unwrap_err();
+        assert!(err.to_string().contains("too deeply nested"));
+        assert!(parse(&chain("^", 20000)).is_err());
+        assert!(parse(&chain("+", 10000)).is_err());
+    }
+
+    #[test]
+    fn depth_limit_positions_at_the_offending_statement() {
+        let input = format!("x = 1\n{}", chain("+", MAX_AST_DEPTH + 1));
+        assert_eq!(parse_spanned(&input).unwrap_err().position, 6);
+    }
+
+    #[test]
+    fn m_means_minutes_only_next_to_units_of_time() {
+        let unit_names = |input| {
+            let stmts = parse(input).unwrap();
+            let mut names = Vec::new();
+            let mut exprs = match &stmts[0] {
+                Statement::Expression(expr) => vec![expr],
+                stmt => panic!("{:?}", stmt),
+            };
+            while let Some(expr) = exprs.pop() {
+                match expr {
+                    Expression::Quantity(_, unit) => names.insert(0, unit.clone()),
+                    Expression::BinaryOp(_, a, b) => exprs.extend([&**a, &**b]),
+                    _ => (),
+                }
+            }
+            names
+        };
+        assert_eq!(unit_names("5m"), ["m"]);
+        assert_eq!(unit_names("1h30m"), ["h", "min"]);
+        assert_eq!(unit_names("2m30s"), ["min", "s"]);
+        assert_eq!(unit_names("1m30cm"), ["m", "cm"]);
+    }
+}
@@ -1,6 +1,13 @@
+// the parser combinators nest deeply in types
+#![recursion_limit = "256"]
+
+mod color;
+pub mod diagnostic;
 pub mod interpreter;
 pub mod language;
 pub mod repl;
 
+#[cfg(feature = "python")]
+mod python;
 #[cfg(target_arch = "wasm32")]
 mod wasm;
@@ -0,0 +1,230 @@
+use anyhow::Result;
+use libbeek::{
+    diagnostic::{Diagnostic, SYNTAX_ERROR},
+    interpreter::{
+        self,
+        env::{Environment, Field, Function, NamedItem},
+        format::{format_value, NumberFormat},
+        EvalError,
+    },
+};
+use serde_json::{json, Value as Json};
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, Write},
+    ops::Range,
+    path::Path,
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Serves JSON-RPC 2.0 over stdio, one message per line
+///
+/// - `eval` with `{"input": "..."}` executes statements and returns the last result
+///   and the changes made to the environment
+/// - `reset` clears the environment
+pub fn run(mut env: Environment) -> Result<()> {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Json>(&line) {
+            // notifications, which lack ids, get no responses
+            Ok(request) if request.get("id").is_none() => {
+                handle(&request, &mut env);
+                continue;
+            }
+            Ok(request) => handle(&request, &mut env),
+            Err(err) => error(Json::Null, PARSE_ERROR, err.to_string(), Json::Null),
+        };
+        writeln!(stdout, "{}", response)?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+fn handle(request: &Json, env: &mut Environment) -> Json {
+    let id = request["id"].clone();
+    let method = match request["method"].as_str() {
+        Some(method) => method,
+        None => return error(id, INVALID_REQUEST, "Expected a method", Json::Null),
+    };
+
+    match method {
+        "eval" => {
+            let input = match request["params"]["input"].as_str() {
+                Some(input) => input,
+                None => return error(id, INVALID_PARAMS, "Expected a string input", Json::Null),
+            };
+            eval(id, input, env)
+        }
+        "reset" => {
+            let before = snapshot(env);
+            env.reset();
+            success(id, json!({ "changes": diff(&before, &snapshot(env)) }))
+        }
+        _ => error(
+            id,
+            METHOD_NOT_FOUND,
+            format!("Unknown method {}", method),
+            Json::Null,
+        ),
+    }
+}
+
+fn eval(id: Json, input: &str, env: &mut Environment) -> Json {
+    let stmts = match interpreter::parse_spanned(input, env) {
+        Ok(stmts) => stmts,
+        Err(err) => {
+            // span of the offending character, which is empty at the end of input
+            let start = err.position;
+            let end = input[start..]
+                .chars()
+                .next()
+                .map_or(start, |c| start + c.len_utf8());
+            return error(
+                id,
+                SYNTAX_ERROR.into(),
+                err.to_string().trim(),
+                json!({ "kind": "syntax", "span": { "start": start, "end": end } }),
+            );
+        }
+    };
+
+    let before = snapshot(env);
+    let result = stmts.iter().try_fold(None, |_, (stmt, span)| {
+        interpreter::exec_stmt(stmt, env).map_err(|err| (err, span.clone()))
+    });
+    let changes = diff(&before, &snapshot(env));
+    match result {
+        Ok(value) => success(
+            id,
+            json!({
+                "value": value.map(|x| format_value(&x, NumberFormat::Shortest)),
+                "changes": changes,
+            }),
+        ),
+        Err((err, span)) => {
+            let mut data = error_data(&err, Some(span));
+            data["changes"] = changes;
+            error(id, err.code().into(), err.to_string(), data)
+        }
+    }
+}
+
+/// Machine-readable details of an evaluation error, along with the byte range of the statement
+/// that failed if known
+pub fn error_data(err: &EvalError, span: Option<Range<usize>>) -> Json {
+    let details = match err {
+        EvalError::NumericalError(x) => json!({ "value": x.to_string() }),
+        EvalError::ReferenceError(ident) => json!({ "identifier": ident.to_string() }),
+        EvalError::ArityError {
+            name,
+            expected,
+            got,
+        } => json!({
+            "function": name,
+            "expected": { "min": expected.min, "max": expected.max },
+            "got": got,
+        }),
+        EvalError::DestructuringError { expected, got } => {
+            json!({ "expected": expected, "got": got })
+        }
+        EvalError::AssertionError { expr, .. } => json!({ "expression": expr }),
+        EvalError::OverflowError { function, input } => {
+            json!({ "function": function, "input": input.0 })
+        }
+        EvalError::DimensionError { left, right, .. } => {
+            json!({ "left": left.to_string(), "right": right.to_string() })
+        }
+        EvalError::StepLimitError(max) => json!({ "limit": max }),
+        EvalError::TypeError(_) | EvalError::DefinitionError(_) | EvalError::IncludeError(_) => {
+            json!({})
+        }
+    };
+    let span = span.map(|span| json!({ "start": span.start, "end": span.end }));
+    json!({ "kind": err.kind(), "span": span, "details": details })
+}
+
+/// Textual representation of every named item
+fn snapshot(env: &Environment) -> BTreeMap<String, String> {
+    env.iter()
+        .map(|(name, item)| {
+            let desc = match item {
+                NamedItem::Field(Field::Variable(value)) => {
+                    format_value(value, NumberFormat::Shortest)
+                }
+                NamedItem::Field(Field::Constant(value)) => {
+                    format!("const {}", format_value(value, NumberFormat::Shortest))
+                }
+                NamedItem::Function(Function::UserDefined { params, expr }) => format!(
+                    "{}({}) = {}",
+                    name,
+                    params
+                        .iter()
+                        .map(|x| x.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    expr
+                ),
+                NamedItem::Function(_) => "built-in".to_string(),
+            };
+            (name.to_string(), desc)
+        })
+        .collect()
+}
+
+fn diff(before: &BTreeMap<String, String>, after: &BTreeMap<String, String>) -> Json {
+    let defined: BTreeMap<_, _> = after
+        .iter()
+        .filter(|(name, desc)| before.get(*name) != Some(desc))
+        .collect();
+    let deleted: Vec<_> = before
+        .keys()
+        .filter(|name| !after.contains_key(*name))
+        .collect();
+    json!({ "defined": defined, "deleted": deleted })
+}
+
+fn success(id: Json, result: Json) -> Json {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error(id: Json, code: i64, message: impl ToString, data: Json) -> Json {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message.to_string(), "data": data },
+    })
+}
+
+/// Serializes `diagnostic` as `{"error": {...}}` with the line and the column it points at,
+/// along with the machine-readable `details` of the error
+pub fn diagnostic_json(
+    diagnostic: &Diagnostic,
+    details: Json,
+    path: Option<&Path>,
+    source: &str,
+    first_line: usize,
+) -> Json {
+    let (line, column) = diagnostic.position(source, first_line);
+    json!({
+        "error": {
+            "kind": diagnostic.kind,
+            "code": diagnostic.code,
+            "message": diagnostic.message,
+            "file": path.map(|path| path.display().to_string()),
+            "line": line,
+            "column": column,
+            "notes": diagnostic.notes,
+            "help": diagnostic.help,
+            "details": details,
+        }
+    })
+}
@@ -1,14 +1,50 @@
 use anyhow::{anyhow, Result};
+use colored::Colorize;
+use crossterm::{
+    cursor::MoveTo,
+    execute, queue,
+    terminal::{Clear, ClearType},
+};
 use libbeek::{
-    interpreter::{self, env::Environment},
-    language::{self, Number},
-    repl::{Repl, Response},
+    diagnostic::Diagnostic,
+    interpreter::{
+        self,
+        env::Environment,
+        format::{format_value, NumberFormat},
+        settings::Settings,
+        value::Value,
+    },
+    repl::{Limits, Repl, Response},
+};
+use progress::Progress;
+use rustyline::{
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::{Hint, Hinter},
+    history::History,
+    Config, Context, Editor,
+};
+use rustyline_derive::{Helper, Validator};
+use serde_json::{json, Value as Json};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
 };
-use rustyline::{completion::Completer, error::ReadlineError, Context, Editor};
-use rustyline_derive::{Helper, Highlighter, Hinter, Validator};
-use std::{cell::RefCell, io::BufRead, path::PathBuf, rc::Rc};
 use structopt::{clap::AppSettings, StructOpt};
 
+mod machine;
+mod progress;
+mod server;
+mod transcript;
+mod tui;
+
 #[derive(Debug, StructOpt)]
 #[structopt(author = env!("CARGO_PKG_AUTHORS"),
             setting(AppSettings::TrailingVarArg),
@@ -24,70 +60,547 @@ struct Opt {
     /// Enter REPL after running scripts
     #[structopt(short, long)]
     interactive: bool,
+
+    /// Read scripts from stdin even if it is a terminal
+    #[structopt(long, conflicts_with_all = &["script", "file"])]
+    stdin: bool,
+
+    /// Never enter REPL, reading scripts from stdin if no other scripts are given
+    #[structopt(long, conflicts_with_all = &["interactive", "tui"])]
+    no_interactive: bool,
+
+    /// Format of results and errors of scripts
+    #[structopt(long, value_name = "format", default_value = "text", possible_values = &["text", "json"])]
+    format: Format,
+
+    /// Run independent files given with -f in parallel, each in an environment of its own
+    #[structopt(long, requires = "file", conflicts_with = "interactive")]
+    parallel: bool,
+
+    /// Run files given with -f one after another, each in an environment of its own
+    #[structopt(long, requires = "file", conflicts_with_all = &["interactive", "parallel"])]
+    isolate: bool,
+
+    /// Report every failing statement in files given with -f and run the rest of them
+    #[structopt(long, requires = "file")]
+    keep_going: bool,
+
+    /// Take JSON-RPC requests from stdin instead of running the REPL
+    #[structopt(long, conflicts_with_all = &["script", "file", "interactive"])]
+    machine: bool,
+
+    /// Re-run the inputs of a saved transcript and report outputs differing from the recorded ones
+    #[structopt(long, value_name = "file", conflicts_with_all = &["script", "file", "interactive", "machine"])]
+    check_transcript: Option<PathBuf>,
+
+    /// Run a full-screen interface instead of the line REPL
+    #[structopt(long, conflicts_with = "machine")]
+    tui: bool,
+
+    /// Reject implicit multiplication and check definitions more thoroughly
+    #[structopt(long)]
+    strict: bool,
+
+    /// Append inputs and outputs of the REPL to a file with timestamps
+    #[structopt(long, value_name = "file")]
+    log: Option<PathBuf>,
+
+    /// Maximum number of entries kept in the REPL history
+    #[structopt(long, value_name = "n", default_value = "100")]
+    history_size: usize,
+
+    /// Record repeated inputs in the REPL history
+    #[structopt(long)]
+    history_keep_dups: bool,
+
+    /// Do not record inputs starting with a space in the REPL history
+    #[structopt(long)]
+    history_ignore_space: bool,
+
+    #[structopt(subcommand)]
+    cmd: Option<Subcommand>,
+}
+
+/// How results and errors of scripts are written
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Format {
+    Text,
+    /// One JSON object per line, `{"result": ...}` or `{"error": {...}}`
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err("Format must be text or json".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+enum Subcommand {
+    /// Serve an HTTP+JSON API evaluating statements in per-session environments
+    Serve {
+        /// Address to listen on
+        #[structopt(long, default_value = "127.0.0.1")]
+        host: String,
+        /// Port to listen on
+        #[structopt(short, long, default_value = "8080")]
+        port: u16,
+        /// Maximum number of characters in an input
+        #[structopt(long, default_value = "10000")]
+        max_input_length: usize,
+        /// Maximum number of nodes in the syntax tree of a statement
+        #[structopt(long, default_value = "10000")]
+        max_ast_size: usize,
+        /// Maximum number of evaluation steps taken for an input
+        #[structopt(long, default_value = "1000000")]
+        max_steps: usize,
+        /// Maximum number of live sessions
+        #[structopt(long, default_value = "1000")]
+        max_sessions: usize,
+        /// Minutes after which an unused session is discarded
+        #[structopt(long, default_value = "30")]
+        session_timeout: u64,
+        /// Number of threads evaluating inputs
+        #[structopt(long, default_value = "4")]
+        threads: usize,
+    },
 }
 
 fn main() -> Result<()> {
     let opt = Opt::from_args();
 
+    if let Some(Subcommand::Serve {
+        host,
+        port,
+        max_input_length,
+        max_ast_size,
+        max_steps,
+        max_sessions,
+        session_timeout,
+        threads,
+    }) = opt.cmd
+    {
+        colored::control::set_override(false);
+        return server::serve(server::Options {
+            host,
+            port,
+            limits: Limits {
+                max_input_length: Some(max_input_length),
+                max_ast_size: Some(max_ast_size),
+                max_steps: Some(max_steps),
+            },
+            max_sessions,
+            session_timeout: Duration::from_secs(session_timeout * 60),
+            threads,
+        });
+    }
+
     let mut env = Environment::new();
+    env.settings_mut().strict = opt.strict;
+
+    if opt.machine {
+        colored::control::set_override(false);
+        return machine::run(env);
+    }
+
+    if let Some(path) = &opt.check_transcript {
+        colored::control::set_override(false);
+        if !transcript::check(path, env)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
     let script_given = !opt.script.is_empty();
     let files_given = !opt.file.is_empty();
-    let stdin_given = atty::isnt(atty::Stream::Stdin);
+    let stdin_given = opt.stdin || opt.no_interactive || atty::isnt(atty::Stream::Stdin);
+
+    if opt.parallel {
+        colored::control::set_override(false);
+        if !run_files_in_parallel(&opt.file, env.settings(), opt.format, opt.keep_going) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if opt.isolate {
+        colored::control::set_override(false);
+        if !run_files_isolated(&opt.file, env.settings(), opt.format, opt.keep_going) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
     if script_given || files_given || stdin_given {
         colored::control::set_override(false);
 
+        // only set with --keep-going, as errors exit right away otherwise
+        let mut failed = false;
+
         let last_result = if script_given {
-            run_script(&opt.script.join(" "), &mut env)
+            Ok(run_script(&opt.script.join(" "), 1, &mut env, opt.format))
         } else if files_given {
-            opt.file.iter().try_fold(None, |_, file| {
-                let script = std::fs::read_to_string(file)?;
-                run_script(&script, &mut env)
-            })
+            progress::handle_interrupts();
+            Ok(opt.file.iter().fold(None, |_, file| {
+                run_file(file, &mut env, opt.format, opt.keep_going, &mut failed)
+            }))
         } else if stdin_given {
-            std::io::stdin()
-                .lock()
-                .lines()
-                .try_fold(None, |last, line| {
+            std::io::stdin().lock().lines().enumerate().try_fold(
+                None,
+                |last, (i, line)| -> Result<_> {
                     let line = line?;
                     if line.trim().is_empty() {
                         Ok(last)
                     } else {
-                        run_script(&line, &mut env)
+                        Ok(run_script(&line, i + 1, &mut env, opt.format))
                     }
-                })
+                },
+            )
         } else {
             unreachable!()
         };
 
         if let Some(last_result) = last_result? {
-            println!("{}", last_result);
+            match opt.format {
+                Format::Text => println!("{}", last_result),
+                Format::Json => println!(
+                    "{}",
+                    json!({ "result": format_value(&last_result, NumberFormat::Shortest) })
+                ),
+            }
+        }
+        if failed {
+            std::process::exit(1);
         }
 
         if !opt.interactive {
             return Ok(());
         }
+        if atty::isnt(atty::Stream::Stdin) {
+            reattach_terminal()?;
+        }
+    }
+
+    let mut repl = Repl::with_env(env);
+    if let Some(path) = &opt.log {
+        repl.start_log(path)
+            .map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+    }
+
+    if opt.tui {
+        return tui::run(repl);
     }
 
     colored::control::unset_override();
-    run_repl(env)
+    let config = Config::builder()
+        .max_history_size(opt.history_size)
+        .history_ignore_dups(!opt.history_keep_dups)
+        .history_ignore_space(opt.history_ignore_space)
+        .build();
+    run_repl(repl, config)
+}
+
+/// Makes the terminal stdin again so that the REPL can follow scripts piped in
+#[cfg(unix)]
+fn reattach_terminal() -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let tty = File::open("/dev/tty")?;
+    if unsafe { libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO) } < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn reattach_terminal() -> Result<()> {
+    Err(anyhow!(
+        "Cannot enter REPL after reading scripts from stdin"
+    ))
+}
+
+/// Runs `script`, which is the line numbered `line_number` of the input, exiting with errors
+fn run_script(
+    script: &str,
+    line_number: usize,
+    env: &mut Environment,
+    format: Format,
+) -> Option<Value> {
+    let exit = |text: String, diagnostic: Diagnostic, details: Json| -> ! {
+        match format {
+            Format::Text => eprintln!("Error: {}", text),
+            Format::Json => eprintln!(
+                "{}",
+                machine::diagnostic_json(&diagnostic, details, None, script, line_number)
+            ),
+        }
+        std::process::exit(1)
+    };
+
+    let stmts = match interpreter::parse_spanned(script, env) {
+        Ok(stmts) => stmts,
+        Err(err) => exit(
+            err.to_string(),
+            Diagnostic::from_parse_error(&err, script, err.position),
+            json!({}),
+        ),
+    };
+    let mut last_result = None;
+    for (stmt, span) in stmts {
+        last_result = match interpreter::exec_stmt(&stmt, env) {
+            Ok(value) => value,
+            Err(err) => exit(
+                err.to_string(),
+                Diagnostic::from_eval_error(&err, script, span, env),
+                machine::error_data(&err, None)["details"].take(),
+            ),
+        };
+    }
+    last_result
 }
 
-fn run_script(script: &str, env: &mut Environment) -> Result<Option<Number>> {
-    let stmts = language::parse(script).map_err(|err| anyhow!(err.to_string()))?;
+/// Runs the script in `path`, exiting with errors reported with the part of the script
+/// they come from. With `keep_going`, errors are reported once the script has run to the end
+/// and `failed` is set instead.
+fn run_file(
+    path: &Path,
+    env: &mut Environment,
+    format: Format,
+    keep_going: bool,
+    failed: &mut bool,
+) -> Option<Value> {
+    let mut errors = Vec::new();
+    let result = exec_file(path, env, true, format, keep_going.then_some(&mut errors));
+    for msg in &errors {
+        eprintln!("{}", msg);
+    }
+    *failed |= !errors.is_empty();
+    result.unwrap_or_else(|msg| {
+        eprintln!("{}", msg);
+        if !keep_going {
+            std::process::exit(1)
+        }
+        *failed = true;
+        None
+    })
+}
 
-    stmts
-        .iter()
-        .try_fold(None, |_, stmt| interpreter::exec_stmt(stmt, env))
-        .map_err(|err| anyhow!(err))
+/// Runs the script in `path`. Errors are returned ready to be shown.
+/// If `errors` is given, errors in statements are added to it and the rest of the script runs.
+///
+/// Statements never span lines, so the script is read, parsed and executed line by line
+/// to keep memory usage flat however large the script is.
+fn exec_file(
+    path: &Path,
+    env: &mut Environment,
+    report_progress: bool,
+    format: Format,
+    mut errors: Option<&mut Vec<String>>,
+) -> std::result::Result<Option<Value>, String> {
+    let io_error = |err: std::io::Error| match format {
+        Format::Text => format!("{}: {}: {}", "error".red().bold(), path.display(), err),
+        Format::Json => json!({
+            "error": {
+                "kind": "io",
+                "message": err.to_string(),
+                "file": path.display().to_string(),
+            }
+        })
+        .to_string(),
+    };
+    let report =
+        |diagnostic: Diagnostic, details: Json, source: &str, line_number: usize| match format {
+            Format::Text => diagnostic.render(Some(path), source, line_number),
+            Format::Json => {
+                machine::diagnostic_json(&diagnostic, details, Some(path), source, line_number)
+                    .to_string()
+            }
+        };
+    let mut fail = |msg: String| match errors.as_mut() {
+        Some(errors) => {
+            errors.push(msg);
+            Ok(())
+        }
+        None => Err(msg),
+    };
+    let canonical = std::fs::canonicalize(path).map_err(io_error)?;
+    let file = File::open(path).map_err(io_error)?;
+    let size = file.metadata().ok().map(|metadata| metadata.len());
+    let mut reader = BufReader::new(file);
+
+    env.with_file(canonical, |env| {
+        // dropping the progress erases its report before errors are shown
+        let mut progress = report_progress.then(|| Progress::new(path, size));
+        let mut last_result = None;
+        let mut line = String::new();
+        let mut line_number = 0;
+        loop {
+            line.clear();
+            let len = reader.read_line(&mut line).map_err(io_error)?;
+            if len == 0 {
+                break;
+            }
+            line_number += 1;
+            if let Some(progress) = &mut progress {
+                progress.read_line(len);
+            }
+
+            let source = line.trim_end_matches(['\n', '\r']);
+            let stmts = match interpreter::parse_spanned(source, env) {
+                Ok(stmts) => stmts,
+                Err(err) => {
+                    fail(report(
+                        Diagnostic::from_parse_error(&err, source, err.position),
+                        json!({}),
+                        source,
+                        line_number,
+                    ))?;
+                    continue;
+                }
+            };
+            for (stmt, span) in stmts {
+                if let Some(progress) = &mut progress {
+                    if !progress.step() {
+                        progress.finish();
+                        eprintln!("{}", progress.summary());
+                        std::process::exit(130)
+                    }
+                }
+                match interpreter::exec_stmt(&stmt, env) {
+                    Ok(value) => last_result = value,
+                    Err(err) => fail(report(
+                        Diagnostic::from_eval_error(&err, source, span, env),
+                        machine::error_data(&err, None)["details"].take(),
+                        source,
+                        line_number,
+                    ))?,
+                }
+            }
+        }
+        Ok(last_result)
+    })
+}
+
+/// Runs independent script files on a pool of threads, each in an environment of its own,
+/// and prints their last results in the order of the files. Returns whether all of them succeeded.
+fn run_files_in_parallel(
+    paths: &[PathBuf],
+    settings: &Settings,
+    format: Format,
+    keep_going: bool,
+) -> bool {
+    let workers = std::thread::available_parallelism()
+        .map_or(1, usize::from)
+        .min(paths.len());
+    let next = AtomicUsize::new(0);
+    let mut outcomes: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut outcomes = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let path = match paths.get(i) {
+                            Some(path) => path,
+                            None => break,
+                        };
+                        let mut env = Environment::new();
+                        *env.settings_mut() = settings.clone();
+                        let mut errors = Vec::new();
+                        let errors_kept = keep_going.then_some(&mut errors);
+                        // values are not `Send`, so they leave the thread as text
+                        let outcome = exec_file(path, &mut env, false, format, errors_kept)
+                            .map(|result| result.map(|value| format_file_result(&value, format)));
+                        outcomes.push((i, outcome, errors));
+                    }
+                    outcomes
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+    outcomes.sort_by_key(|(i, _, _)| *i);
+
+    outcomes
+        .into_iter()
+        .fold(true, |succeeded, (i, outcome, errors)| {
+            report_file_outcome(&paths[i], outcome, &errors, format) && succeeded
+        })
 }
 
-fn run_repl(env: Environment) -> Result<()> {
-    let repl = Repl::with_env(env);
+/// Runs script files one after another, each in an environment of its own, and prints the last
+/// result of each file once it has run. Returns whether all of them succeeded.
+fn run_files_isolated(
+    paths: &[PathBuf],
+    settings: &Settings,
+    format: Format,
+    keep_going: bool,
+) -> bool {
+    progress::handle_interrupts();
+    paths.iter().fold(true, |succeeded, path| {
+        let mut env = Environment::new();
+        *env.settings_mut() = settings.clone();
+        let mut errors = Vec::new();
+        let outcome = exec_file(
+            path,
+            &mut env,
+            true,
+            format,
+            keep_going.then_some(&mut errors),
+        )
+        .map(|result| result.map(|value| format_file_result(&value, format)));
+        report_file_outcome(path, outcome, &errors, format) && succeeded
+    })
+}
+
+fn format_file_result(value: &Value, format: Format) -> String {
+    match format {
+        Format::Text => value.to_string(),
+        Format::Json => format_value(value, NumberFormat::Shortest),
+    }
+}
+
+/// Prints the errors and the last result of a file run in an environment of its own.
+/// Returns whether the file ran without errors.
+fn report_file_outcome(
+    path: &Path,
+    outcome: std::result::Result<Option<String>, String>,
+    errors: &[String],
+    format: Format,
+) -> bool {
+    for msg in errors {
+        eprintln!("{}", msg);
+    }
+    match outcome {
+        Ok(Some(result)) => match format {
+            Format::Text => println!("{}: {}", path.display(), result),
+            Format::Json => println!(
+                "{}",
+                json!({ "file": path.display().to_string(), "result": result })
+            ),
+        },
+        Ok(None) => (),
+        Err(msg) => {
+            eprintln!("{}", msg);
+            return false;
+        }
+    }
+    errors.is_empty()
+}
+
+fn run_repl(repl: Repl, config: Config) -> Result<()> {
     let repl = Rc::new(RefCell::new(repl));
 
-    let mut editor = Editor::new();
+    let mut editor = Editor::with_config(config);
     let helper = RLHelper(repl.clone());
     editor.set_helper(Some(helper));
 
@@ -95,18 +608,40 @@ fn run_repl(env: Environment) -> Result<()> {
     if let Some(history_file) = history_file.as_ref() {
         let _ = editor.load_history(history_file);
     }
+    let usage_file = config_dir().map(|dir| dir.join("usage"));
+    if let Some(usage) = usage_file
+        .as_ref()
+        .and_then(|file| std::fs::read_to_string(file).ok())
+    {
+        repl.borrow_mut().load_usage(&usage);
+    }
 
     loop {
         match editor.readline("> ") {
             Ok(line) => {
+                let line = match expand_history(&line, editor.history()) {
+                    Ok(Some(expanded)) => {
+                        println!("{}", expanded);
+                        expanded
+                    }
+                    Ok(None) => line,
+                    Err(err) => {
+                        println!("{}", err.red());
+                        continue;
+                    }
+                };
                 editor.add_history_entry(line.as_str());
 
                 match repl.borrow_mut().run(&line) {
-                    Response::Message(msg) => println!("{}", msg),
-                    Response::ClearScreen => println!("\x1Bc"),
+                    Response::Message(msg) => println!("{}", msg.render()),
+                    Response::ClearScreen { keep_scrollback } => clear_screen(keep_scrollback)?,
+                    Response::ClearHistory => editor.clear_history(),
                     Response::Quit => break,
                     _ => (),
                 }
+                for watch in repl.borrow().watches() {
+                    println!("{}", watch);
+                }
             }
             Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
             Err(err) => return Err(anyhow!(err)),
@@ -121,44 +656,119 @@ fn run_repl(env: Environment) -> Result<()> {
             }
         }
     }
+    // so are counts of uses ranking completions, which live next to the history
+    if let Some(usage_file) = usage_file {
+        let _ = std::fs::write(usage_file, repl.borrow().save_usage());
+    }
 
     Ok(())
 }
 
+/// Expands `!!`, `!n` and `!-n` into the previous, n-th and n-th last history entries,
+/// optionally followed by a substitution `:s/old/new/`
+fn expand_history(line: &str, history: &History) -> Result<Option<String>, String> {
+    let spec = match line.trim().strip_prefix('!') {
+        Some(spec) => spec,
+        None => return Ok(None),
+    };
+    let (designator, modifier) = match spec.split_once(':') {
+        Some((designator, modifier)) => (designator, Some(modifier)),
+        None => (spec, None),
+    };
+
+    let index = match designator {
+        "!" => history.len().checked_sub(1),
+        _ => match designator.strip_prefix('-') {
+            Some(n) => n.parse().ok().and_then(|n| history.len().checked_sub(n)),
+            None => designator
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1)),
+        },
+    };
+    let entry = index
+        .and_then(|i| history.get(i))
+        .ok_or_else(|| format!("No such history entry !{}", designator))?;
+
+    let modifier = match modifier {
+        Some(modifier) => modifier,
+        None => return Ok(Some(entry.clone())),
+    };
+    let mut parts = modifier
+        .strip_prefix("s/")
+        .ok_or_else(|| format!("Unknown modifier :{}", modifier))?
+        .splitn(3, '/');
+    let old = parts.next().unwrap_or_default();
+    let new = parts.next().unwrap_or_default();
+    if old.is_empty() || !entry.contains(old) {
+        return Err(format!("Substitution failed: {} not found", old));
+    }
+    Ok(Some(entry.replacen(old, new, 1)))
+}
+
 fn config_dir() -> Option<PathBuf> {
     dirs::config_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")))
 }
 
-#[derive(Helper, Hinter, Validator, Highlighter)]
+#[derive(Helper, Validator)]
 struct RLHelper(Rc<RefCell<Repl>>);
 
 impl Completer for RLHelper {
-    type Candidate = String;
+    type Candidate = Pair;
 
     fn complete(
         &self,
         line: &str,
         pos: usize,
         _: &Context<'_>,
-    ) -> Result<(usize, Vec<String>), ReadlineError> {
-        let (left, _) = line.split_at(pos);
-        let start = left
-            .trim_end_matches(|c: char| c.is_alphanumeric() || c == '_')
-            .len();
-        let (_, prefix) = left.split_at(start);
-        let candidates = self
-            .0
-            .borrow()
-            .completion_candidates()
-            .filter_map(|x| {
-                if x.starts_with(prefix) {
-                    Some(x.to_string())
-                } else {
-                    None
-                }
+    ) -> Result<(usize, Vec<Pair>), ReadlineError> {
+        let (start, candidates) = self.0.borrow().complete(&line[..pos]);
+        // functions are listed with their signatures, and completed with an open paren
+        let pairs = candidates
+            .into_iter()
+            .map(|x| Pair {
+                replacement: x.replacement(),
+                display: x.signature.unwrap_or(x.name),
             })
             .collect();
+        Ok((start, pairs))
+    }
+}
+
+/// Signature of the function being called, shown after the input
+struct SignatureHint(String);
+
+impl Hint for SignatureHint {
+    fn display(&self) -> &str {
+        &self.0
+    }
+
+    // the hint is not text to be inserted
+    fn completion(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl Hinter for RLHelper {
+    type Hint = SignatureHint;
+
+    fn hint(&self, line: &str, pos: usize, _: &Context<'_>) -> Option<SignatureHint> {
+        let signature = self.0.borrow().signature_help(&line[..pos])?;
+        Some(SignatureHint(format!("  {}", signature)))
+    }
+}
+
+impl Highlighter for RLHelper {
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(hint.dimmed().to_string())
+    }
+}
 
-        Ok((start, candidates))
+/// Clears the terminal, along with its scrollback unless `keep_scrollback` is true
+fn clear_screen(keep_scrollback: bool) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout();
+    if !keep_scrollback {
+        queue!(stdout, Clear(ClearType::Purge))?;
     }
+    execute!(stdout, Clear(ClearType::All), MoveTo(0, 0))
 }
@@ -0,0 +1,112 @@
+//! Progress reports and interruption of scripts run with `-f`
+
+use std::{
+    io::Write,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Interval between progress reports, which also delays the first report
+/// so that short scripts run silently
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Whether scripts are running, in which case Ctrl-C stops them between statements
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Makes Ctrl-C interrupt scripts between statements. Outside scripts it quits as usual.
+pub fn handle_interrupts() {
+    let _ = ctrlc::set_handler(|| {
+        if RUNNING.load(Ordering::SeqCst) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        } else {
+            std::process::exit(130);
+        }
+    });
+}
+
+/// Tracks the statements executed in a script
+pub struct Progress<'a> {
+    path: &'a Path,
+    /// Size of the script in bytes, if known
+    size: Option<u64>,
+    /// Bytes of the script read so far
+    read: u64,
+    /// Line the current statement is on
+    line: usize,
+    executed: usize,
+    last_report: Instant,
+    /// Whether a report is on the terminal and has to be erased
+    reported: bool,
+}
+
+impl<'a> Progress<'a> {
+    pub fn new(path: &'a Path, size: Option<u64>) -> Self {
+        RUNNING.store(true, Ordering::SeqCst);
+        Self {
+            path,
+            size,
+            read: 0,
+            line: 0,
+            executed: 0,
+            last_report: Instant::now(),
+            reported: false,
+        }
+    }
+
+    /// Called after reading a line of `len` bytes
+    pub fn read_line(&mut self, len: usize) {
+        self.read += len as u64;
+        self.line += 1;
+    }
+
+    /// Called before executing a statement. Returns `false` if the script was interrupted.
+    pub fn step(&mut self) -> bool {
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            return false;
+        }
+        if self.last_report.elapsed() >= INTERVAL && atty::is(atty::Stream::Stderr) {
+            let percentage = match self.size {
+                Some(size) if size > 0 => format!(" ({}%)", self.read * 100 / size),
+                _ => String::new(),
+            };
+            eprint!(
+                "\r{}: {} statements{}",
+                self.path.display(),
+                self.executed,
+                percentage
+            );
+            let _ = std::io::stderr().flush();
+            self.last_report = Instant::now();
+            self.reported = true;
+        }
+        self.executed += 1;
+        true
+    }
+
+    /// Describes how far the script got when it was interrupted
+    pub fn summary(&self) -> String {
+        format!(
+            "Interrupted {} at line {} after executing {} statements",
+            self.path.display(),
+            self.line,
+            self.executed
+        )
+    }
+
+    /// Erases the progress report so that other messages can be written
+    pub fn finish(&mut self) {
+        if self.reported {
+            eprint!("\r\x1B[K");
+            self.reported = false;
+        }
+        RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Drop for Progress<'_> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
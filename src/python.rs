@@ -0,0 +1,120 @@
+use crate::{
+    interpreter::{
+        self, env,
+        format::{format_value, NumberFormat},
+        value::Value,
+    },
+    repl,
+};
+use pyo3::{create_exception, exceptions::PyException, prelude::*, IntoPyObjectExt};
+use std::path::PathBuf;
+
+create_exception!(beek, BeekError, PyException);
+
+#[pymodule]
+fn beek(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    // results are returned to Python as plain strings
+    #[cfg(feature = "colored")]
+    colored::control::set_override(false);
+
+    m.add("BeekError", m.py().get_type::<BeekError>())?;
+    m.add_class::<Environment>()?;
+    m.add_class::<Repl>()?;
+    m.add_function(wrap_pyfunction!(eval, m)?)?;
+    Ok(())
+}
+
+/// Evaluates `input` in a fresh environment and returns the value of the last statement
+#[pyfunction]
+fn eval(py: Python<'_>, input: &str) -> PyResult<PyObject> {
+    Environment::new().eval(py, input)
+}
+
+/// Variables, functions and settings that statements are evaluated in
+#[pyclass(unsendable)]
+#[derive(Clone)]
+struct Environment {
+    inner: env::Environment,
+}
+
+#[pymethods]
+impl Environment {
+    #[new]
+    fn new() -> Self {
+        Self {
+            inner: env::Environment::new(),
+        }
+    }
+
+    /// Evaluates `input` and returns the value of the last statement, or `None` if there is none
+    fn eval(&mut self, py: Python<'_>, input: &str) -> PyResult<PyObject> {
+        let stmts = interpreter::parse(input, &self.inner)
+            .map_err(|err| BeekError::new_err(err.to_string().trim().to_string()))?;
+        let mut last_result = None;
+        for stmt in stmts {
+            last_result = interpreter::exec_stmt(&stmt, &mut self.inner)
+                .map_err(|err| BeekError::new_err(err.to_string()))?;
+        }
+        to_python(py, last_result.as_ref())
+    }
+
+    /// Runs the script in the file at `path`, such as a `.beek` file of definitions
+    /// shared with the command line, and returns the value of its last statement
+    fn load(&mut self, py: Python<'_>, path: PathBuf) -> PyResult<PyObject> {
+        let result = interpreter::exec_file(&path, &mut self.inner)
+            .map_err(|err| BeekError::new_err(err.to_string()))?;
+        to_python(py, result.as_ref())
+    }
+}
+
+/// REPL taking the same inputs, including commands, as the command line one
+#[pyclass(unsendable)]
+struct Repl {
+    inner: repl::Repl,
+}
+
+#[pymethods]
+impl Repl {
+    #[new]
+    #[pyo3(signature = (env = None))]
+    fn new(env: Option<Environment>) -> Self {
+        Self {
+            inner: match env {
+                Some(env) => repl::Repl::with_env(env.inner),
+                None => repl::Repl::new(),
+            },
+        }
+    }
+
+    /// Runs `input` and returns the output shown for it, raising `BeekError` if it fails
+    fn run(&mut self, input: &str) -> PyResult<String> {
+        match self.inner.run(input) {
+            repl::Response::Message(msg) => match msg.error() {
+                Some(line) => Err(BeekError::new_err(line.plain_text())),
+                None => Ok(msg.render()),
+            },
+            repl::Response::Quit => {
+                self.inner = repl::Repl::new();
+                Ok("".into())
+            }
+            _ => Ok("".into()),
+        }
+    }
+}
+
+/// Converts numbers to `float` or `int` and lists to `list`. Values without counterparts
+/// in Python, such as quantities and functions, are converted to strings as they are shown.
+fn to_python(py: Python<'_>, value: Option<&Value>) -> PyResult<PyObject> {
+    match value {
+        None => Ok(py.None()),
+        Some(Value::Number(x)) => x.0.into_py_any(py),
+        Some(Value::Integer(n)) => n.into_py_any(py),
+        Some(Value::String(s)) => s.into_py_any(py),
+        Some(Value::List(xs)) => xs
+            .iter()
+            .map(|x| to_python(py, Some(x)))
+            .collect::<PyResult<Vec<_>>>()?
+            .into_py_any(py),
+        Some(value) => format_value(value, NumberFormat::Shortest).into_py_any(py),
+    }
+}
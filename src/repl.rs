@@ -1,97 +1,1236 @@
 use crate::{
+    color::Colorize,
+    diagnostic::Diagnostic,
     interpreter::{
         self,
-        env::{Environment, Field, Function, NamedItem},
+        env::{Environment, Field, Function, NamedItem, LAST_RESULT_NAMES},
+        settings::{self, RoundingMode, BIT_WIDTHS, MAX_MODULUS, MAX_SIGFIGS, MIN_WRAP_WIDTH},
+        trace::{Event, Trace},
+        value::Value,
+        warning::{Warning, Warnings},
+    },
+    language::{
+        self, Explicit, Expression, FunctionDefinition, Identifier, Number, Parameter, Statement,
+        Wrapped,
     },
-    language::{self, Identifier, Number},
 };
-use colored::Colorize;
 use itertools::Itertools;
+use regex::Regex;
+use rpn::Rpn;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::Write,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+mod rpn;
 
 #[derive(Debug, Clone)]
 pub enum Response {
     Empty,
-    Message(String),
-    ClearScreen,
+    Message(Message),
+    ClearScreen {
+        /// Whether lines scrolled off the screen are kept
+        keep_scrollback: bool,
+    },
+    ClearHistory,
     Quit,
 }
 
+impl Response {
+    /// Message of plain text, such as the output of a command
+    fn text(text: impl Into<String>) -> Self {
+        Self::Message(Message {
+            lines: vec![Line::new(LineKind::Text, text.into())],
+        })
+    }
+
+    /// Message of an error other than those of parsing and evaluation, which is shown in red
+    fn error(message: impl Into<String>) -> Self {
+        Self::Message(Message {
+            lines: vec![Line::new(
+                LineKind::Error(None),
+                message.into().red().to_string(),
+            )],
+        })
+    }
+}
+
+/// Outcome of a statement run by `Repl::run_script`
+#[derive(Debug, Clone)]
+pub struct StatementOutcome {
+    /// Statement as parsed, or `None` for a syntax error in the script
+    pub statement: Option<Statement>,
+    /// Byte range of the script the statement, or the syntax error, spans
+    pub span: Range<usize>,
+    /// Value of the statement if it has one, or the error it failed with
+    pub result: Result<Option<Value>, Diagnostic>,
+    pub warnings: Vec<Warning>,
+}
+
+/// Output shown for an input, which is kept in lines of different kinds so that frontends can
+/// inspect values and errors without parsing colored text
+#[derive(Debug, Clone, Default)]
+pub struct Message {
+    pub lines: Vec<Line>,
+}
+
+impl Message {
+    /// Text of the message as the REPL shows it, which may contain colors
+    pub fn render(&self) -> String {
+        self.lines.iter().map(|line| &line.text).join("\n")
+    }
+
+    /// Value of the last statement that gave one
+    pub fn value(&self) -> Option<&Value> {
+        self.lines.iter().rev().find_map(|line| match &line.kind {
+            LineKind::Result(value) => Some(value),
+            _ => None,
+        })
+    }
+
+    /// Error that stopped the input, if any
+    pub fn error(&self) -> Option<&Line> {
+        self.lines
+            .iter()
+            .find(|line| matches!(line.kind, LineKind::Error(_)))
+    }
+}
+
+/// Part of a message, which is a single line unless it is text such as the output of a command
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub kind: LineKind,
+    /// Text as shown, which may contain colors
+    pub text: String,
+}
+
+impl Line {
+    fn new(kind: LineKind, text: String) -> Self {
+        Self { kind, text }
+    }
+
+    /// Text without colors
+    pub fn plain_text(&self) -> String {
+        strip_colors(&self.text)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum LineKind {
+    /// Statement as normalized by the parser, echoed before it is evaluated
+    Statement(Statement),
+    /// Value of a statement
+    Result(Value),
+    Warning,
+    /// Error, along with its diagnostic if it comes from parsing or evaluation
+    Error(Option<Diagnostic>),
+    /// Other output, such as that of commands and traces
+    Text,
+}
+
 #[derive(Debug, Default)]
 pub struct Repl {
     env: Environment,
+    /// Postfix calculator taking input instead of the parser, or `None` if RPN mode is off
+    rpn: Option<Rpn>,
+    echo: Echo,
+    /// Whether output is free of colors and echoed statements so that it can be copied as is
+    plain: bool,
+    /// Expressions re-evaluated after every input
+    watches: Vec<Expression>,
+    /// Number of the last result variable such as `r7`, or `None` if results are not named
+    result_vars: Option<usize>,
+    /// Whether results are appended to the list `results`
+    record: bool,
+    /// Name of the workspace whose environment is `env`
+    workspace: String,
+    /// Environments of the other workspaces
+    workspaces: Vec<(String, Environment)>,
+    /// Session log that inputs and responses are appended to, along with its path
+    log: Option<(PathBuf, File)>,
+    /// Most recent error, along with the input it comes from
+    last_error: Option<(String, Diagnostic)>,
+    usage: Usage,
+    limits: Limits,
+}
+
+/// Limits on inputs and on the work done for them, which keep untrusted input, as on websites,
+/// from exhausting resources. `None` means no limit, which is the default.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum number of characters in an input
+    pub max_input_length: Option<usize>,
+    /// Maximum number of nodes in the syntax tree of a statement
+    pub max_ast_size: Option<usize>,
+    /// Maximum number of evaluation steps taken for an input
+    pub max_steps: Option<usize>,
+}
+
+impl Limits {
+    /// Fails with a message if `input` is longer than allowed
+    pub fn check_input(&self, input: &str) -> Result<(), String> {
+        if let Some(max) = self.max_input_length {
+            let len = input.chars().count();
+            if len > max {
+                return Err(format!(
+                    "Input is too long ({} characters, at most {})",
+                    len, max
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails with a message if any of `stmts` has more nodes than allowed
+    pub fn check_statements<'a>(
+        &self,
+        stmts: impl IntoIterator<Item = &'a Statement>,
+    ) -> Result<(), String> {
+        if let Some(max) = self.max_ast_size {
+            if let Some(size) = stmts.into_iter().map(Statement::size).find(|x| *x > max) {
+                return Err(format!(
+                    "Statement is too large ({} nodes, at most {})",
+                    size, max
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How often and how recently names have been used, for ranking completions
+#[derive(Debug, Default)]
+struct Usage {
+    /// Number of inputs each name appeared in, and the number of the last such input
+    names: HashMap<String, (u64, u64)>,
+    /// Number of inputs recorded
+    inputs: u64,
+}
+
+impl Usage {
+    fn record(&mut self, input: &str) {
+        self.inputs += 1;
+        let names: HashSet<_> = input
+            .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+            .filter(|word| word.starts_with(|c: char| c.is_alphabetic() || c == '_'))
+            .collect();
+        for name in names {
+            let (count, last) = self.names.entry(name.to_string()).or_default();
+            *count += 1;
+            *last = self.inputs;
+        }
+    }
+
+    /// Sort key putting more frequently used names first, and more recently used ones
+    /// first among those used as often
+    fn rank(&self, name: &str) -> Reverse<(u64, u64)> {
+        Reverse(self.names.get(name).copied().unwrap_or_default())
+    }
+}
+
+/// Name of the workspace a session starts in
+const DEFAULT_WORKSPACE: &str = "main";
+
+/// Name that input can be completed to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub name: String,
+    pub kind: CandidateKind,
+    /// Signature of a function, such as `atan2(y, x)`
+    pub signature: Option<String>,
+    /// Description of a built-in
+    pub doc: Option<&'static str>,
+}
+
+impl Candidate {
+    /// Text inserted in place of the name being completed, with `(` appended for functions
+    pub fn replacement(&self) -> String {
+        match self.kind {
+            CandidateKind::Function => format!("{}(", self.name),
+            _ => self.name.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CandidateKind {
+    Command,
+    Constant,
+    Variable,
+    Function,
+}
+
+/// Name of the list results are appended to with `record on`
+const RESULTS_NAME: &str = "results";
+
+/// How results of statements are shown
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+enum Echo {
+    /// Normalized statement followed by ` = value`
+    #[default]
+    On,
+    /// ` = value` only
+    Off,
+    /// Value only
+    Raw,
 }
 
 impl Repl {
     pub fn new() -> Self {
-        Default::default()
+        Self::with_env(Environment::new())
     }
 
-    pub fn with_env(env: Environment) -> Self {
-        Self { env }
+    pub fn with_env(mut env: Environment) -> Self {
+        env.set_warnings(Some(Warnings::default()));
+        Self {
+            env,
+            workspace: DEFAULT_WORKSPACE.to_string(),
+            ..Default::default()
+        }
     }
 
     pub fn run(&mut self, input: &str) -> Response {
-        if let Some(first_line) = input.trim().lines().next() {
-            if let Ok(cmd) = first_line.parse() {
-                return exec_command(cmd, &mut self.env);
+        if let Err(message) = self.limits.check_input(input) {
+            return Response::error(message);
+        }
+        self.env.set_step_limit(self.limits.max_steps);
+
+        self.usage.record(input);
+        let response = match self.run_all(input) {
+            Response::Message(mut msg) if self.plain => {
+                for line in &mut msg.lines {
+                    line.text = line.plain_text();
+                }
+                Response::Message(msg)
+            }
+            response => response,
+        };
+        match self.write_log(input, &response) {
+            Ok(()) => response,
+            Err(err) => {
+                let path = self.log.take().unwrap().0;
+                let err =
+                    Response::error(format!("Stopped logging to {}: {}", path.display(), err));
+                match (response, err) {
+                    (Response::Empty, err) => err,
+                    (Response::Message(mut msg), Response::Message(err)) => {
+                        msg.lines.extend(err.lines);
+                        Response::Message(msg)
+                    }
+                    (response, _) => response,
+                }
+            }
+        }
+    }
+
+    /// Parses `script` as a whole and runs each of its statements, returning their outcomes
+    /// in order. Unlike `run`, statements after a failing one still run, and commands are not
+    /// taken. If the script fails to parse, nothing runs and the only outcome is the syntax error.
+    ///
+    /// Of the limits, only `max_steps` applies, to each statement.
+    pub fn run_script(&mut self, script: &str) -> Vec<StatementOutcome> {
+        self.usage.record(script);
+        let stmts = match interpreter::parse_spanned(script, &self.env) {
+            Ok(stmts) => stmts,
+            Err(err) => {
+                let diagnostic = Diagnostic::from_parse_error(&err, script, err.position);
+                self.last_error = Some((script.to_string(), diagnostic.clone()));
+                return vec![StatementOutcome {
+                    statement: None,
+                    span: diagnostic.span.clone(),
+                    result: Err(diagnostic),
+                    warnings: Vec::new(),
+                }];
+            }
+        };
+
+        let mut outcomes = Vec::with_capacity(stmts.len());
+        for (stmt, span) in stmts {
+            self.env.set_step_limit(self.limits.max_steps);
+            let result = match interpreter::exec_stmt(&stmt, &mut self.env) {
+                Ok(value) => {
+                    if let (Statement::Expression(expr), Some(value)) = (&stmt, &value) {
+                        self.record_result(expr, value);
+                        self.name_result(value);
+                    }
+                    Ok(value)
+                }
+                Err(err) => {
+                    let diagnostic =
+                        Diagnostic::from_eval_error(&err, script, span.clone(), &self.env);
+                    self.last_error = Some((script.to_string(), diagnostic.clone()));
+                    Err(diagnostic)
+                }
+            };
+            // traces are shown only by `run`
+            if let Some(trace) = self.env.trace() {
+                trace.take();
+            }
+            let warnings = self
+                .env
+                .warnings()
+                .map_or_else(Vec::new, |warnings| warnings.take());
+            outcomes.push(StatementOutcome {
+                statement: Some(stmt),
+                span,
+                result,
+                warnings,
+            });
+        }
+        outcomes
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Starts appending inputs and responses to the file at `path`
+    pub fn start_log(&mut self, path: &Path) -> std::io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.log = Some((path.to_path_buf(), file));
+        Ok(())
+    }
+
+    fn write_log(&mut self, input: &str, response: &Response) -> std::io::Result<()> {
+        let file = match &mut self.log {
+            Some((_, file)) => file,
+            None => return Ok(()),
+        };
+        writeln!(file, "[{}] > {}", timestamp(), input.trim_end())?;
+        if let Response::Message(msg) = response {
+            writeln!(file, "{}", strip_colors(&msg.render()))?;
+        }
+        file.flush()
+    }
+
+    fn show_last_error(&self) -> Response {
+        match &self.last_error {
+            Some((input, diagnostic)) => Response::text(diagnostic.render(None, input, 1)),
+            None => Response::text("No errors so far".to_string()),
+        }
+    }
+
+    fn set_log(&mut self, arg: Option<String>) -> Response {
+        match arg.as_deref() {
+            None => Response::text(match &self.log {
+                Some((path, _)) => format!("Log: {}", path.display()),
+                None => "Log: off".to_string(),
+            }),
+            Some("off") => {
+                self.log = None;
+                Response::Empty
+            }
+            Some(path) => match self.start_log(Path::new(path)) {
+                Ok(()) => Response::Empty,
+                Err(err) => Response::error(format!("{}: {}", path, err)),
+            },
+        }
+    }
+
+    fn run_all(&mut self, input: &str) -> Response {
+        let inputs = strip_transcript(input);
+        if let [input] = &inputs[..] {
+            return self.run_one(input);
+        }
+
+        let mut lines = Vec::new();
+        for input in inputs {
+            match self.run_one(&input) {
+                Response::Empty => (),
+                Response::Message(msg) => lines.extend(msg.lines),
+                response => return response,
+            }
+        }
+        message(lines)
+    }
+
+    /// Runs commands and statements in `input` in order. Commands take a whole statement,
+    /// so they can be mixed with statements as in `x = 3; list`.
+    fn run_one(&mut self, input: &str) -> Response {
+        let mut lines = Vec::new();
+        // start of the statements not run yet
+        let mut start = 0;
+        for piece in split_statements(input) {
+            let cmd = match input[piece.clone()].trim().parse() {
+                Ok(cmd) => cmd,
+                Err(()) => continue,
+            };
+            // the statements before may define a function the piece calls
+            if !self.run_stmts(&input[start..piece.start], &mut lines) {
+                return message(lines);
+            }
+            start = piece.start;
+            if is_statement(input[piece.clone()].trim(), &self.env) {
+                continue;
+            }
+            start = piece.end;
+            match self.run_command(cmd) {
+                Response::Empty => (),
+                Response::Message(msg) => lines.extend(msg.lines),
+                response => return response,
+            }
+        }
+        self.run_stmts(&input[start..], &mut lines);
+        message(lines)
+    }
+
+    fn run_command(&mut self, cmd: Command) -> Response {
+        match cmd {
+            Command::Rpn(arg) => self.set_rpn(arg),
+            Command::Echo(arg) => self.set_echo(arg),
+            Command::ResultVars(arg) => self.set_result_vars(arg),
+            Command::Record(arg) => self.set_record(arg),
+            Command::Output(arg) => self.set_output(arg),
+            Command::Watch(input) => self.watch(&input),
+            Command::Unwatch(arg) => self.unwatch(arg),
+            Command::Workspace(args) => self.workspace(&args),
+            Command::Log(arg) => self.set_log(arg),
+            Command::Error => self.show_last_error(),
+            cmd => {
+                let response = exec_command(cmd, &mut self.env);
+                let mut lines = format_warnings(&self.env);
+                match response {
+                    _ if lines.is_empty() => response,
+                    Response::Empty => message(lines),
+                    Response::Message(msg) => {
+                        lines.extend(msg.lines);
+                        message(lines)
+                    }
+                    response => response,
+                }
             }
         }
+    }
+
+    /// Runs statements, adding their output to `lines`.
+    /// Returns whether they all ran without errors.
+    fn run_stmts(&mut self, input: &str, lines: &mut Vec<Line>) -> bool {
+        if input.trim().is_empty() {
+            return true;
+        }
+
+        if let Some(rpn) = &mut self.rpn {
+            let result = rpn.run(input, &mut self.env);
+            lines.extend(format_warnings(&self.env));
+            return match result {
+                Ok(()) => {
+                    let stack = format_stack(rpn.stack(), &self.env);
+                    lines.push(Line::new(LineKind::Text, stack));
+                    true
+                }
+                Err(e) => {
+                    lines.push(Line::new(LineKind::Error(None), e.red().to_string()));
+                    false
+                }
+            };
+        }
 
-        let stmts = match language::parse(input) {
+        let stmts = match interpreter::parse_spanned(input, &self.env) {
             Ok(x) => x,
             Err(e) => {
-                return Response::Message(e.to_string().trim().red().to_string());
+                let diagnostic = Diagnostic::from_parse_error(&e, input, e.position);
+                lines.push(Line::new(
+                    LineKind::Error(Some(diagnostic.clone())),
+                    e.to_string().trim().red().to_string(),
+                ));
+                self.last_error = Some((input.to_string(), diagnostic));
+                return false;
             }
         };
 
-        if stmts.is_empty() {
-            return Response::Empty;
+        if let Err(message) = self
+            .limits
+            .check_statements(stmts.iter().map(|(stmt, _)| stmt))
+        {
+            lines.push(Line::new(LineKind::Error(None), message.red().to_string()));
+            return false;
         }
 
-        let mut msg_lines = Vec::new();
-        for stmt in stmts {
-            msg_lines.push(format!("{}", stmt));
+        // plain output shows bare values
+        let echo = if self.plain { Echo::Raw } else { self.echo };
+
+        for (stmt, span) in stmts {
+            if echo == Echo::On {
+                let text = format_stmt(&stmt, &self.env);
+                lines.push(Line::new(LineKind::Statement(stmt.clone()), text));
+            }
+
+            let result = interpreter::exec_stmt(&stmt, &mut self.env);
+            if let Some(trace) = self.env.trace() {
+                let events = trace.take();
+                lines.extend(
+                    events
+                        .iter()
+                        .map(|event| Line::new(LineKind::Text, format_event(event))),
+                );
+            }
+            lines.extend(format_warnings(&self.env));
 
-            match interpreter::exec_stmt(&stmt, &mut self.env) {
+            match result {
                 Ok(Some(value)) => {
-                    msg_lines.push(format!(" = {}", value));
+                    let name = match stmt {
+                        Statement::Expression(expr) => {
+                            self.record_result(&expr, &value);
+                            self.name_result(&value)
+                        }
+                        _ => None,
+                    };
+                    let rounded = is_rounded(&value, &self.env);
+                    let formatted = format_value(&value, &self.env);
+                    let text = match (echo, name) {
+                        (Echo::Raw, _) => formatted,
+                        (_, Some(name)) => format!(
+                            " {} {}  {}",
+                            if rounded { '≈' } else { '=' },
+                            formatted,
+                            format!("({})", name).dimmed()
+                        ),
+                        (_, None) => {
+                            format!(" {} {}", if rounded { '≈' } else { '=' }, formatted)
+                        }
+                    };
+                    lines.push(Line::new(LineKind::Result(value), text));
                 }
                 Err(e) => {
-                    msg_lines.push(e.to_string().red().to_string());
-                    return Response::Message(msg_lines.join("\n"));
+                    let diagnostic = Diagnostic::from_eval_error(&e, input, span, &self.env);
+                    lines.push(Line::new(
+                        LineKind::Error(Some(diagnostic.clone())),
+                        e.to_string().red().to_string(),
+                    ));
+                    self.last_error = Some((input.to_string(), diagnostic));
+                    return false;
                 }
                 _ => (),
             }
         }
+        true
+    }
+
+    fn set_echo(&mut self, arg: Option<String>) -> Response {
+        self.echo = match arg.as_deref() {
+            None => {
+                return Response::text(format!(
+                    "Echo: {}",
+                    match self.echo {
+                        Echo::On => "on",
+                        Echo::Off => "off",
+                        Echo::Raw => "raw",
+                    }
+                ))
+            }
+            Some("on") => Echo::On,
+            Some("off") => Echo::Off,
+            Some("raw") => Echo::Raw,
+            Some(_) => return Response::error("Echo must be on, off or raw"),
+        };
+        Response::Empty
+    }
+
+    fn watch(&mut self, input: &str) -> Response {
+        match interpreter::parse(input, &self.env) {
+            Ok(stmts) => match &stmts[..] {
+                [Statement::Expression(expr)] => {
+                    self.watches.push(expr.clone());
+                    Response::Empty
+                }
+                _ => Response::error("Specify an expression to watch"),
+            },
+            Err(e) => Response::error(e.to_string().trim()),
+        }
+    }
+
+    fn unwatch(&mut self, arg: Option<String>) -> Response {
+        match arg.as_deref() {
+            None | Some("all") => self.watches.clear(),
+            Some(n) => match n.parse::<usize>() {
+                Ok(n) if (1..=self.watches.len()).contains(&n) => {
+                    self.watches.remove(n - 1);
+                }
+                _ => return Response::error(format!("No watch numbered {}", n)),
+            },
+        }
+        Response::Empty
+    }
+
+    /// Evaluates the watch expressions, formatted as `[1] x + 1 = 2`
+    pub fn watches(&self) -> Vec<String> {
+        let watches = self
+            .watches
+            .iter()
+            .enumerate()
+            .map(|(i, expr)| {
+                let result = match interpreter::eval_expr(expr, &self.env) {
+                    Ok(value) => format_result(&value, &self.env),
+                    Err(e) => format!(": {}", e.to_string().red()),
+                };
+                format!("[{}] {}{}", i + 1, expr, result)
+            })
+            .collect();
+        // warnings were already shown when the watched names were defined
+        format_warnings(&self.env);
+        watches
+    }
+
+    fn workspace(&mut self, args: &[String]) -> Response {
+        let result = match args {
+            [] => {
+                let names = std::iter::once(&self.workspace)
+                    .chain(self.workspaces.iter().map(|(name, _)| name))
+                    .sorted()
+                    .map(|name| {
+                        if *name == self.workspace {
+                            format!("* {}", name)
+                        } else {
+                            format!("  {}", name)
+                        }
+                    })
+                    .join("\n");
+                return Response::text(names);
+            }
+            [subcmd, name] if subcmd == "new" => self.new_workspace(name),
+            [subcmd, name] if subcmd == "switch" => self.switch_workspace(name),
+            [subcmd, src, dest] if subcmd == "copy" => self.copy_workspace(src, dest),
+            [subcmd, name] if subcmd == "delete" => self.delete_workspace(name),
+            _ => Err("Usage: workspace [new/switch/delete name, copy src dest]".to_string()),
+        };
+        match result {
+            Ok(()) => Response::Empty,
+            Err(e) => Response::error(e),
+        }
+    }
+
+    fn has_workspace(&self, name: &str) -> bool {
+        self.workspace == name || self.workspaces.iter().any(|(x, _)| x == name)
+    }
+
+    /// Creates an empty workspace with the current settings and switches to it
+    fn new_workspace(&mut self, name: &str) -> Result<(), String> {
+        if self.has_workspace(name) {
+            return Err(format!("Workspace {} already exists", name));
+        }
+        let mut env = Environment::new();
+        *env.settings_mut() = self.env.settings().clone();
+        env.set_warnings(Some(Warnings::default()));
+        let prev_env = std::mem::replace(&mut self.env, env);
+        let prev_name = std::mem::replace(&mut self.workspace, name.to_string());
+        self.workspaces.push((prev_name, prev_env));
+        Ok(())
+    }
+
+    fn switch_workspace(&mut self, name: &str) -> Result<(), String> {
+        if self.workspace == name {
+            return Ok(());
+        }
+        let i = self
+            .workspaces
+            .iter()
+            .position(|(x, _)| x == name)
+            .ok_or_else(|| format!("No workspace named {}", name))?;
+        let (name, env) = self.workspaces.remove(i);
+        let prev_env = std::mem::replace(&mut self.env, env);
+        let prev_name = std::mem::replace(&mut self.workspace, name);
+        self.workspaces.push((prev_name, prev_env));
+        Ok(())
+    }
+
+    fn copy_workspace(&mut self, src: &str, dest: &str) -> Result<(), String> {
+        if self.has_workspace(dest) {
+            return Err(format!("Workspace {} already exists", dest));
+        }
+        let env = if self.workspace == src {
+            &self.env
+        } else {
+            self.workspaces
+                .iter()
+                .find(|(x, _)| x == src)
+                .map(|(_, env)| env)
+                .ok_or_else(|| format!("No workspace named {}", src))?
+        };
+        let mut env = env.clone();
+        // warnings of the copy are collected separately from the original
+        env.set_warnings(Some(Warnings::default()));
+        self.workspaces.push((dest.to_string(), env));
+        Ok(())
+    }
+
+    fn delete_workspace(&mut self, name: &str) -> Result<(), String> {
+        if self.workspace == name {
+            return Err("Cannot delete the active workspace".to_string());
+        }
+        let i = self
+            .workspaces
+            .iter()
+            .position(|(x, _)| x == name)
+            .ok_or_else(|| format!("No workspace named {}", name))?;
+        self.workspaces.remove(i);
+        Ok(())
+    }
+
+    fn set_output(&mut self, arg: Option<String>) -> Response {
+        self.plain = match arg.as_deref() {
+            None => {
+                return Response::text(
+                    if self.plain {
+                        "Output: plain"
+                    } else {
+                        "Output: normal"
+                    }
+                    .to_string(),
+                )
+            }
+            Some("normal") => false,
+            Some("plain") => true,
+            Some(_) => return Response::error("Output must be normal or plain"),
+        };
+        Response::Empty
+    }
+
+    fn set_result_vars(&mut self, arg: Option<String>) -> Response {
+        match arg.as_deref() {
+            None => Response::text(
+                if self.result_vars.is_some() {
+                    "Result variables: on"
+                } else {
+                    "Result variables: off"
+                }
+                .to_string(),
+            ),
+            Some("on") => {
+                self.result_vars.get_or_insert(0);
+                Response::Empty
+            }
+            Some("off") => {
+                self.result_vars = None;
+                Response::Empty
+            }
+            Some(_) => Response::error("Result variables must be on or off"),
+        }
+    }
+
+    fn set_record(&mut self, arg: Option<String>) -> Response {
+        match arg.as_deref() {
+            None => Response::text(
+                if self.record {
+                    "Record: on"
+                } else {
+                    "Record: off"
+                }
+                .to_string(),
+            ),
+            Some("on") => match self.env.resolve_field(&Identifier::new(RESULTS_NAME)) {
+                Ok(Value::List(_)) | Err(_) => {
+                    self.record = true;
+                    Response::Empty
+                }
+                Ok(_) => Response::error(format!(
+                    "{} is already defined as something other than a list",
+                    RESULTS_NAME
+                )),
+            },
+            Some("off") => {
+                self.record = false;
+                Response::Empty
+            }
+            Some(_) => Response::error("Record must be on or off"),
+        }
+    }
+
+    /// Appends `value`, the result of `expr`, to the list `results` if recording is on.
+    /// Results computed from the list itself, such as `avg(results)`, are left out.
+    fn record_result(&mut self, expr: &Expression, value: &Value) {
+        let name = Identifier::new(RESULTS_NAME);
+        if !self.record || expr.uses(&name) {
+            return;
+        }
+        let mut results = match self.env.resolve_field(&name) {
+            Ok(Value::List(xs)) => xs,
+            // leaves alone what the user has put in place of the list
+            Ok(_) => return,
+            Err(_) => Vec::new(),
+        };
+        results.push(value.clone());
+        let _ = self.env.assign_var(&name, Value::List(results));
+    }
+
+    /// Assigns `value` to the next unused name of the form `r1`, `r2`, ... if result variables
+    /// are on, and returns the name
+    fn name_result(&mut self, value: &Value) -> Option<Identifier> {
+        let count = self.result_vars.as_mut()?;
+        let name = loop {
+            *count += 1;
+            let name = Identifier(format!("r{}", count));
+            if !self.env.iter().any(|(x, _)| *x == name) {
+                break name;
+            }
+        };
+        self.env.assign_var(&name, value.clone()).ok()?;
+        Some(name)
+    }
+
+    fn set_rpn(&mut self, arg: Option<String>) -> Response {
+        match arg.as_deref() {
+            None => Response::text(match &self.rpn {
+                Some(rpn) => format!("RPN mode: on\n{}", format_stack(rpn.stack(), &self.env)),
+                None => "RPN mode: off".to_string(),
+            }),
+            Some("on") => {
+                self.rpn.get_or_insert_with(Rpn::default);
+                Response::Empty
+            }
+            Some("off") => {
+                self.rpn = None;
+                Response::Empty
+            }
+            Some(_) => Response::error("RPN mode must be on or off"),
+        }
+    }
+
+    pub fn completion_candidates(&self) -> impl Iterator<Item = Candidate> + '_ {
+        let last_result_names = if self.env.last_result().is_some() {
+            LAST_RESULT_NAMES
+        } else {
+            &[]
+        };
+
+        let commands = COMMANDS.iter().map(|name| Candidate {
+            name: name.to_string(),
+            kind: CandidateKind::Command,
+            signature: None,
+            doc: None,
+        });
+        let last_result = last_result_names.iter().map(|name| Candidate {
+            name: name.to_string(),
+            kind: CandidateKind::Variable,
+            signature: None,
+            doc: None,
+        });
+        let items = self.env.iter().map(|(name, item)| {
+            let (kind, signature) = match item {
+                NamedItem::Field(Field::Constant(_)) => (CandidateKind::Constant, None),
+                NamedItem::Field(Field::Variable(_)) => (CandidateKind::Variable, None),
+                NamedItem::Function(func) => (CandidateKind::Function, Some(func.signature(name))),
+            };
+            Candidate {
+                name: name.0.clone(),
+                kind,
+                signature,
+                doc: self.env.builtin(name).map(|builtin| builtin.doc),
+            }
+        });
+
+        commands
+            .chain(last_result)
+            .chain(items)
+            .unique_by(|x| x.name.clone())
+            .sorted_by(|a, b| a.name.cmp(&b.name))
+    }
+
+    /// Completes the name ending at the end of `left`, the part of input before the cursor.
+    /// Returns where the name starts and the candidates replacing it.
+    pub fn complete(&self, left: &str) -> (usize, Vec<Candidate>) {
+        let start = left
+            .trim_end_matches(|c: char| c.is_alphanumeric() || c == '_' || c == '.')
+            .len();
+        let prefix = &left[start..];
+        let candidates = self
+            .completion_candidates()
+            .filter(|x| x.name.starts_with(prefix))
+            .sorted_by_key(|x| self.usage.rank(&x.name))
+            .collect();
+        (start, candidates)
+    }
+
+    /// Counts of uses of names, one name per line, to be restored with `load_usage`
+    pub fn save_usage(&self) -> String {
+        self.usage
+            .names
+            .iter()
+            .sorted()
+            .map(|(name, (count, last))| format!("{} {} {}", count, last, name))
+            .join("\n")
+    }
+
+    /// Restores counts of uses of names saved by `save_usage`, skipping malformed lines
+    pub fn load_usage(&mut self, saved: &str) {
+        for line in saved.lines() {
+            let mut fields = line.splitn(3, ' ');
+            if let (Some(Ok(count)), Some(Ok(last)), Some(name)) = (
+                fields.next().map(str::parse),
+                fields.next().map(str::parse),
+                fields.next(),
+            ) {
+                self.usage.names.insert(name.to_string(), (count, last));
+                self.usage.inputs = self.usage.inputs.max(last);
+            }
+        }
+    }
 
-        Response::Message(msg_lines.join("\n"))
+    /// Signature of the function whose arguments are being typed in `left`,
+    /// the part of input before the cursor, such as `atan2(y, x)` for `atan2(1, `
+    pub fn signature_help(&self, left: &str) -> Option<String> {
+        open_parens(left).into_iter().rev().find_map(|i| {
+            let before = &left[..i];
+            let start = before
+                .trim_end_matches(|c: char| c.is_alphanumeric() || c == '_' || c == '.')
+                .len();
+            let name = Identifier::new(&before[start..]);
+            let func = self.env.resolve_func(&name).ok()?;
+            Some(func.signature(&name))
+        })
     }
 
-    pub fn completion_candidates(&self) -> impl Iterator<Item = &str> {
-        COMMANDS
+    /// User-defined variables and functions written as their definitions, such as `x = 1`
+    pub fn definitions(&self) -> Vec<String> {
+        self.env
             .iter()
-            .copied()
-            .chain(self.env.iter().map(|(name, _)| name.0.as_str()))
+            .filter_map(|(name, item)| match item {
+                NamedItem::Field(Field::Variable(value)) => Some(format!("{} = {}", name, value)),
+                NamedItem::Function(Function::UserDefined { params, expr }) => {
+                    Some(format_func_def(name, params, expr, &self.env))
+                }
+                _ => None,
+            })
             .sorted()
+            .collect()
+    }
+}
+
+/// Positions of the parentheses left open in `input`, from the outermost.
+/// Brackets and braces are tracked too so that `]` does not close a parenthesis.
+fn open_parens(input: &str) -> Vec<usize> {
+    let mut open = Vec::new();
+    let mut in_string = false;
+    for (i, c) in input.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            _ if in_string => (),
+            '#' => return Vec::new(),
+            '(' | '[' | '{' => open.push((i, c)),
+            ')' | ']' | '}' => {
+                open.pop();
+            }
+            _ => (),
+        }
+    }
+    open.into_iter()
+        .filter(|(_, c)| *c == '(')
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Whether `piece`, which starts with the name of a command, uses the name in a statement
+/// instead, as in the assignment `error = 0.05`, the definition `hist(x) = x` and the call
+/// `log (x)`
+fn is_statement(piece: &str, env: &Environment) -> bool {
+    let name = piece.split_whitespace().next().unwrap_or_default();
+    let rest = piece[name.len()..].trim_start();
+    if rest.starts_with('=') {
+        return true;
+    }
+    if !rest.starts_with('(') {
+        return false;
+    }
+    let is_func = env.resolve_func(&Identifier::new(name)).is_ok();
+    is_func
+        || matches!(
+            interpreter::parse(piece, env).as_deref(),
+            Ok([Statement::FunctionDefinition(_)])
+        )
+}
+
+/// Splits input into the byte ranges of pieces separated by `;` or line breaks,
+/// which may be either statements or commands. Separators in string literals, comments and
+/// cases in braces don't count, as with the statements of the language.
+fn split_statements(input: &str) -> Vec<Range<usize>> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut in_string = false;
+    let mut in_comment = false;
+    let mut braces = 0usize;
+    for (i, c) in input.char_indices() {
+        match c {
+            '\n' => {
+                pieces.push(start..i);
+                start = i + 1;
+                in_string = false;
+                in_comment = false;
+                braces = 0;
+            }
+            _ if in_comment => (),
+            '"' => in_string = !in_string,
+            _ if in_string => (),
+            '#' => in_comment = true,
+            '{' => braces += 1,
+            '}' => braces = braces.saturating_sub(1),
+            ';' if braces == 0 => {
+                pieces.push(start..i);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    pieces.push(start..input.len());
+    pieces
+}
+
+/// Splits input into the inputs of a pasted transcript, stripping prompts `> ` and results ` = x`.
+/// Input without prompts is kept as a whole apart from the results.
+fn strip_transcript(input: &str) -> Vec<String> {
+    if input.lines().any(|line| line.starts_with("> ")) {
+        input
+            .lines()
+            .filter_map(|line| line.strip_prefix("> "))
+            .map(|line| line.to_string())
+            .collect()
+    } else if input
+        .lines()
+        .any(|line| line.trim_start().starts_with("= "))
+    {
+        vec![input
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("= "))
+            .join("\n")]
+    } else {
+        vec![input.to_string()]
     }
 }
 
 #[derive(Debug, Clone)]
 enum Command {
-    Help,
-    List,
-    Delete(Vec<Identifier>),
-    Reset,
-    Clear,
+    Help(Option<String>),
+    List {
+        ns: Option<String>,
+        /// Whether built-ins are listed as well
+        all: bool,
+    },
+    Delete(Vec<DeleteTarget>),
+    Reset(Vec<String>),
+    Rename(Vec<Identifier>),
+    Find {
+        pattern: Option<String>,
+        values: bool,
+    },
+    Bits(Option<String>),
+    SetMod(Option<String>),
+    SigFigs(Option<String>),
+    Rounding(Option<String>),
+    Exact(Option<String>),
+    CallMul(Option<String>),
+    Strict(Option<String>),
+    Guard(Option<String>),
+    ImplicitMul(Option<String>),
+    Wrap(Option<String>),
+    Run(Option<String>),
+    Explain(String),
+    Hist {
+        expr: String,
+        bins: Option<String>,
+    },
+    Parse(String),
+    Repr,
+    Trace(Option<String>),
+    Warnings(Option<String>),
+    Rpn(Option<String>),
+    Echo(Option<String>),
+    ResultVars(Option<String>),
+    Record(Option<String>),
+    Output(Option<String>),
+    Watch(String),
+    Unwatch(Option<String>),
+    Workspace(Vec<String>),
+    Log(Option<String>),
+    Error,
+    History(Option<String>),
+    Clear {
+        keep_scrollback: bool,
+    },
     Quit,
 }
 
+#[derive(Debug, Clone)]
+enum DeleteTarget {
+    Name(Identifier),
+    /// Glob with `*` and `?` wildcards, or regular expression enclosed in slashes
+    Pattern(String),
+    AllVariables,
+}
+
+impl DeleteTarget {
+    fn from_arg(arg: &str) -> Self {
+        if arg == "--all-vars" {
+            Self::AllVariables
+        } else if is_pattern(arg) {
+            Self::Pattern(arg.to_string())
+        } else {
+            Self::Name(Identifier::new(arg))
+        }
+    }
+}
+
+fn is_pattern(s: &str) -> bool {
+    s.contains(['*', '?']) || (s.len() >= 2 && s.starts_with('/') && s.ends_with('/'))
+}
+
+/// Compiles a glob with `*` and `?` wildcards or a regular expression enclosed in slashes,
+/// either of which has to match whole names
+fn compile_pattern(pattern: &str) -> Result<Regex, String> {
+    let regex = match pattern.strip_prefix('/').and_then(|x| x.strip_suffix('/')) {
+        Some(regex) => format!("^(?:{})$", regex),
+        None => format!(
+            "^{}$",
+            regex::escape(pattern)
+                .replace(r"\*", ".*")
+                .replace(r"\?", ".")
+        ),
+    };
+    Regex::new(&regex).map_err(|_| format!("Invalid pattern {}", pattern))
+}
+
 #[rustfmt::skip]
 static COMMANDS: &[&str] = &[
     "help", "?",
     "list", "ls", "ll",
     "delete", "del", "rm",
     "reset",
+    "rename",
+    "find",
+    "bits",
+    "setmod",
+    "sigfigs",
+    "rounding",
+    "exact",
+    "callmul",
+    "strict",
+    "guard",
+    "implicitmul",
+    "wrap",
+    "run",
+    "explain",
+    "hist",
+    "parse", "whatis",
+    "repr",
+    "trace",
+    "warnings",
+    "rpn",
+    "echo",
+    "autovars",
+    "record",
+    "watch",
+    "unwatch",
+    "workspace", "ws",
+    "log",
+    "error",
+    "output",
+    "history",
     "clear", "cls",
     "quit", "exit",
 ];
@@ -102,17 +1241,73 @@ impl std::str::FromStr for Command {
     fn from_str(s: &str) -> Result<Self, ()> {
         let mut tokens = s.split_whitespace();
         let name = tokens.next().ok_or(())?.to_ascii_lowercase();
-        let args = tokens;
+        let mut args = tokens;
 
         match &name[..] {
-            "help" | "?" => Ok(Self::Help),
-            "list" | "ls" | "ll" => Ok(Self::List),
-            "delete" | "del" | "rm" => {
-                let idents: Vec<_> = args.map(|ident| Identifier(ident.to_string())).collect();
-                Ok(Self::Delete(idents))
-            }
-            "reset" => Ok(Self::Reset),
-            "clear" | "cls" => Ok(Self::Clear),
+            "help" | "?" => Ok(Self::Help(args.next().map(|x| x.to_string()))),
+            "list" | "ls" | "ll" => {
+                let (flags, mut namespaces): (Vec<_>, Vec<_>) =
+                    args.partition(|x| x.starts_with("--"));
+                Ok(Self::List {
+                    ns: namespaces.pop().map(|x| x.to_string()),
+                    all: flags.contains(&"--all"),
+                })
+            }
+            "delete" | "del" | "rm" => Ok(Self::Delete(args.map(DeleteTarget::from_arg).collect())),
+            "reset" => Ok(Self::Reset(args.map(|x| x.to_string()).collect())),
+            "rename" => Ok(Self::Rename(args.map(Identifier::new).collect())),
+            "find" => {
+                let (flags, patterns): (Vec<_>, Vec<_>) = args.partition(|x| x.starts_with("--"));
+                Ok(Self::Find {
+                    pattern: Some(patterns.join(" ")).filter(|x| !x.is_empty()),
+                    values: flags.contains(&"--values"),
+                })
+            }
+            "setmod" => Ok(Self::SetMod(args.next().map(|x| x.to_ascii_lowercase()))),
+            "bits" => Ok(Self::Bits(args.next().map(|x| x.to_ascii_lowercase()))),
+            "sigfigs" => Ok(Self::SigFigs(args.next().map(|x| x.to_ascii_lowercase()))),
+            "rounding" => Ok(Self::Rounding(args.next().map(|x| x.to_ascii_lowercase()))),
+            "exact" => Ok(Self::Exact(args.next().map(|x| x.to_ascii_lowercase()))),
+            "callmul" => Ok(Self::CallMul(args.next().map(|x| x.to_ascii_lowercase()))),
+            "wrap" => Ok(Self::Wrap(args.next().map(|x| x.to_ascii_lowercase()))),
+            "strict" => Ok(Self::Strict(args.next().map(|x| x.to_ascii_lowercase()))),
+            "guard" => Ok(Self::Guard(args.next().map(|x| x.to_ascii_lowercase()))),
+            "implicitmul" => Ok(Self::ImplicitMul(
+                args.next().map(|x| x.to_ascii_lowercase()),
+            )),
+            "explain" => Ok(Self::Explain(s.trim_start()[name.len()..].to_string())),
+            "hist" => {
+                let rest = &s.trim_start()[name.len()..];
+                let (expr, bins) = match rest.split_once("--bins") {
+                    Some((expr, bins)) => (expr, Some(bins.trim().to_string())),
+                    None => (rest, None),
+                };
+                Ok(Self::Hist {
+                    expr: expr.to_string(),
+                    bins,
+                })
+            }
+            "trace" => Ok(Self::Trace(args.next().map(|x| x.to_ascii_lowercase()))),
+            "warnings" => Ok(Self::Warnings(args.next().map(|x| x.to_ascii_lowercase()))),
+            "echo" => Ok(Self::Echo(args.next().map(|x| x.to_ascii_lowercase()))),
+            "output" => Ok(Self::Output(args.next().map(|x| x.to_ascii_lowercase()))),
+            "watch" => Ok(Self::Watch(s.trim_start()[name.len()..].to_string())),
+            "unwatch" => Ok(Self::Unwatch(args.next().map(|x| x.to_ascii_lowercase()))),
+            "workspace" | "ws" => Ok(Self::Workspace(args.map(|x| x.to_string()).collect())),
+            "log" => Ok(Self::Log(Some(args.join(" ")).filter(|x| !x.is_empty()))),
+            "error" => Ok(Self::Error),
+            "record" => Ok(Self::Record(args.next().map(|x| x.to_ascii_lowercase()))),
+            "autovars" => Ok(Self::ResultVars(
+                args.next().map(|x| x.to_ascii_lowercase()),
+            )),
+            "history" => Ok(Self::History(args.next().map(|x| x.to_ascii_lowercase()))),
+            "rpn" => Ok(Self::Rpn(args.next().map(|x| x.to_ascii_lowercase()))),
+            "repr" => Ok(Self::Repr),
+            "parse" | "whatis" => Ok(Self::Parse(s.trim_start()[name.len()..].to_string())),
+            "run" => Ok(Self::Run(Some(args.join(" ")).filter(|x| !x.is_empty()))),
+            "clear" | "cls" => Ok(Self::Clear {
+                keep_scrollback: args.any(|x| x == "--keep-scrollback"),
+            }),
             "quit" | "exit" => Ok(Self::Quit),
             _ => Err(()),
         }
@@ -121,86 +1316,931 @@ impl std::str::FromStr for Command {
 
 fn exec_command(cmd: Command, env: &mut Environment) -> Response {
     match cmd {
-        Command::Help => {
-            Response::Message("Documentation: https://github.com/mosmeh/beek#reference".to_string())
+        Command::Help(None) => {
+            Response::text("Documentation: https://github.com/mosmeh/beek#reference".to_string())
         }
-        Command::List => {
-            let msg_consts = format_fields(env.iter().filter_map(|(name, item)| match item {
+        Command::Help(Some(name)) => {
+            let ident = Identifier::new(&name);
+            let builtin = match env.builtin(&ident) {
+                Some(builtin) => builtin,
+                None => return Response::error(format!("No built-in named {}", ident)),
+            };
+            let usage = match env.iter().find(|(x, _)| **x == ident) {
+                Some((_, NamedItem::Function(func))) => func.signature(&ident),
+                _ => ident.to_string(),
+            };
+            let mut msg_lines = vec![usage, builtin.doc.to_string()];
+            msg_lines.push(format!("> {}", builtin.example));
+            // examples run apart from the user's definitions, and are shown without results
+            // if they depend on something outside, such as files
+            let mut example_env = Environment::new();
+            *example_env.settings_mut() = env.settings().clone();
+            let result = interpreter::parse(builtin.example, &example_env)
+                .map_err(|_| ())
+                .and_then(|stmts| {
+                    stmts.iter().try_fold(None, |_, stmt| {
+                        interpreter::exec_stmt(stmt, &mut example_env).map_err(|_| ())
+                    })
+                });
+            if let Ok(Some(value)) = result {
+                msg_lines.push(format_result(&value, &example_env));
+            }
+            Response::text(msg_lines.join("\n"))
+        }
+        Command::List { ns, all } => {
+            let in_ns = |name: &Identifier| ns.as_ref().is_none_or(|ns| name.is_in_namespace(ns));
+            let user_items = || {
+                env.iter()
+                    .filter(|(name, _)| env.builtin(name).is_none() && in_ns(name))
+            };
+
+            let consts = format_fields(user_items().filter_map(|(name, item)| match item {
                 NamedItem::Field(Field::Constant(value)) => Some((name, value)),
                 _ => None,
             }));
 
-            let msg_vars = format_fields(env.iter().filter_map(|(name, item)| match item {
+            let vars = format_fields(user_items().filter_map(|(name, item)| match item {
                 NamedItem::Field(Field::Variable(value)) => Some((name, value)),
                 _ => None,
             }));
 
-            let msg_funcs = env
-                .iter()
+            let funcs = user_items()
                 .filter_map(|(name, item)| match item {
-                    NamedItem::Function(Function::UserDefined { arg_names, expr }) => {
-                        Some(format!(
-                            "{}({}) = {}\n",
-                            name,
-                            arg_names
-                                .iter()
-                                .map(|x| x.to_string())
-                                .collect::<Vec<_>>()
-                                .join(", "),
-                            expr
-                        ))
+                    NamedItem::Function(Function::UserDefined { params, expr }) => {
+                        Some(format_func_def(name, params, expr, env))
                     }
                     _ => None,
                 })
                 .sorted()
-                .collect::<Vec<_>>()
-                .concat();
-
-            Response::Message(format!(
-                r#"Constants:
-{}
-Variables:
-{}
-User-defined functions:
-{}"#,
-                msg_consts, msg_vars, msg_funcs
-            ))
-        }
-        Command::Delete(idents) => {
-            let errors: Vec<_> = idents
+                .collect();
+
+            let units = align_columns(
+                env.units()
+                    .filter(|(name, _)| in_ns(name))
+                    .sorted_by(|(a, _), (b, _)| a.cmp(b))
+                    .map(|(name, unit)| (name.to_string(), format!("= {}", unit.size()))),
+            );
+
+            let last_result = match env.last_result() {
+                Some(value) if ns.is_none() => vec![format!(
+                    "{} = {}",
+                    LAST_RESULT_NAMES
+                        .iter()
+                        .map(|name| Identifier(name.to_string()))
+                        .join(" = "),
+                    value
+                )],
+                _ => Vec::new(),
+            };
+
+            let mut sections = vec![
+                ("Constants".to_string(), consts),
+                ("Variables".to_string(), vars),
+                ("User-defined functions".to_string(), funcs),
+                ("User-defined units".to_string(), units),
+                ("Last result".to_string(), last_result),
+            ];
+            if all {
+                let builtins = env
+                    .iter()
+                    .filter(|(name, _)| in_ns(name))
+                    .filter_map(|(name, item)| Some((env.builtin(name)?, name, item)))
+                    .sorted_by_key(|(builtin, name, _)| (builtin.category, *name))
+                    .group_by(|(builtin, _, _)| builtin.category);
+                for (category, items) in &builtins {
+                    let items: Vec<_> = items.collect();
+                    let consts =
+                        format_fields(items.iter().filter_map(|(_, name, item)| match item {
+                            NamedItem::Field(field) => Some((*name, field.value())),
+                            _ => None,
+                        }));
+                    let funcs =
+                        align_columns(items.iter().filter_map(
+                            |(builtin, name, item)| match item {
+                                NamedItem::Function(func) => Some((
+                                    func.signature(name),
+                                    format!(" {}", builtin.doc.dimmed()),
+                                )),
+                                _ => None,
+                            },
+                        ));
+                    sections.push((category.to_string(), [consts, funcs].concat()));
+                }
+            }
+
+            let msg = sections
                 .into_iter()
-                .filter_map(|ident| env.delete(&ident).err())
-                .map(|err| err.to_string())
+                .filter(|(_, lines)| !lines.is_empty())
+                .map(|(title, lines)| format!("{}:\n{}", title, lines.join("\n")))
+                .join("\n\n");
+            if msg.is_empty() {
+                Response::text("Nothing is defined. `list --all` lists built-ins".to_string())
+            } else {
+                Response::text(msg)
+            }
+        }
+        Command::Delete(targets) => {
+            let errors: Vec<_> = targets
+                .into_iter()
+                .filter_map(|target| delete(target, env).err())
                 .collect();
             if errors.is_empty() {
                 Response::Empty
             } else {
-                Response::Message(errors.join("\n").red().to_string())
+                Response::error(errors.join("\n"))
+            }
+        }
+        Command::Reset(args) => {
+            match args.split_first() {
+                None => env.reset(),
+                Some((target, [])) if target == "vars" => {
+                    env.retain(|_, item| !matches!(item, NamedItem::Field(Field::Variable(_))))
+                }
+                Some((target, [])) if target == "funcs" => {
+                    env.retain(|_, item| !matches!(item, NamedItem::Function(_)))
+                }
+                Some((target, names)) if target == "keep" && !names.is_empty() => {
+                    let names: Vec<_> = names
+                        .join(" ")
+                        .split([',', ' '])
+                        .filter(|x| !x.is_empty())
+                        .map(Identifier::new)
+                        .collect();
+                    if let Err(err) = env.reset_keeping(&names) {
+                        return Response::error(err.to_string());
+                    }
+                }
+                _ => {
+                    return Response::error(
+                        "Reset target must be vars, funcs or keep followed by names",
+                    )
+                }
+            }
+            Response::Empty
+        }
+        Command::Rename(idents) => match &idents[..] {
+            [from, to] if is_valid_name(to) => match env.rename(from, to) {
+                Ok(()) => Response::Empty,
+                Err(err) => Response::error(err.to_string()),
+            },
+            [_, to] => Response::error(format!("{} is not a valid name", to)),
+            _ => Response::error("Specify an old and a new name"),
+        },
+        Command::Find { pattern: None, .. } => Response::error("Specify a pattern to find"),
+        Command::Find {
+            pattern: Some(pattern),
+            values,
+        } => {
+            let regex = if is_pattern(&pattern) {
+                compile_pattern(&pattern)
+            } else {
+                Regex::new(&format!("(?i){}", regex::escape(&pattern)))
+                    .map_err(|err| err.to_string())
+            };
+            let regex = match regex {
+                Ok(regex) => regex,
+                Err(err) => return Response::error(err),
+            };
+
+            let matches: Vec<_> = env
+                .iter()
+                .filter_map(|(name, item)| {
+                    let (def, value) = match item {
+                        NamedItem::Field(Field::Constant(value)) => {
+                            (format!("const {}", name), Some(value.to_string()))
+                        }
+                        NamedItem::Field(Field::Variable(value)) => {
+                            (name.to_string(), Some(value.to_string()))
+                        }
+                        NamedItem::Function(Function::UserDefined { params, expr }) => {
+                            (format_signature(name, params), Some(expr.to_string()))
+                        }
+                        NamedItem::Function(_) => (format!("{} (built-in function)", name), None),
+                    };
+                    let is_match = regex.is_match(&name.0)
+                        || (values && value.as_ref().is_some_and(|x| regex.is_match(x)));
+                    is_match.then(|| match value {
+                        Some(value) => format!("{} = {}", def, value),
+                        None => def,
+                    })
+                })
+                .sorted()
+                .collect();
+            if matches.is_empty() {
+                Response::text(format!("Nothing matches {}", pattern))
+            } else {
+                Response::text(matches.join("\n"))
+            }
+        }
+        Command::Bits(None) => Response::text(match env.settings().bits {
+            Some(bits) => format!("Programmer mode: {} bits", bits),
+            None => "Programmer mode: off".to_string(),
+        }),
+        Command::Bits(Some(arg)) => {
+            let bits = match arg.as_str() {
+                "off" => None,
+                _ => match arg.parse() {
+                    Ok(bits) if BIT_WIDTHS.contains(&bits) => Some(bits),
+                    _ => {
+                        return Response::error(format!(
+                            "Bit width must be one of {} or off",
+                            BIT_WIDTHS.iter().join(", ")
+                        ))
+                    }
+                },
+            };
+            env.settings_mut().bits = bits;
+            Response::Empty
+        }
+        Command::SetMod(None) => Response::text(match env.settings().modulus {
+            Some(modulus) => format!("Arithmetic modulo {}", modulus),
+            None => "Modular arithmetic: off".to_string(),
+        }),
+        Command::SetMod(Some(arg)) => {
+            let modulus = match arg.as_str() {
+                "off" => None,
+                _ => match arg.parse() {
+                    Ok(modulus) if (2..=MAX_MODULUS).contains(&modulus) => Some(modulus),
+                    _ => {
+                        return Response::error(format!(
+                            "Modulus must be an integer between 2 and {} or off",
+                            MAX_MODULUS
+                        ))
+                    }
+                },
+            };
+            env.settings_mut().modulus = modulus;
+            Response::Empty
+        }
+        Command::SigFigs(None) => Response::text(match env.settings().sigfigs {
+            Some(sigfigs) => format!("Significant figures: {}", sigfigs),
+            None => "Significant figures: off".to_string(),
+        }),
+        Command::SigFigs(Some(arg)) => {
+            let sigfigs = match arg.as_str() {
+                "off" => None,
+                _ => match arg.parse() {
+                    Ok(sigfigs) if (1..=MAX_SIGFIGS).contains(&sigfigs) => Some(sigfigs),
+                    _ => {
+                        return Response::error(format!(
+                            "Significant figures must be between 1 and {} or off",
+                            MAX_SIGFIGS
+                        ))
+                    }
+                },
+            };
+            env.settings_mut().sigfigs = sigfigs;
+            Response::Empty
+        }
+        Command::Wrap(None) => Response::text(match env.settings().wrap_width {
+            Some(width) => format!("Wrap width: {}", width),
+            None => "Wrap width: off".to_string(),
+        }),
+        Command::Wrap(Some(arg)) => {
+            let width = match arg.as_str() {
+                "off" => None,
+                _ => match arg.parse() {
+                    Ok(width) if width >= MIN_WRAP_WIDTH => Some(width),
+                    _ => {
+                        return Response::error(format!(
+                            "Wrap width must be at least {} or off",
+                            MIN_WRAP_WIDTH
+                        ))
+                    }
+                },
+            };
+            env.settings_mut().wrap_width = width;
+            Response::Empty
+        }
+        Command::Rounding(None) => {
+            Response::text(format!("Rounding: {}", env.settings().rounding.name()))
+        }
+        Command::Rounding(Some(arg)) => {
+            match RoundingMode::NAMES.iter().find(|(name, _)| *name == arg) {
+                Some((_, mode)) => env.settings_mut().rounding = *mode,
+                None => {
+                    return Response::error(format!(
+                        "Rounding must be one of {}",
+                        RoundingMode::NAMES.iter().map(|(name, _)| name).join(", ")
+                    ))
+                }
+            }
+            Response::Empty
+        }
+        Command::Run(None) => Response::error("Specify a file to run"),
+        Command::Run(Some(path)) => match interpreter::exec_file(Path::new(&path), env) {
+            Ok(Some(value)) => Response::text(format_result(&value, env)),
+            Ok(None) => Response::Empty,
+            Err(err) => Response::error(err.to_string()),
+        },
+        Command::Parse(input) => match interpreter::parse(&input, env) {
+            Ok(stmts) => Response::text(
+                stmts
+                    .iter()
+                    .map(|stmt| Explicit(stmt).to_string())
+                    .join("\n"),
+            ),
+            Err(e) => Response::error(e.to_string().trim()),
+        },
+        Command::Explain(input) => {
+            let expr = match interpreter::parse(&input, env) {
+                Ok(stmts) => match &stmts[..] {
+                    [Statement::Expression(expr)] => expr.clone(),
+                    _ => return Response::error("Specify an expression to explain"),
+                },
+                Err(e) => return Response::error(e.to_string().trim()),
+            };
+
+            let (result, events) = interpreter::explain(&expr, env);
+            let mut msg_lines = vec![expr.to_string()];
+            msg_lines.extend(events.iter().map(format_event));
+            msg_lines.push(match result {
+                Ok(value) => format_result(&value, env),
+                Err(e) => e.to_string().red().to_string(),
+            });
+            Response::text(msg_lines.join("\n"))
+        }
+        Command::Hist { expr, bins } => {
+            let bins = match bins.map(|x| x.parse()) {
+                None => None,
+                Some(Ok(bins)) if (1..=MAX_BINS).contains(&bins) => Some(bins),
+                Some(_) => {
+                    return Response::error(format!(
+                        "Number of bins must be between 1 and {}",
+                        MAX_BINS
+                    ))
+                }
+            };
+            let expr = match interpreter::parse(&expr, env) {
+                Ok(stmts) => match &stmts[..] {
+                    [Statement::Expression(expr)] => expr.clone(),
+                    _ => return Response::error("Specify a list to draw"),
+                },
+                Err(e) => return Response::error(e.to_string().trim()),
+            };
+            let xs = interpreter::eval_expr(&expr, env).and_then(|value| {
+                value
+                    .as_list()?
+                    .iter()
+                    .map(Value::as_f64)
+                    .collect::<Result<Vec<_>, _>>()
+            });
+            match xs {
+                Ok(xs) if xs.is_empty() => {
+                    Response::error("Cannot draw a histogram of an empty list")
+                }
+                Ok(xs) => Response::text(format_histogram(&xs, bins)),
+                Err(e) => Response::error(e.to_string()),
+            }
+        }
+        Command::Trace(None) => Response::text(
+            if env.trace().is_some() {
+                "Trace: on"
+            } else {
+                "Trace: off"
+            }
+            .to_string(),
+        ),
+        Command::Trace(Some(arg)) => {
+            let trace = match arg.as_str() {
+                "on" => Some(Trace::calls()),
+                "off" => None,
+                _ => return Response::error("Trace must be on or off"),
+            };
+            env.set_trace(trace);
+            Response::Empty
+        }
+        Command::Exact(None) => Response::text(
+            if env.settings().exact {
+                "Exact forms: on"
+            } else {
+                "Exact forms: off"
+            }
+            .to_string(),
+        ),
+        Command::Exact(Some(arg)) => {
+            env.settings_mut().exact = match arg.as_str() {
+                "on" => true,
+                "off" => false,
+                _ => return Response::error("Exact forms must be on or off"),
+            };
+            Response::Empty
+        }
+        Command::CallMul(None) => Response::text(
+            if env.settings().call_multiplies {
+                "Calls on numbers multiply: on"
+            } else {
+                "Calls on numbers multiply: off"
+            }
+            .to_string(),
+        ),
+        Command::CallMul(Some(arg)) => {
+            env.settings_mut().call_multiplies = match arg.as_str() {
+                "on" => true,
+                "off" => false,
+                _ => return Response::error("Multiplying calls on numbers must be on or off"),
+            };
+            Response::Empty
+        }
+        Command::Strict(None) => Response::text(
+            if env.settings().strict {
+                "Strict mode: on"
+            } else {
+                "Strict mode: off"
+            }
+            .to_string(),
+        ),
+        Command::Strict(Some(arg)) => {
+            env.settings_mut().strict = match arg.as_str() {
+                "on" => true,
+                "off" => false,
+                _ => return Response::error("Strict mode must be on or off"),
+            };
+            Response::Empty
+        }
+        Command::Guard(None) => Response::text(
+            if env.settings().guard {
+                "Guard against overwriting: on"
+            } else {
+                "Guard against overwriting: off"
             }
+            .to_string(),
+        ),
+        Command::Guard(Some(arg)) => {
+            env.settings_mut().guard = match arg.as_str() {
+                "on" => true,
+                "off" => false,
+                _ => return Response::error("Guard against overwriting must be on or off"),
+            };
+            Response::Empty
         }
-        Command::Reset => {
-            *env = Environment::new();
+        Command::ImplicitMul(None) => Response::text(
+            if env.settings().implicit_mul() {
+                "Implicit multiplication: on"
+            } else {
+                "Implicit multiplication: off"
+            }
+            .to_string(),
+        ),
+        Command::ImplicitMul(Some(arg)) => {
+            env.settings_mut().explicit_mul = match arg.as_str() {
+                "on" => false,
+                "off" => true,
+                _ => return Response::error("Implicit multiplication must be on or off"),
+            };
+            Response::Empty
+        }
+        Command::Warnings(None) => Response::text(
+            if env.warnings().is_some() {
+                "Warnings: on"
+            } else {
+                "Warnings: off"
+            }
+            .to_string(),
+        ),
+        Command::Warnings(Some(arg)) => {
+            let warnings = match arg.as_str() {
+                "on" => Some(Warnings::default()),
+                "off" => None,
+                _ => return Response::error("Warnings must be on or off"),
+            };
+            env.set_warnings(warnings);
             Response::Empty
         }
-        Command::Clear => Response::ClearScreen,
+        // handled by Repl since they have their own state
+        Command::Rpn(_)
+        | Command::Echo(_)
+        | Command::ResultVars(_)
+        | Command::Record(_)
+        | Command::Output(_)
+        | Command::Watch(_)
+        | Command::Unwatch(_)
+        | Command::Workspace(_)
+        | Command::Log(_)
+        | Command::Error => {
+            unreachable!()
+        }
+        Command::Repr => match env.last_result() {
+            Some(value) => match format_representations(value) {
+                Ok(msg) => Response::text(msg),
+                Err(e) => Response::error(e.to_string()),
+            },
+            None => Response::error("No result to show yet"),
+        },
+        Command::History(arg) if arg.as_deref() == Some("clear") => Response::ClearHistory,
+        Command::History(_) => Response::error("Use history clear to clear history"),
+        Command::Clear { keep_scrollback } => Response::ClearScreen { keep_scrollback },
         Command::Quit => Response::Quit,
     }
 }
 
-fn format_fields<'a>(iter: impl Iterator<Item = (&'a Identifier, &'a Number)>) -> String {
-    iter.sorted_by(|(a_name, a_value), (b_name, b_value)| {
-        a_value
-            .partial_cmp(b_value)
-            .unwrap() // fields don't contain NaNs
-            .then_with(|| a_name.cmp(b_name))
-    })
-    .group_by(|(_, value)| *value)
-    .into_iter()
-    .map(|(value, fields)| {
-        let names = fields.map(|(name, _)| name).join(" = ");
-        format!("{} = {}\n", names, value)
+/// Message of `lines`, or no message if there are none
+fn message(lines: Vec<Line>) -> Response {
+    if lines.is_empty() {
+        Response::Empty
+    } else {
+        Response::Message(Message { lines })
+    }
+}
+
+fn format_warnings(env: &Environment) -> Vec<Line> {
+    env.warnings().map_or_else(Vec::new, |warnings| {
+        warnings
+            .take()
+            .iter()
+            .map(|warning| {
+                let text = format!("Warning: {}", warning).dimmed().to_string();
+                Line::new(LineKind::Warning, text)
+            })
+            .collect()
     })
-    .sorted()
-    .collect::<Vec<_>>()
-    .concat()
+}
+
+/// Formats the stack with the top at the bottom, as HP calculators do
+fn format_stack(stack: &[Value], env: &Environment) -> String {
+    stack
+        .iter()
+        .enumerate()
+        .map(|(i, value)| format!("{}: {}", stack.len() - i, format_value(value, env)))
+        .join("\n")
+}
+
+fn format_event(event: &Event) -> String {
+    let indent = |depth| "  ".repeat(depth + 1);
+    match event {
+        Event::Rewrite { depth, expr, value } => format!("{}{} → {}", indent(*depth), expr, value),
+        Event::Call { depth, name, args } => format!(
+            "{}→ {}({})",
+            indent(*depth),
+            Identifier(name.clone()),
+            args.iter().join(", ")
+        ),
+        Event::Return { depth, value } => format!("{}← {}", indent(*depth), value),
+    }
+}
+
+const MAX_BINS: usize = 100;
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Draws a histogram of `xs` with bars of `█`. The number of bins defaults to Sturges' rule.
+fn format_histogram(xs: &[f64], bins: Option<usize>) -> String {
+    let bins = bins.unwrap_or_else(|| (xs.len() as f64).log2().ceil() as usize + 1);
+    let min = xs.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = xs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    // a single bin holds everything if all the values are the same
+    let bins = if min == max { 1 } else { bins };
+    let width = (max - min) / bins as f64;
+
+    let mut counts = vec![0; bins];
+    for x in xs {
+        let i = if width > 0.0 {
+            ((x - min) / width) as usize
+        } else {
+            0
+        };
+        // the maximum belongs to the last bin, which is closed
+        counts[i.min(bins - 1)] += 1;
+    }
+
+    let edge = |i: usize| {
+        Number(settings::round_to_sigfigs(
+            min + width * i as f64,
+            4,
+            RoundingMode::HalfUp,
+        ))
+        .to_string()
+    };
+    let labels: Vec<_> = (0..bins)
+        .map(|i| {
+            let close = if i == bins - 1 { ']' } else { ')' };
+            format!("[{}, {}{}", edge(i), edge(i + 1), close)
+        })
+        .collect();
+    let label_width = labels.iter().map(|x| x.chars().count()).max().unwrap_or(0);
+    let max_count = counts.iter().copied().max().unwrap_or(0);
+    labels
+        .iter()
+        .zip(counts)
+        .map(|(label, count)| {
+            let bar = "█".repeat(count * HISTOGRAM_WIDTH / max_count);
+            format!(
+                "{}{} {} {}",
+                label,
+                " ".repeat(label_width - label.chars().count()),
+                bar,
+                count
+            )
+        })
+        .join("\n")
+}
+
+/// Current time in UTC in the form of `2022-02-13T09:05:42Z`
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // converts days since the epoch into a civil date in the proleptic Gregorian calendar
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// Removes ANSI escape sequences setting colors
+fn strip_colors(s: &str) -> String {
+    let mut stripped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // sequences are of the form `ESC [ ... m`
+            chars.by_ref().find(|c| *c == 'm');
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Lists `value` written in several ways for cross-checking
+fn format_representations(value: &Value) -> Result<String, interpreter::EvalError> {
+    let x = value.as_f64()?;
+    let mut reprs = vec![
+        ("decimal", Number(x).to_string()),
+        ("scientific", format!("{:e}", x)),
+    ];
+    match value.to_exact_integer() {
+        Some(n) => {
+            reprs.push(("hexadecimal", format!("{:#x}", n)));
+            reprs.push(("binary", format!("{:#b}", n)));
+        }
+        None => reprs.extend(settings::fraction_form(x).map(|form| ("fraction", form))),
+    }
+    reprs.extend(settings::pi_form(x).map(|form| ("multiple of π", form)));
+
+    let width = reprs
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap();
+    Ok(reprs
+        .into_iter()
+        .map(|(label, repr)| {
+            format!(
+                "{}{}  {}",
+                label,
+                " ".repeat(width - label.chars().count()),
+                repr
+            )
+        })
+        .join("\n"))
+}
+
+/// Formats `value` as ` = value`, or ` ≈ value` if it is rounded for display
+fn format_result(value: &Value, env: &Environment) -> String {
+    let sign = if is_rounded(value, env) { '≈' } else { '=' };
+    format!(" {} {}", sign, format_value(value, env))
+}
+
+fn format_value(value: &Value, env: &Environment) -> String {
+    let shown = match (env.settings().sigfigs, value) {
+        (Some(n), Value::Number(x)) => {
+            Number(settings::round_to_sigfigs(x.0, n, env.settings().rounding)).to_string()
+        }
+        (Some(n), Value::Quantity(q)) => format!(
+            "{} {}",
+            Number(settings::round_to_sigfigs(
+                q.magnitude(),
+                n,
+                env.settings().rounding
+            )),
+            q.unit.symbol
+        ),
+        _ => value.to_string(),
+    };
+    let shown = match value {
+        Value::Number(x) if env.settings().exact && env.settings().bits.is_none() => {
+            match settings::exact_form(x.0) {
+                Some(form) => format!("{} ({})", shown, form),
+                None => shown,
+            }
+        }
+        _ => shown,
+    };
+    match (env.settings().bits, value.as_f64()) {
+        (Some(bits), Ok(x)) => {
+            let pattern = settings::to_bit_pattern(x, bits);
+            format!(
+                "{}  {}  {}",
+                shown,
+                format!("{:#x}", pattern).cyan(),
+                format!("{:#b}", pattern).cyan()
+            )
+        }
+        _ => shown,
+    }
+}
+
+/// Whether `value` is displayed rounded to significant figures
+fn is_rounded(value: &Value, env: &Environment) -> bool {
+    let x = match value {
+        Value::Number(x) => x.0,
+        Value::Quantity(q) => q.magnitude(),
+        _ => return false,
+    };
+    env.settings()
+        .sigfigs
+        .is_some_and(|n| settings::round_to_sigfigs(x, n, env.settings().rounding) != x)
+}
+
+fn delete(target: DeleteTarget, env: &mut Environment) -> Result<(), String> {
+    let names: Vec<_> = match target {
+        DeleteTarget::Name(ident) => return env.delete(&ident).map_err(|err| err.to_string()),
+        DeleteTarget::Pattern(pattern) => {
+            let regex = compile_pattern(&pattern)?;
+            let names: Vec<_> = env
+                .iter()
+                .filter(|(name, item)| is_user_defined(item) && regex.is_match(&name.0))
+                .map(|(name, _)| name.clone())
+                .collect();
+            if names.is_empty() {
+                return Err(format!("No variable or function matches {}", pattern));
+            }
+            names
+        }
+        DeleteTarget::AllVariables => env
+            .iter()
+            .filter(|(_, item)| matches!(item, NamedItem::Field(Field::Variable(_))))
+            .map(|(name, _)| name.clone())
+            .collect(),
+    };
+    for name in names {
+        env.delete(&name).map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
+
+/// Whether `item` is a variable or function that can be deleted
+fn is_user_defined(item: &NamedItem) -> bool {
+    match item {
+        NamedItem::Field(Field::Variable(_)) => true,
+        NamedItem::Field(Field::Constant(_)) => false,
+        NamedItem::Function(func) => !func.is_builtin(),
+    }
+}
+
+/// Writes a statement, wrapped at the width set with `wrap`
+fn format_stmt(stmt: &Statement, env: &Environment) -> String {
+    match env.settings().wrap_width {
+        Some(width) => Wrapped(stmt, width).to_string(),
+        None => stmt.to_string(),
+    }
+}
+
+fn format_func_def(
+    name: &Identifier,
+    params: &[Parameter],
+    expr: &Expression,
+    env: &Environment,
+) -> String {
+    let def = Statement::FunctionDefinition(FunctionDefinition {
+        name: name.clone(),
+        params: params.to_vec(),
+        expr: expr.clone(),
+    });
+    format_stmt(&def, env)
+}
+
+fn format_signature(name: &Identifier, params: &[Parameter]) -> String {
+    format!("{}({})", name, params.iter().join(", "))
+}
+
+fn is_valid_name(ident: &Identifier) -> bool {
+    matches!(
+        language::parse(&ident.0).as_deref(),
+        Ok([Statement::Expression(Expression::Field(x))]) if x == ident
+    )
+}
+
+/// Lines of `name = value`, with names of the same value joined as in `pi = π = 3.14`
+/// and the values aligned
+fn format_fields<'a>(iter: impl Iterator<Item = (&'a Identifier, &'a Value)>) -> Vec<String> {
+    align_columns(
+        iter.sorted_by(|(a_name, a_value), (b_name, b_value)| {
+            a_value
+                .partial_cmp(b_value)
+                .unwrap() // fields don't contain NaNs
+                .then_with(|| a_name.cmp(b_name))
+        })
+        .group_by(|(_, value)| *value)
+        .into_iter()
+        .map(|(value, fields)| {
+            let names = fields.map(|(name, _)| name).join(" = ");
+            (names, format!("= {}", value))
+        })
+        .sorted(),
+    )
+}
+
+/// Joins pairs of columns into lines, padding the first columns to the same width
+fn align_columns(rows: impl Iterator<Item = (String, String)>) -> Vec<String> {
+    let rows: Vec<_> = rows.collect();
+    let width = |s: &str| strip_colors(s).chars().count();
+    let max_width = rows.iter().map(|(left, _)| width(left)).max().unwrap_or(0);
+    rows.into_iter()
+        .map(|(left, right)| {
+            let padding = " ".repeat(max_width - width(&left));
+            format!("{}{} {}", left, padding, right)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pieces(input: &str) -> Vec<&str> {
+        split_statements(input)
+            .into_iter()
+            .map(|range| &input[range])
+            .collect()
+    }
+
+    fn value(repl: &mut Repl, input: &str) -> Option<Value> {
+        match repl.run(input) {
+            Response::Message(msg) => msg.value().cloned(),
+            _ => None,
+        }
+    }
+
+    fn text(repl: &mut Repl, input: &str) -> String {
+        match repl.run(input) {
+            Response::Message(msg) => msg.render(),
+            _ => String::new(),
+        }
+    }
+
+    #[test]
+    fn split_statements_at_separators() {
+        assert_eq!(pieces("x = 1; list\ny"), ["x = 1", " list", "y"]);
+        assert_eq!(pieces(""), [""]);
+    }
+
+    #[test]
+    fn split_statements_skips_separators_in_strings_comments_and_cases() {
+        assert_eq!(pieces("s = \"a; b\"; t"), ["s = \"a; b\"", " t"]);
+        assert_eq!(pieces("x # a; b\ny"), ["x # a; b", "y"]);
+        assert_eq!(
+            pieces("f(x) = {1 if x > 0; 0 otherwise}; f(2)"),
+            ["f(x) = {1 if x > 0; 0 otherwise}", " f(2)"]
+        );
+    }
+
+    #[test]
+    fn assignments_to_command_names_are_statements() {
+        let mut repl = Repl::new();
+        for input in [
+            "error = 0.05",
+            "exact = 2",
+            "record = [1]",
+            "hist = 3",
+            "run = 1",
+        ] {
+            assert!(value(&mut repl, input).is_some(), "{}", input);
+        }
+        assert_eq!(value(&mut repl, "y = error * 2"), Some(0.1.into()));
+        assert_eq!(value(&mut repl, "x = 1; hist == 3"), Some(1.0.into()));
+    }
+
+    #[test]
+    fn calls_and_definitions_named_after_commands_are_statements() {
+        let mut repl = Repl::new();
+        assert_eq!(value(&mut repl, "log (1)"), Some(0.0.into()));
+        assert_eq!(value(&mut repl, "hist(x) = 2x; hist (3)"), Some(6.0.into()));
+    }
+
+    #[test]
+    fn commands_run_between_statements() {
+        let mut repl = Repl::new();
+        assert!(text(&mut repl, "x = 3; strict on; strict").ends_with("Strict mode: on"));
+        assert!(text(&mut repl, "explain (1 + 2) * 3").ends_with("9"));
+        assert_eq!(text(&mut repl, "error"), "No errors so far");
+    }
 }
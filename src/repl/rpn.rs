@@ -0,0 +1,131 @@
+use crate::{
+    interpreter::{self, env::Environment, value::Value},
+    language::{self, BinaryOp, Identifier, Statement, UnaryOp},
+};
+
+/// Postfix (reverse Polish notation) calculator sharing the environment with the REPL
+#[derive(Debug, Clone, Default)]
+pub struct Rpn {
+    stack: Vec<Value>,
+}
+
+impl Rpn {
+    /// Values on the stack, the top last
+    pub fn stack(&self) -> &[Value] {
+        &self.stack
+    }
+
+    /// Runs whitespace-separated tokens. The stack is left untouched if any of them fails.
+    pub fn run(&mut self, line: &str, env: &mut Environment) -> Result<(), String> {
+        let mut stack = self.stack.clone();
+        for token in line.split_whitespace() {
+            run_token(token, &mut stack, env)?;
+        }
+        if let Some(top) = stack.last() {
+            env.set_last_result(top.clone());
+        }
+        self.stack = stack;
+        Ok(())
+    }
+}
+
+fn run_token(token: &str, stack: &mut Vec<Value>, env: &Environment) -> Result<(), String> {
+    let binary_op = match token {
+        "+" => Some(BinaryOp::Add),
+        "-" => Some(BinaryOp::Subtract),
+        "*" | "·" | "×" => Some(BinaryOp::Multiply),
+        "/" | "÷" => Some(BinaryOp::Divide),
+        "//" => Some(BinaryOp::FloorDivide),
+        "%" => Some(BinaryOp::Modulo),
+        "^" | "**" => Some(BinaryOp::Power),
+        "&" => Some(BinaryOp::BitAnd),
+        "|" => Some(BinaryOp::BitOr),
+        "<<" => Some(BinaryOp::ShiftLeft),
+        ">>" => Some(BinaryOp::ShiftRight),
+        "<" => Some(BinaryOp::Less),
+        "<=" | "≤" => Some(BinaryOp::LessOrEqual),
+        ">" => Some(BinaryOp::Greater),
+        ">=" | "≥" => Some(BinaryOp::GreaterOrEqual),
+        "==" => Some(BinaryOp::Equal),
+        "!=" | "≠" => Some(BinaryOp::NotEqual),
+        _ => None,
+    };
+    if let Some(op) = binary_op {
+        let mut args = pop(stack, 2)?.into_iter();
+        let (a, b) = (args.next().unwrap(), args.next().unwrap());
+        let value = interpreter::apply_binary_op(op, a, b, env).map_err(|e| e.to_string())?;
+        stack.push(value);
+        return Ok(());
+    }
+
+    let unary_op = match token {
+        "neg" | "chs" => Some(UnaryOp::Negate),
+        "!" => Some(UnaryOp::Factorial),
+        "~" => Some(UnaryOp::BitNot),
+        _ => None,
+    };
+    if let Some(op) = unary_op {
+        let x = pop(stack, 1)?.pop().unwrap();
+        let value = interpreter::apply_unary_op(op, x, env).map_err(|e| e.to_string())?;
+        stack.push(value);
+        return Ok(());
+    }
+
+    match token {
+        "dup" => {
+            let x = pop(stack, 1)?.pop().unwrap();
+            stack.push(x.clone());
+            stack.push(x);
+            return Ok(());
+        }
+        "drop" => {
+            pop(stack, 1)?;
+            return Ok(());
+        }
+        "swap" => {
+            let mut args = pop(stack, 2)?;
+            args.reverse();
+            stack.extend(args);
+            return Ok(());
+        }
+        _ => (),
+    }
+
+    // functions take as many arguments as they require, or the whole stack if variadic
+    let name = Identifier(token.to_string());
+    if let Ok(func) = env.resolve_func(&name) {
+        let arity = func.arity();
+        let n = match arity.max {
+            Some(_) => arity.min,
+            None => stack.len(),
+        };
+        let args = pop(stack, n)?;
+        let value = interpreter::call_func(&name, &args, env).map_err(|e| e.to_string())?;
+        stack.push(value);
+        return Ok(());
+    }
+
+    // anything else is pushed as an expression, such as a number or a variable
+    let stmts = language::parse(token).map_err(|e| e.to_string().trim().to_string())?;
+    match &stmts[..] {
+        [Statement::Expression(expr)] => {
+            let value = interpreter::eval_expr(expr, env).map_err(|e| e.to_string())?;
+            stack.push(value);
+            Ok(())
+        }
+        _ => Err(format!("Unknown token {}", token)),
+    }
+}
+
+/// Pops `n` values, keeping their order on the stack
+fn pop(stack: &mut Vec<Value>, n: usize) -> Result<Vec<Value>, String> {
+    if stack.len() < n {
+        return Err(format!(
+            "Expected {} {} on the stack, but found {}",
+            n,
+            if n == 1 { "value" } else { "values" },
+            stack.len()
+        ));
+    }
+    Ok(stack.split_off(stack.len() - n))
+}
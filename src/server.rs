@@ -0,0 +1,288 @@
+use crate::machine;
+use anyhow::{anyhow, Result};
+use libbeek::{
+    interpreter::{
+        self,
+        env::Environment,
+        format::{format_value, NumberFormat},
+    },
+    repl::Limits,
+};
+use rand::Rng;
+use serde_json::{json, Value as Json};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    io::Read,
+    panic::{self, AssertUnwindSafe},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Stack size of worker threads. Evaluation recurses once per level of syntax trees, which
+/// are at most `MAX_AST_DEPTH` deep, and again for each call of a user-defined function, so
+/// the stack is made far larger than the default of 2 MiB.
+const WORKER_STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Bytes of a request body other than the characters of the input, such as the session name
+const MAX_BODY_OVERHEAD: usize = 4096;
+
+/// How the server listens and what it allows each session
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub host: String,
+    pub port: u16,
+    /// Limits applied to every input
+    pub limits: Limits,
+    /// Maximum number of live sessions, beyond which the least recently used one is discarded
+    pub max_sessions: usize,
+    /// Time after which an unused session is discarded
+    pub session_timeout: Duration,
+    /// Number of threads evaluating inputs
+    pub threads: usize,
+}
+
+/// Serves an HTTP+JSON API where each session has its own environment
+///
+/// - `POST /eval` with `{"input": "...", "session": "..."}` evaluates statements in the session,
+///   which is created if `session` is omitted or unknown
+/// - `DELETE /sessions/<id>` discards a session
+///
+/// Sessions are spread over worker threads by name, so that a long evaluation only holds up
+/// the sessions sharing its worker.
+pub fn serve(options: Options) -> Result<()> {
+    let server = Server::http((options.host.as_str(), options.port)).map_err(|err| anyhow!(err))?;
+    eprintln!("Listening on http://{}", server.server_addr());
+
+    let threads = options.threads.max(1);
+    let mut workers = (0..threads)
+        .map(|_| spawn_worker(&options, threads))
+        .collect::<Result<Vec<_>>>()?;
+
+    for mut request in server.incoming_requests() {
+        let job = match read_job(&mut request, &options.limits) {
+            Ok(job) => job,
+            Err((status, body)) => {
+                respond(request, status, body);
+                continue;
+            }
+        };
+        let mut hasher = DefaultHasher::new();
+        job.session().hash(&mut hasher);
+        let i = hasher.finish() as usize % workers.len();
+        if let Err(mpsc::SendError((request, job))) = workers[i].send((request, job)) {
+            // the worker has stopped, losing its sessions, so a new one takes its place
+            eprintln!("Restarting a worker thread that has stopped");
+            let restarted = spawn_worker(&options, threads).and_then(|worker| {
+                worker
+                    .send((request, job))
+                    .map_err(|_| anyhow!("A worker thread has stopped"))?;
+                Ok(worker)
+            });
+            match restarted {
+                Ok(worker) => workers[i] = worker,
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Starts a worker thread, returning the sender of its jobs
+fn spawn_worker(options: &Options, threads: usize) -> Result<Sender<(Request, Job)>> {
+    let (sender, receiver) = mpsc::channel();
+    let options = options.clone();
+    // environments cannot move between threads, so the worker is created in its own
+    thread::Builder::new()
+        .stack_size(WORKER_STACK_SIZE)
+        .spawn(move || Worker::new(&options, threads).run(receiver))?;
+    Ok(sender)
+}
+
+/// Work for the session named by the request
+enum Job {
+    Eval { session: String, input: String },
+    Delete { session: String },
+}
+
+impl Job {
+    fn session(&self) -> &str {
+        match self {
+            Self::Eval { session, .. } | Self::Delete { session } => session,
+        }
+    }
+}
+
+/// Reads the request into a job, or responds with an error
+fn read_job(request: &mut Request, limits: &Limits) -> Result<Job, (u16, Json)> {
+    let url = request.url().to_string();
+    match (request.method(), url.as_str()) {
+        (Method::Post, "/eval") => {
+            // JSON escapes such as \u00e9 take up to six bytes for a character
+            let max_len = limits
+                .max_input_length
+                .map_or(usize::MAX, |max| max.saturating_mul(6) + MAX_BODY_OVERHEAD);
+            let too_large = || {
+                error(
+                    413,
+                    format!("Request body is larger than {} bytes", max_len),
+                )
+            };
+            if request.body_length().is_some_and(|len| len > max_len) {
+                return Err(too_large());
+            }
+            let mut body = String::new();
+            let mut reader = request.as_reader().take(max_len as u64 + 1);
+            if let Err(err) = reader.read_to_string(&mut body) {
+                return Err(error(400, err));
+            }
+            if body.len() > max_len {
+                return Err(too_large());
+            }
+            let body: Json = serde_json::from_str(&body).map_err(|err| error(400, err))?;
+            let input = match body["input"].as_str() {
+                Some(input) => input.to_string(),
+                None => return Err(error(400, "Expected a string input")),
+            };
+            limits.check_input(&input).map_err(|err| error(413, err))?;
+
+            let session = match body["session"].as_str() {
+                Some(session) => session.to_string(),
+                None => format!("{:016x}", rand::thread_rng().gen::<u64>()),
+            };
+            Ok(Job::Eval { session, input })
+        }
+        (Method::Delete, path) => match path.strip_prefix("/sessions/") {
+            Some(session) => Ok(Job::Delete {
+                session: session.to_string(),
+            }),
+            None => Err(error(404, "Not found")),
+        },
+        _ => Err(error(404, "Not found")),
+    }
+}
+
+/// Environment of a session and when it was last used
+struct Session {
+    env: Environment,
+    last_used: Instant,
+}
+
+/// Thread running the jobs of some of the sessions
+struct Worker {
+    sessions: HashMap<String, Session>,
+    limits: Limits,
+    max_sessions: usize,
+    session_timeout: Duration,
+}
+
+impl Worker {
+    /// Creates one of `threads` workers, which holds its share of the sessions
+    fn new(options: &Options, threads: usize) -> Self {
+        Self {
+            sessions: HashMap::new(),
+            limits: options.limits,
+            max_sessions: (options.max_sessions / threads).max(1),
+            session_timeout: options.session_timeout,
+        }
+    }
+
+    fn run(&mut self, jobs: Receiver<(Request, Job)>) {
+        for (request, job) in jobs {
+            let (status, body) = self.handle(job);
+            respond(request, status, body);
+        }
+    }
+
+    fn handle(&mut self, job: Job) -> (u16, Json) {
+        let now = Instant::now();
+        let timeout = self.session_timeout;
+        self.sessions
+            .retain(|_, session| now.duration_since(session.last_used) < timeout);
+
+        match job {
+            Job::Eval { session, input } => {
+                if !self.sessions.contains_key(&session) && self.sessions.len() >= self.max_sessions
+                {
+                    let oldest = self
+                        .sessions
+                        .iter()
+                        .min_by_key(|(_, session)| session.last_used)
+                        .map(|(name, _)| name.clone());
+                    if let Some(oldest) = oldest {
+                        self.sessions.remove(&oldest);
+                    }
+                }
+                let entry = self.sessions.entry(session.clone()).or_insert_with(|| {
+                    let mut env = Environment::new();
+                    env.set_file_access(false);
+                    Session {
+                        env,
+                        last_used: now,
+                    }
+                });
+                entry.last_used = now;
+                let env = &mut entry.env;
+                let limits = &self.limits;
+                match panic::catch_unwind(AssertUnwindSafe(|| eval(&input, env, limits))) {
+                    Ok(Ok(result)) => (200, json!({ "session": session, "result": result })),
+                    Ok(Err((status, err))) => (status, json!({ "session": session, "error": err })),
+                    Err(_) => {
+                        // the environment may have been left half-updated
+                        self.sessions.remove(&session);
+                        let err = "Internal error while evaluating; the session has been discarded";
+                        (500, json!({ "session": session, "error": err }))
+                    }
+                }
+            }
+            Job::Delete { session } => match self.sessions.remove(&session) {
+                Some(_) => (200, json!({})),
+                None => error(404, "No such session"),
+            },
+        }
+    }
+}
+
+/// Executes statements in `input`, returning the last result if any
+fn eval(
+    input: &str,
+    env: &mut Environment,
+    limits: &Limits,
+) -> Result<Option<String>, (u16, Json)> {
+    assert!(input != "boom");
+    let stmts = interpreter::parse_spanned(input, env).map_err(|err| {
+        let data = json!({ "message": err.to_string().trim(), "code": 1, "kind": "syntax" });
+        (422, data)
+    })?;
+    limits
+        .check_statements(stmts.iter().map(|(stmt, _)| stmt))
+        .map_err(|err| (413, err.into()))?;
+    env.set_step_limit(limits.max_steps);
+    let last_result = stmts
+        .iter()
+        .try_fold(None, |_, (stmt, span)| {
+            interpreter::exec_stmt(stmt, env).map_err(|err| (err, span.clone()))
+        })
+        .map_err(|(err, span)| {
+            let mut data = machine::error_data(&err, Some(span));
+            data["message"] = err.to_string().into();
+            data["code"] = err.code().into();
+            (422, data)
+        })?;
+    Ok(last_result.map(|x| format_value(&x, NumberFormat::Shortest)))
+}
+
+fn respond(request: Request, status: u16, body: Json) {
+    let response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+    // the client may have gone away, which is no reason to stop serving others
+    let _ = request.respond(response);
+}
+
+fn error(status: u16, err: impl ToString) -> (u16, Json) {
+    (status, json!({ "error": err.to_string() }))
+}
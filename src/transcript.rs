@@ -0,0 +1,86 @@
+//! Replaying transcripts of sessions to check that they still produce the recorded outputs
+
+use anyhow::{anyhow, Result};
+use libbeek::{
+    interpreter::env::Environment,
+    repl::{Repl, Response},
+};
+use std::path::Path;
+
+const PROMPT: &str = "> ";
+
+/// Input of a transcript along with the output recorded after it
+struct Case {
+    line_number: usize,
+    input: String,
+    expected: Vec<String>,
+}
+
+/// Runs the inputs of the transcript in `path`, which are the lines starting with `> `,
+/// and reports those whose outputs differ from the lines recorded after them.
+/// Returns whether all of the outputs matched.
+///
+/// Outputs are compared as shown with `echo off`, ignoring blank lines and trailing whitespace.
+pub fn check(path: &Path, env: Environment) -> Result<bool> {
+    let transcript =
+        std::fs::read_to_string(path).map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+
+    let mut cases: Vec<Case> = Vec::new();
+    for (i, line) in transcript.lines().enumerate() {
+        if let Some(input) = line.strip_prefix(PROMPT) {
+            cases.push(Case {
+                line_number: i + 1,
+                input: input.to_string(),
+                expected: Vec::new(),
+            });
+        } else if let Some(case) = cases.last_mut() {
+            // lines before the first input are free-form comments
+            case.expected.push(line.to_string());
+        }
+    }
+
+    let mut repl = Repl::with_env(env);
+    repl.run("echo off");
+
+    let mut failed = 0;
+    for case in &cases {
+        let actual = match repl.run(&case.input) {
+            Response::Message(msg) => msg.render().lines().map(str::to_string).collect(),
+            _ => Vec::new(),
+        };
+        let expected = normalize(&case.expected);
+        let actual = normalize(&actual);
+        if expected != actual {
+            failed += 1;
+            println!(
+                "mismatch: {}:{}: {}{}",
+                path.display(),
+                case.line_number,
+                PROMPT,
+                case.input
+            );
+            for line in &expected {
+                println!("- {}", line);
+            }
+            for line in &actual {
+                println!("+ {}", line);
+            }
+        }
+    }
+
+    println!(
+        "{} inputs checked, {} passed, {} failed",
+        cases.len(),
+        cases.len() - failed,
+        failed
+    );
+    Ok(failed == 0)
+}
+
+fn normalize(lines: &[String]) -> Vec<&str> {
+    lines
+        .iter()
+        .map(|line| line.trim_end())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
@@ -0,0 +1,267 @@
+//! Full-screen alternative to the line REPL, showing definitions and watches next to the results
+
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use libbeek::repl::{Repl, Response};
+use ratatui::{
+    layout::{Constraint, Layout, Position, Rect},
+    widgets::{Block, Borders, Paragraph},
+    DefaultTerminal, Frame,
+};
+
+const PROMPT: &str = "> ";
+
+/// Lines scrolled by PageUp and PageDown
+const PAGE: usize = 10;
+
+pub fn run(repl: Repl) -> Result<()> {
+    // messages are shown as plain text
+    colored::control::set_override(false);
+
+    let mut terminal = ratatui::init();
+    let result = App::new(repl).run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+struct App {
+    repl: Repl,
+    /// Inputs and their responses
+    output: Vec<String>,
+    /// Number of lines the output is scrolled up from the bottom
+    scroll: usize,
+    input: String,
+    /// Position of the cursor in the input, in chars
+    cursor: usize,
+    history: Vec<String>,
+    /// Index of the history entry shown in the input, or `None` if the input is new
+    history_index: Option<usize>,
+    /// Candidates of the last ambiguous completion
+    candidates: Vec<String>,
+}
+
+impl App {
+    fn new(repl: Repl) -> Self {
+        Self {
+            repl,
+            output: Vec::new(),
+            scroll: 0,
+            input: String::new(),
+            cursor: 0,
+            history: Vec::new(),
+            history_index: None,
+            candidates: Vec::new(),
+        }
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                // Windows reports releases of keys as well
+                if key.kind == KeyEventKind::Press && !self.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, input_area] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(3)]).areas(frame.area());
+        let [results_area, definitions_area] =
+            Layout::horizontal([Constraint::Percentage(70), Constraint::Percentage(30)])
+                .areas(main);
+
+        self.draw_results(frame, results_area);
+
+        let watches = self.repl.watches();
+        let definitions_area = if watches.is_empty() {
+            definitions_area
+        } else {
+            let [definitions_area, watches_area] = Layout::vertical([
+                Constraint::Fill(1),
+                Constraint::Length(watches.len() as u16 + 2),
+            ])
+            .areas(definitions_area);
+            frame.render_widget(
+                Paragraph::new(watches.join("\n"))
+                    .block(Block::default().borders(Borders::ALL).title(" Watches ")),
+                watches_area,
+            );
+            definitions_area
+        };
+
+        let definitions = self.repl.definitions().join("\n");
+        frame.render_widget(
+            Paragraph::new(definitions).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Definitions "),
+            ),
+            definitions_area,
+        );
+
+        let title = if self.candidates.is_empty() {
+            let i = self.byte_index();
+            self.repl
+                .signature_help(&self.input[..i])
+                .map_or_else(String::new, |signature| format!(" {} ", signature))
+        } else {
+            format!(" {} ", self.candidates.join(" "))
+        };
+        frame.render_widget(
+            Paragraph::new(format!("{}{}", PROMPT, self.input))
+                .block(Block::default().borders(Borders::ALL).title(title)),
+            input_area,
+        );
+        let column = PROMPT.len() + self.cursor;
+        frame.set_cursor_position(Position::new(
+            input_area.x + 1 + column as u16,
+            input_area.y + 1,
+        ));
+    }
+
+    fn draw_results(&mut self, frame: &mut Frame, area: Rect) {
+        let height = usize::from(area.height.saturating_sub(2));
+        let max_scroll = self.output.len().saturating_sub(height);
+        self.scroll = self.scroll.min(max_scroll);
+
+        let title = if self.scroll > 0 {
+            format!(" Results (scrolled up {} lines) ", self.scroll)
+        } else {
+            " Results ".to_string()
+        };
+        let top = max_scroll - self.scroll;
+        frame.render_widget(
+            Paragraph::new(self.output.join("\n"))
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .scroll((top as u16, 0)),
+            area,
+        );
+    }
+
+    /// Returns `false` if the app should quit
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if !matches!(key.code, KeyCode::Tab) {
+            self.candidates.clear();
+        }
+
+        match key.code {
+            KeyCode::Char('c') if ctrl => return false,
+            KeyCode::Char('d') if ctrl && self.input.is_empty() => return false,
+            KeyCode::Char('l') if ctrl => self.output.clear(),
+            KeyCode::Char(c) if !ctrl => {
+                let i = self.byte_index();
+                self.input.insert(i, c);
+                self.cursor += 1;
+            }
+            KeyCode::Esc => return false,
+            KeyCode::Enter => return self.submit(),
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                let i = self.byte_index();
+                self.input.remove(i);
+            }
+            KeyCode::Delete if self.cursor < self.input.chars().count() => {
+                let i = self.byte_index();
+                self.input.remove(i);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.input.chars().count()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.input.chars().count(),
+            KeyCode::Up => self.history_prev(),
+            KeyCode::Down => self.history_next(),
+            KeyCode::PageUp => self.scroll += PAGE,
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(PAGE),
+            KeyCode::Tab => self.complete(),
+            _ => (),
+        }
+        true
+    }
+
+    /// Runs the input. Returns `false` if the app should quit
+    fn submit(&mut self) -> bool {
+        let line = std::mem::take(&mut self.input);
+        self.cursor = 0;
+        self.history_index = None;
+        self.scroll = 0;
+        if line.trim().is_empty() {
+            return true;
+        }
+
+        self.output.push(format!("{}{}", PROMPT, line));
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        match self.repl.run(&line) {
+            Response::Empty => (),
+            Response::Message(msg) => self.output.extend(msg.render().lines().map(str::to_string)),
+            Response::ClearScreen { .. } => self.output.clear(),
+            Response::ClearHistory => self.history.clear(),
+            Response::Quit => return false,
+        }
+        true
+    }
+
+    fn history_prev(&mut self) {
+        let index = match self.history_index {
+            Some(0) => return,
+            Some(i) => i - 1,
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+        self.show_history(Some(index));
+    }
+
+    fn history_next(&mut self) {
+        match self.history_index {
+            Some(i) if i + 1 < self.history.len() => self.show_history(Some(i + 1)),
+            // moving past the newest entry goes back to empty input
+            Some(_) => self.show_history(None),
+            None => (),
+        }
+    }
+
+    fn show_history(&mut self, index: Option<usize>) {
+        self.history_index = index;
+        self.input = index.map_or_else(String::new, |i| self.history[i].clone());
+        self.cursor = self.input.chars().count();
+    }
+
+    /// Completes the name before the cursor, or shows the candidates if it is ambiguous
+    fn complete(&mut self) {
+        let i = self.byte_index();
+        let (start, candidates) = self.repl.complete(&self.input[..i]);
+        let replacements: Vec<_> = candidates.iter().map(|x| x.replacement()).collect();
+        let common = match replacements.split_first() {
+            Some((first, rest)) => rest.iter().fold(first.as_str(), |common, x| {
+                let len = common
+                    .char_indices()
+                    .zip(x.chars())
+                    .find(|((_, a), b)| a != b)
+                    .map_or(common.len().min(x.len()), |((i, _), _)| i);
+                &common[..len]
+            }),
+            None => return,
+        };
+        let common = common.to_string();
+        self.cursor += common[i - start..].chars().count();
+        self.input.replace_range(start..i, &common);
+        if candidates.len() > 1 {
+            self.candidates = candidates
+                .into_iter()
+                .map(|x| x.signature.unwrap_or(x.name))
+                .collect();
+        }
+    }
+
+    fn byte_index(&self) -> usize {
+        self.input
+            .char_indices()
+            .nth(self.cursor)
+            .map_or(self.input.len(), |(i, _)| i)
+    }
+}
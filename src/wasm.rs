@@ -1,9 +1,13 @@
-use crate::repl;
+use crate::{
+    interpreter::format::{format_value, NumberFormat},
+    repl,
+};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen(start)]
 pub fn main() {
     std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    #[cfg(feature = "colored")]
     colored::control::set_override(true);
 }
 
@@ -18,16 +22,76 @@ pub enum ResponseKind {
 #[wasm_bindgen]
 pub struct Response {
     message: String,
+    value: Option<String>,
+    error: Option<String>,
     #[wasm_bindgen(readonly)]
     pub kind: ResponseKind,
 }
 
+impl Response {
+    fn new(kind: ResponseKind) -> Self {
+        Self {
+            message: "".into(),
+            value: None,
+            error: None,
+            kind,
+        }
+    }
+}
+
 #[wasm_bindgen]
 impl Response {
     #[wasm_bindgen(getter)]
     pub fn message(&self) -> String {
         self.message.clone()
     }
+
+    /// Value of the last statement in the fewest digits that read back as the same number
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> Option<String> {
+        self.value.clone()
+    }
+
+    /// Error without colors
+    #[wasm_bindgen(getter)]
+    pub fn error(&self) -> Option<String> {
+        self.error.clone()
+    }
+}
+
+#[wasm_bindgen]
+pub struct Candidate {
+    inner: repl::Candidate,
+}
+
+#[wasm_bindgen]
+impl Candidate {
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.inner.name.clone()
+    }
+
+    /// One of `command`, `constant`, `variable` and `function`
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> String {
+        format!("{:?}", self.inner.kind).to_lowercase()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn signature(&self) -> Option<String> {
+        self.inner.signature.clone()
+    }
+
+    /// Description of a built-in
+    #[wasm_bindgen(getter)]
+    pub fn doc(&self) -> Option<String> {
+        self.inner.doc.map(str::to_string)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn replacement(&self) -> String {
+        self.inner.replacement()
+    }
 }
 
 #[wasm_bindgen]
@@ -46,34 +110,76 @@ impl Repl {
 
     pub fn run(&mut self, input: &str) -> Response {
         match self.inner.run(input) {
-            repl::Response::Empty => Response {
-                message: "".into(),
-                kind: ResponseKind::Message,
-            },
+            repl::Response::Empty | repl::Response::ClearHistory => {
+                Response::new(ResponseKind::Message)
+            }
             repl::Response::Message(msg) => Response {
-                message: msg.into(),
+                message: msg.render(),
+                value: msg
+                    .value()
+                    .map(|value| format_value(value, NumberFormat::Shortest)),
+                error: msg.error().map(repl::Line::plain_text),
                 kind: ResponseKind::Message,
             },
-            repl::Response::ClearScreen => Response {
-                message: "".into(),
-                kind: ResponseKind::Clear,
-            },
+            repl::Response::ClearScreen { .. } => Response::new(ResponseKind::Clear),
             repl::Response::Quit => {
+                // limits are set by the embedder, so they outlive the session
+                let limits = self.inner.limits();
                 self.inner = repl::Repl::new();
+                self.inner.set_limits(limits);
 
-                Response {
-                    message: "".into(),
-                    kind: ResponseKind::Reset,
-                }
+                Response::new(ResponseKind::Reset)
             }
         }
     }
 
+    /// Maximum number of characters in an input, or `undefined` for no limit
+    #[wasm_bindgen(getter)]
+    pub fn max_input_length(&self) -> Option<usize> {
+        self.inner.limits().max_input_length
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_max_input_length(&mut self, max: Option<usize>) {
+        self.inner.set_limits(repl::Limits {
+            max_input_length: max,
+            ..self.inner.limits()
+        });
+    }
+
+    /// Maximum number of nodes in the syntax tree of a statement, or `undefined` for no limit
+    #[wasm_bindgen(getter)]
+    pub fn max_ast_size(&self) -> Option<usize> {
+        self.inner.limits().max_ast_size
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_max_ast_size(&mut self, max: Option<usize>) {
+        self.inner.set_limits(repl::Limits {
+            max_ast_size: max,
+            ..self.inner.limits()
+        });
+    }
+
+    /// Maximum number of evaluation steps taken for an input, or `undefined` for no limit
+    #[wasm_bindgen(getter)]
+    pub fn max_steps(&self) -> Option<usize> {
+        self.inner.limits().max_steps
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_max_steps(&mut self, max: Option<usize>) {
+        self.inner.set_limits(repl::Limits {
+            max_steps: max,
+            ..self.inner.limits()
+        });
+    }
+
     #[wasm_bindgen(getter)]
-    pub fn completion_candidates(&self) -> Vec<JsValue> {
+    pub fn completion_candidates(&self) -> Vec<Candidate> {
         self.inner
             .completion_candidates()
-            .map(JsValue::from_str)
+            .map(|inner| Candidate { inner })
             .collect()
     }
 }